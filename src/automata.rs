@@ -219,8 +219,7 @@ impl DeterministicFinalAutomata {
         // Find and return transition for the given state and symbol
         self.transitions
             .iter()
-            .filter(|(from_state, sym, _)| from_state == state && sym == symbol)
-            .next()
+            .find(|(from_state, sym, _)| from_state == state && sym == symbol)
             .cloned()
             .unwrap()
     }
@@ -0,0 +1,728 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/**
+    Problems found while validating a grammar
+    * UndefinedNonterminal A nonterminal that is used but has no productions
+    * Unreachable A nonterminal that cannot be reached from the start symbol
+    * NonGenerating A nonterminal that does not derive any terminal word
+ **/
+#[derive(Debug)]
+pub enum GrammarIssue {
+    UndefinedNonterminal(String),
+    Unreachable(String),
+    NonGenerating(String),
+}
+
+impl fmt::Display for GrammarIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarIssue::UndefinedNonterminal(symbol) => write!(f, "{} is used but never defined", symbol),
+            GrammarIssue::Unreachable(symbol) => write!(f, "{} is not reachable from the start symbol", symbol),
+            GrammarIssue::NonGenerating(symbol) => write!(f, "{} does not derive any terminal word", symbol),
+        }
+    }
+}
+
+/**
+    Struct to represent a parse tree
+    * symbol The symbol of the node
+    * children The children of the node, empty for terminals and ε
+ **/
+pub struct ParseTree {
+    pub symbol: String,
+    pub children: Vec<ParseTree>,
+}
+
+impl ParseTree {
+    /**
+        Function to print the parse tree, one node per line, indented by depth
+        * @param depth The depth of the current node
+     **/
+    pub fn print_tree(&self, depth: usize) {
+        println!("{}{}", "  ".repeat(depth), self.symbol);
+        for child in &self.children {
+            child.print_tree(depth + 1);
+        }
+    }
+}
+
+/**
+    Struct to represent the result of the CYK algorithm
+    * word The letters of the checked word
+    * table The triangular table, table[i][l - 1] derives the subword of length l starting at i
+    * parse_tree A parse tree of the word, None if the word is not derived
+ **/
+pub struct CykResult {
+    pub word: Vec<String>,
+    pub table: Vec<Vec<Vec<String>>>,
+    pub parse_tree: Option<ParseTree>,
+}
+
+impl CykResult {
+    pub fn is_accepted(&self) -> bool {
+        self.parse_tree.is_some()
+    }
+
+    /**
+        Function to print the triangular table, the longest subwords on top
+     **/
+    pub fn print_table(&self) {
+        let n = self.word.len();
+        for length in (1..=n).rev() {
+            let row: Vec<String> = (0..=(n - length))
+                .map(|i| {
+                    let cell = &self.table[i][length - 1];
+                    if cell.is_empty() { "∅".to_string() } else { format!("{{{}}}", cell.join(",")) }
+                })
+                .collect();
+            println!("{:>2}: {}", length, row.join(" "));
+        }
+        println!("    {}", self.word.join(" "));
+    }
+}
+
+/**
+    Struct to represent a Context-Free Grammar
+    * terminals The terminal symbols
+    * nonterminals The nonterminal symbols
+    * start_symbol The start symbol
+    * productions The productions, an empty right-hand side stands for ε
+ **/
+pub struct ContextFreeGrammar {
+    terminals: Vec<String>,
+    nonterminals: Vec<String>,
+    start_symbol: String,
+    productions: Vec<(String, Vec<String>)>,
+}
+
+/**
+    Implementation of ContextFreeGrammar
+ **/
+impl ContextFreeGrammar {
+    /**
+        Function to create a new instance of ContextFreeGrammar without productions
+        * @param terminals The terminal symbols
+        * @param nonterminals The nonterminal symbols
+        * @param start_symbol The start symbol
+        * @returns An instance of ContextFreeGrammar
+     **/
+    pub fn new(terminals: Vec<String>, nonterminals: Vec<String>, start_symbol: String) -> Self {
+        Self {
+            terminals,
+            nonterminals,
+            start_symbol,
+            productions: Vec::new(),
+        }
+    }
+
+    /**
+        Function to add a production to the grammar, duplicates are ignored
+        * @param left The nonterminal on the left-hand side
+        * @param right The symbols on the right-hand side, empty for ε
+     **/
+    pub fn add_production(&mut self, left: &str, right: Vec<String>) {
+        let production = (left.to_string(), right);
+        if !self.productions.contains(&production) {
+            self.productions.push(production);
+        }
+    }
+
+    pub fn terminals(&self) -> &[String] {
+        &self.terminals
+    }
+
+    pub fn nonterminals(&self) -> &[String] {
+        &self.nonterminals
+    }
+
+    pub fn start_symbol(&self) -> &str {
+        &self.start_symbol
+    }
+
+    pub fn productions(&self) -> &[(String, Vec<String>)] {
+        &self.productions
+    }
+
+    pub fn is_terminal(&self, symbol: &str) -> bool {
+        self.terminals.iter().any(|terminal| terminal == symbol)
+    }
+
+    /**
+        Function to print the grammar
+        * @param self The grammar to print
+     **/
+    pub fn print_grammar(&self) {
+        println!("The context-free grammar:");
+        println!("Terminals: {:?}", self.terminals);
+        println!("Nonterminals: {:?}", self.nonterminals);
+        println!("Start symbol: {}", self.start_symbol);
+        println!("Productions:");
+        print!("{}", self);
+    }
+
+    /**
+        Function to collect the nonterminals that derive at least one terminal word
+        * @returns The generating nonterminals
+     **/
+    pub fn generating_symbols(&self) -> HashSet<String> {
+        let mut generating: HashSet<String> = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (left, right) in &self.productions {
+                if generating.contains(left) {
+                    continue;
+                }
+                if right.iter().all(|symbol| self.is_terminal(symbol) || generating.contains(symbol)) {
+                    generating.insert(left.clone());
+                    changed = true;
+                }
+            }
+        }
+        generating
+    }
+
+    /**
+        Function to collect the symbols that appear in some sentential form derived from the start symbol
+        * @returns The reachable terminals and nonterminals
+     **/
+    pub fn reachable_symbols(&self) -> HashSet<String> {
+        let mut reachable: HashSet<String> = HashSet::new();
+        let mut to_visit = vec![self.start_symbol.clone()];
+        reachable.insert(self.start_symbol.clone());
+
+        while let Some(symbol) = to_visit.pop() {
+            for (left, right) in &self.productions {
+                if left != &symbol {
+                    continue;
+                }
+                for next in right {
+                    if reachable.insert(next.clone()) {
+                        to_visit.push(next.clone());
+                    }
+                }
+            }
+        }
+        reachable
+    }
+
+    /**
+        Function to validate the grammar
+        * @returns The issues found, in the order of the nonterminals
+     **/
+    pub fn validate(&self) -> Vec<GrammarIssue> {
+        let generating = self.generating_symbols();
+        let reachable = self.reachable_symbols();
+        let mut issues = Vec::new();
+
+        for nonterminal in &self.nonterminals {
+            if !self.productions.iter().any(|(left, _)| left == nonterminal) {
+                issues.push(GrammarIssue::UndefinedNonterminal(nonterminal.clone()));
+                continue;
+            }
+            if !reachable.contains(nonterminal) {
+                issues.push(GrammarIssue::Unreachable(nonterminal.clone()));
+            }
+            if !generating.contains(nonterminal) {
+                issues.push(GrammarIssue::NonGenerating(nonterminal.clone()));
+            }
+        }
+        issues
+    }
+
+    pub fn print_issues(&self) {
+        let issues = self.validate();
+        if issues.is_empty() {
+            println!("The grammar has no issues");
+            return;
+        }
+
+        println!("Issues found in the grammar:");
+        for issue in issues {
+            println!("- {}", issue);
+        }
+    }
+
+    /**
+        Function to remove the useless symbols of the grammar
+        * First the non-generating nonterminals are removed, then the unreachable symbols
+        * The order matters: removing non-generating symbols can make other symbols unreachable
+     **/
+    pub fn remove_useless_symbols(&mut self) {
+        let generating = self.generating_symbols();
+        self.productions.retain(|(left, right)| {
+            generating.contains(left)
+                && right.iter().all(|symbol| self.terminals.contains(symbol) || generating.contains(symbol))
+        });
+
+        let reachable = self.reachable_symbols();
+        self.productions.retain(|(left, _)| reachable.contains(left));
+        let start_symbol = self.start_symbol.clone();
+        self.nonterminals.retain(|symbol| {
+            symbol == &start_symbol || (generating.contains(symbol) && reachable.contains(symbol))
+        });
+        self.terminals.retain(|symbol| reachable.contains(symbol));
+    }
+    // Returns a nonterminal name based on `base` that is not used yet
+    fn fresh_nonterminal(&self, base: &str) -> String {
+        let mut name = base.to_string();
+        while self.nonterminals.contains(&name) || self.terminals.contains(&name) {
+            name.push('\'');
+        }
+        name
+    }
+
+    /**
+        Function to collect the nonterminals that derive the empty word
+        * @returns The nullable nonterminals
+     **/
+    pub fn nullable_symbols(&self) -> HashSet<String> {
+        let mut nullable: HashSet<String> = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (left, right) in &self.productions {
+                if !nullable.contains(left) && right.iter().all(|symbol| nullable.contains(symbol)) {
+                    nullable.insert(left.clone());
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    /**
+        Function to convert the grammar into Chomsky normal form
+        * Every production of the result is A -> B C or A -> a, only the start symbol may derive ε
+        * The steps are the following:
+        * 1. New start symbol, so the start symbol never appears on a right-hand side
+        * 2. Terminals inside long right-hand sides are replaced by new nonterminals
+        * 3. Right-hand sides longer than two are split into a chain of nonterminals
+        * 4. ε-productions are eliminated
+        * 5. Unit productions are eliminated
+        * 6. Useless symbols are removed
+        * @returns The grammar in Chomsky normal form
+     **/
+    pub fn to_chomsky_normal_form(&self) -> ContextFreeGrammar {
+        let mut grammar = ContextFreeGrammar::new(self.terminals.clone(), self.nonterminals.clone(), self.start_symbol.clone());
+        grammar.productions = self.productions.clone();
+
+        // Step 1: new start symbol
+        let start_symbol = grammar.fresh_nonterminal(&format!("{}0", self.start_symbol));
+        grammar.nonterminals.insert(0, start_symbol.clone());
+        grammar.productions.insert(0, (start_symbol.clone(), vec![self.start_symbol.clone()]));
+        grammar.start_symbol = start_symbol.clone();
+
+        // Step 2: A -> a B becomes A -> T_a B, T_a -> a
+        let mut terminal_symbols: HashMap<String, String> = HashMap::new();
+        let mut productions = Vec::new();
+        for (left, right) in grammar.productions.clone() {
+            if right.len() < 2 {
+                productions.push((left, right));
+                continue;
+            }
+            let mut new_right = Vec::new();
+            for symbol in right {
+                if !grammar.is_terminal(&symbol) {
+                    new_right.push(symbol);
+                    continue;
+                }
+                let nonterminal = match terminal_symbols.get(&symbol) {
+                    Some(nonterminal) => nonterminal.clone(),
+                    None => {
+                        let nonterminal = grammar.fresh_nonterminal(&format!("T_{}", symbol));
+                        grammar.nonterminals.push(nonterminal.clone());
+                        productions.push((nonterminal.clone(), vec![symbol.clone()]));
+                        terminal_symbols.insert(symbol, nonterminal.clone());
+                        nonterminal
+                    }
+                };
+                new_right.push(nonterminal);
+            }
+            productions.push((left, new_right));
+        }
+        grammar.productions = productions;
+
+        // Step 3: A -> X1 X2 X3 becomes A -> X1 A_1, A_1 -> X2 X3
+        let mut productions = Vec::new();
+        for (left, right) in grammar.productions.clone() {
+            if right.len() <= 2 {
+                productions.push((left, right));
+                continue;
+            }
+            let mut current = left.clone();
+            for (index, symbol) in right[..right.len() - 2].iter().enumerate() {
+                let next = grammar.fresh_nonterminal(&format!("{}_{}", left, index + 1));
+                grammar.nonterminals.push(next.clone());
+                productions.push((current, vec![symbol.clone(), next.clone()]));
+                current = next;
+            }
+            productions.push((current, right[right.len() - 2..].to_vec()));
+        }
+        grammar.productions = productions;
+
+        // Step 4: every nullable symbol may be left out, then the ε-productions are dropped
+        let nullable = grammar.nullable_symbols();
+        let mut productions: Vec<(String, Vec<String>)> = Vec::new();
+        for (left, right) in &grammar.productions {
+            let mut variants: Vec<Vec<String>> = vec![Vec::new()];
+            for symbol in right {
+                let mut next_variants = Vec::new();
+                for variant in &variants {
+                    let mut with_symbol = variant.clone();
+                    with_symbol.push(symbol.clone());
+                    next_variants.push(with_symbol);
+                    if nullable.contains(symbol) {
+                        next_variants.push(variant.clone());
+                    }
+                }
+                variants = next_variants;
+            }
+            for variant in variants {
+                let production = (left.clone(), variant);
+                if !production.1.is_empty() && !productions.contains(&production) {
+                    productions.push(production);
+                }
+            }
+        }
+        if nullable.contains(&self.start_symbol) {
+            productions.push((start_symbol.clone(), Vec::new()));
+        }
+        grammar.productions = productions;
+
+        // Step 5: A -> B is replaced by A -> w for every non-unit B -> w reachable through unit productions
+        let is_unit = |right: &Vec<String>, grammar: &ContextFreeGrammar| right.len() == 1 && !grammar.is_terminal(&right[0]);
+        let mut productions: Vec<(String, Vec<String>)> = Vec::new();
+        for nonterminal in &grammar.nonterminals {
+            let mut unit_reachable = vec![nonterminal.clone()];
+            let mut index = 0;
+            while index < unit_reachable.len() {
+                let current = unit_reachable[index].clone();
+                for (left, right) in &grammar.productions {
+                    if left == &current && is_unit(right, &grammar) && !unit_reachable.contains(&right[0]) {
+                        unit_reachable.push(right[0].clone());
+                    }
+                }
+                index += 1;
+            }
+
+            for current in &unit_reachable {
+                for (left, right) in &grammar.productions {
+                    if left != current || is_unit(right, &grammar) {
+                        continue;
+                    }
+                    let production = (nonterminal.clone(), right.clone());
+                    if !productions.contains(&production) {
+                        productions.push(production);
+                    }
+                }
+            }
+        }
+        grammar.productions = productions;
+
+        // Step 6
+        grammar.remove_useless_symbols();
+        grammar
+    }
+
+    /**
+        Function to decide if a word is derived by the grammar with the CYK algorithm
+        * The grammar has to be in Chomsky normal form, see to_chomsky_normal_form
+        * table[i][l - 1] holds the nonterminals that derive the subword of length l starting at i
+        * @param word The word to check, every character is one terminal
+        * @returns The filled table and a parse tree if the word is derived
+     **/
+    pub fn cyk(&self, word: &str) -> CykResult {
+        let letters: Vec<String> = word.chars().map(|letter| letter.to_string()).collect();
+        let n = letters.len();
+
+        if n == 0 {
+            let accepted = self.productions.iter().any(|(left, right)| left == &self.start_symbol && right.is_empty());
+            let parse_tree = accepted.then(|| ParseTree { symbol: self.start_symbol.clone(), children: Vec::new() });
+            return CykResult { word: letters, table: Vec::new(), parse_tree };
+        }
+
+        // For every (start, length, nonterminal) the production and split point that produced it
+        let mut table: Vec<Vec<Vec<String>>> = vec![vec![Vec::new(); n]; n];
+        let mut back: HashMap<(usize, usize, String), (Vec<String>, usize)> = HashMap::new();
+
+        for (i, letter) in letters.iter().enumerate() {
+            for (left, right) in &self.productions {
+                if right.len() == 1 && &right[0] == letter && !table[i][0].contains(left) {
+                    table[i][0].push(left.clone());
+                    back.insert((i, 1, left.clone()), (right.clone(), 0));
+                }
+            }
+        }
+
+        for length in 2..=n {
+            for i in 0..=(n - length) {
+                for split in 1..length {
+                    for (left, right) in &self.productions {
+                        if right.len() != 2 || table[i][length - 1].contains(left) {
+                            continue;
+                        }
+                        if table[i][split - 1].contains(&right[0]) && table[i + split][length - split - 1].contains(&right[1]) {
+                            table[i][length - 1].push(left.clone());
+                            back.insert((i, length, left.clone()), (right.clone(), split));
+                        }
+                    }
+                }
+            }
+        }
+
+        let parse_tree = if table[0][n - 1].contains(&self.start_symbol) {
+            Some(Self::build_parse_tree(&back, 0, n, &self.start_symbol))
+        } else {
+            None
+        };
+
+        CykResult { word: letters, table, parse_tree }
+    }
+
+    fn build_parse_tree(back: &HashMap<(usize, usize, String), (Vec<String>, usize)>, start: usize,
+                        length: usize, symbol: &str) -> ParseTree {
+        let (right, split) = &back[&(start, length, symbol.to_string())];
+        let children = if length == 1 {
+            vec![ParseTree { symbol: right[0].clone(), children: Vec::new() }]
+        } else {
+            vec![
+                Self::build_parse_tree(back, start, *split, &right[0]),
+                Self::build_parse_tree(back, start + split, length - split, &right[1]),
+            ]
+        };
+        ParseTree { symbol: symbol.to_string(), children }
+    }
+}
+
+/**
+    Writes the grammar one nonterminal per line, alternatives separated by |
+    * e.g. S -> a S b | ε
+ **/
+impl fmt::Display for ContextFreeGrammar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for nonterminal in &self.nonterminals {
+            let alternatives: Vec<String> = self.productions.iter()
+                .filter(|(left, _)| left == nonterminal)
+                .map(|(_, right)| if right.is_empty() { "ε".to_string() } else { right.join(" ") })
+                .collect();
+            if !alternatives.is_empty() {
+                writeln!(f, "{} -> {}", nonterminal, alternatives.join(" | "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+    Function to read a grammar from a file and create an instance of ContextFreeGrammar
+    * @param filename The name of the file to read from
+    * @returns An instance of ContextFreeGrammar
+
+    * The format of the file should be the following:
+    * Every line contains the productions of one nonterminal: S -> a S b | ε
+    * The symbols on the right-hand side are separated by spaces, ε stands for the empty word
+    * The left-hand side of the first line is the start symbol
+    * Symbols starting with an uppercase letter or [ are nonterminals, everything else is a terminal
+    * (the [p,X,q] nonterminals come from the stack automata conversion)
+    * Empty lines are skipped
+ **/
+pub fn read_grammar(filename: &str) -> ContextFreeGrammar {
+    let file = File::open(filename).expect("File not found!");
+    let reader = BufReader::new(file);
+
+    let mut rules: Vec<(String, Vec<String>)> = Vec::new();
+    for line in reader.lines().map(|l| l.expect("Error reading line")) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (left, right) = line.split_once("->").expect("Missing -> in production");
+        let left = left.trim().to_string();
+        for alternative in right.split('|') {
+            let symbols: Vec<String> = alternative
+                .split_whitespace()
+                .filter(|symbol| *symbol != "ε")
+                .map(String::from)
+                .collect();
+            rules.push((left.clone(), symbols));
+        }
+    }
+
+    // Nonterminals in order of appearance: defined ones first, then the ones only used
+    let mut nonterminals: Vec<String> = Vec::new();
+    let mut terminals: Vec<String> = Vec::new();
+    for (left, _) in &rules {
+        if !nonterminals.contains(left) {
+            nonterminals.push(left.clone());
+        }
+    }
+    for symbol in rules.iter().flat_map(|(_, right)| right) {
+        let is_nonterminal = symbol.chars().next().is_some_and(|c| c.is_uppercase() || c == '[');
+        if is_nonterminal && !nonterminals.contains(symbol) {
+            nonterminals.push(symbol.clone());
+        } else if !is_nonterminal && !terminals.contains(symbol) {
+            terminals.push(symbol.clone());
+        }
+    }
+
+    let start_symbol = rules.first().expect("The grammar has no productions").0.clone();
+    let mut grammar = ContextFreeGrammar::new(terminals, nonterminals, start_symbol);
+    for (left, right) in rules {
+        grammar.add_production(&left, right);
+    }
+
+    grammar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(symbols: &[&str]) -> Vec<String> {
+        symbols.iter().map(|symbol| symbol.to_string()).collect()
+    }
+
+    fn has_production(grammar: &ContextFreeGrammar, left: &str, right: &[&str]) -> bool {
+        grammar.productions().contains(&(left.to_string(), strings(right)))
+    }
+
+    fn leaves(tree: &ParseTree) -> String {
+        if tree.children.is_empty() {
+            return tree.symbol.clone();
+        }
+        tree.children.iter().map(leaves).collect()
+    }
+
+    // Every production is A -> B C or A -> a, only the start symbol derives ε and it is never on a right-hand side
+    fn assert_chomsky_normal_form(grammar: &ContextFreeGrammar) {
+        for (left, right) in grammar.productions() {
+            let valid = match right.len() {
+                0 => left == grammar.start_symbol(),
+                1 => grammar.is_terminal(&right[0]),
+                2 => right.iter().all(|symbol| !grammar.is_terminal(symbol) && symbol != grammar.start_symbol()),
+                _ => false,
+            };
+            assert!(valid, "{} -> {:?}", left, right);
+        }
+    }
+
+    #[test]
+    fn validation_finds_every_kind_of_issue() {
+        // S -> A B | a, A -> a A, B -> b | C, D -> d
+        let issues: Vec<String> = read_grammar("src/resources/cfg_2.txt").validate().iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, [
+            "A does not derive any terminal word",
+            "D is not reachable from the start symbol",
+            "C is used but never defined",
+        ]);
+        assert!(read_grammar("src/resources/cfg_1.txt").validate().is_empty());
+    }
+
+    #[test]
+    fn useless_symbols_are_removed_non_generating_first() {
+        // Only S -> a is left: without A, the production S -> A B goes and B becomes unreachable
+        let mut grammar = read_grammar("src/resources/cfg_2.txt");
+        grammar.remove_useless_symbols();
+        assert_eq!(grammar.productions(), [("S".to_string(), strings(&["a"]))]);
+        assert_eq!(grammar.nonterminals(), ["S"]);
+        assert_eq!(grammar.terminals(), ["a"]);
+    }
+
+    #[test]
+    fn every_step_of_the_normal_form_is_applied() {
+        // S -> a S b | ε
+        let grammar = read_grammar("src/resources/cfg_1.txt").to_chomsky_normal_form();
+        assert_chomsky_normal_form(&grammar);
+
+        // START: a new start symbol, which keeps the ε of the old one (DEL)
+        assert_eq!(grammar.start_symbol(), "S0");
+        assert!(has_production(&grammar, "S0", &[]));
+        assert!(!has_production(&grammar, "S", &[]));
+        // TERM: the terminals of long right-hand sides get their own nonterminals
+        assert!(has_production(&grammar, "T_a", &["a"]) && has_production(&grammar, "T_b", &["b"]));
+        // BIN: a S b is split into T_a S_1, S_1 -> S T_b
+        assert!(has_production(&grammar, "S", &["T_a", "S_1"]) && has_production(&grammar, "S_1", &["S", "T_b"]));
+        // DEL and UNIT: leaving out the nullable S gives S_1 -> T_b, which is replaced by S_1 -> b;
+        // S0 -> S is replaced by the productions of S
+        assert!(has_production(&grammar, "S_1", &["b"]) && !has_production(&grammar, "S_1", &["T_b"]));
+        assert!(has_production(&grammar, "S0", &["T_a", "S_1"]) && !has_production(&grammar, "S0", &["S"]));
+    }
+
+    #[test]
+    fn unit_chains_and_useless_symbols_are_removed_from_the_normal_form() {
+        // S -> A | x y, A -> B, B -> b, C -> c (unreachable), D -> D d (non-generating)
+        let mut grammar = ContextFreeGrammar::new(strings(&["x", "y", "b", "c", "d"]), strings(&["S", "A", "B", "C", "D"]), "S".to_string());
+        grammar.add_production("S", strings(&["A"]));
+        grammar.add_production("S", strings(&["x", "y"]));
+        grammar.add_production("S", strings(&["D"]));
+        grammar.add_production("A", strings(&["B"]));
+        grammar.add_production("B", strings(&["b"]));
+        grammar.add_production("C", strings(&["c"]));
+        grammar.add_production("D", strings(&["D", "d"]));
+
+        let grammar = grammar.to_chomsky_normal_form();
+        assert_chomsky_normal_form(&grammar);
+        assert!(has_production(&grammar, "S0", &["b"]) && has_production(&grammar, "S0", &["T_x", "T_y"]));
+        assert!(!has_production(&grammar, "S0", &[]));
+        for useless in ["C", "D"] {
+            assert!(!grammar.nonterminals().iter().any(|nonterminal| nonterminal == useless), "{}", useless);
+        }
+        assert!(!grammar.terminals().iter().any(|terminal| terminal == "c" || terminal == "d"));
+    }
+
+    #[test]
+    fn cyk_table_and_parse_tree_of_an_accepted_word() {
+        let grammar = read_grammar("src/resources/cfg_1.txt").to_chomsky_normal_form();
+        let result = grammar.cyk("aabb");
+        assert!(result.is_accepted());
+        assert_eq!(result.table[0][0], ["T_a"]);
+        assert_eq!(result.table[3][0], ["T_b", "S_1"]);
+        // The inner ab is derived by S, the prefix aab by nothing
+        assert!(result.table[1][1].contains(&"S".to_string()));
+        assert!(result.table[0][2].is_empty());
+        assert!(result.table[0][3].contains(&"S0".to_string()));
+
+        let tree = result.parse_tree.unwrap();
+        assert_eq!(tree.symbol, "S0");
+        assert_eq!(leaves(&tree), "aabb");
+    }
+
+    #[test]
+    fn cyk_rejects_the_words_outside_the_language() {
+        let grammar = read_grammar("src/resources/cfg_1.txt").to_chomsky_normal_form();
+        for word in ["a", "ba", "aab", "abab"] {
+            let result = grammar.cyk(word);
+            assert!(!result.is_accepted(), "{}", word);
+            assert!(!result.table[0][word.len() - 1].contains(&"S0".to_string()), "{}", word);
+        }
+    }
+
+    #[test]
+    fn empty_word_is_accepted_only_by_grammars_deriving_it() {
+        let epsilon = read_grammar("src/resources/cfg_1.txt").to_chomsky_normal_form().cyk("");
+        assert!(epsilon.is_accepted());
+        assert!(epsilon.table.is_empty());
+        assert!(epsilon.parse_tree.unwrap().children.is_empty());
+        assert!(!read_grammar("src/resources/cfg_2.txt").to_chomsky_normal_form().cyk("").is_accepted());
+    }
+
+    #[test]
+    fn normal_form_derives_the_words_with_as_many_as_as_bs() {
+        // S -> a S b S | b S a S | ε
+        let grammar = read_grammar("src/resources/cfg_3.txt").to_chomsky_normal_form();
+        assert_chomsky_normal_form(&grammar);
+        for length in 0..=6 {
+            for bits in 0..(1 << length) {
+                let word: String = (0..length).map(|index| if bits >> index & 1 == 1 { 'a' } else { 'b' }).collect();
+                let result = grammar.cyk(&word);
+                assert_eq!(result.is_accepted(), word.matches('a').count() * 2 == length, "{}", word);
+                if let (Some(tree), false) = (result.parse_tree, word.is_empty()) {
+                    assert_eq!(leaves(&tree), word);
+                }
+            }
+        }
+    }
+}
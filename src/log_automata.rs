@@ -0,0 +1,885 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use crate::automata::DeterministicFinalAutomata;
+
+// Patterns with more states are rejected instead of filling the memory
+const MAX_STATES: usize = 5000;
+
+// Counted repetitions are expanded, e.g. a{3} becomes aaa
+const MAX_REPETITION: u32 = 100;
+
+/**
+    The symbols the automata reads
+    * Byte Every byte of the UTF-8 text is a symbol, . matches one byte
+    * Char Every character is a symbol, . matches one character
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alphabet {
+    Byte,
+    Char,
+}
+
+impl Alphabet {
+    fn max_unit(&self) -> u32 {
+        match self {
+            Alphabet::Byte => 0xFF,
+            Alphabet::Char => char::MAX as u32,
+        }
+    }
+}
+
+/**
+    Error found while compiling a pattern into an automata
+    * position The character position in the pattern
+    * message What went wrong, e.g. a feature the automata can't represent
+ **/
+#[derive(Debug, Clone)]
+pub struct PatternError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.position, self.message)
+    }
+}
+
+// Zero width assertions, checked while the automata moves between the symbols
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+// The syntax tree of a pattern; the sets are sorted ranges of symbols, both ends included
+#[derive(Debug, Clone)]
+pub enum Node {
+    Empty,
+    Set(Vec<(u32, u32)>),
+    Assert(Assertion),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat(Box<Node>, u32, Option<u32>),
+}
+
+fn is_word(unit: u32) -> bool {
+    char::from_u32(unit).is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Sorts and merges the ranges of a set
+fn normalize(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges.sort();
+    let mut merged: Vec<(u32, u32)> = Vec::new();
+    for (low, high) in ranges {
+        match merged.last_mut() {
+            Some(last) if low <= last.1.saturating_add(1) => last.1 = last.1.max(high),
+            _ => merged.push((low, high)),
+        }
+    }
+    merged
+}
+
+fn complement(ranges: &[(u32, u32)], max_unit: u32) -> Vec<(u32, u32)> {
+    let mut result = Vec::new();
+    let mut next = 0;
+    for &(low, high) in ranges {
+        if low > next {
+            result.push((next, low - 1));
+        }
+        next = high + 1;
+    }
+    if next <= max_unit {
+        result.push((next, max_unit));
+    }
+    result
+}
+
+// \d, \w and \s are ASCII, like the word boundaries
+fn class_escape(letter: char) -> Option<(Vec<(u32, u32)>, bool)> {
+    let ranges = match letter.to_ascii_lowercase() {
+        'd' => vec![('0' as u32, '9' as u32)],
+        'w' => vec![('0' as u32, '9' as u32), ('A' as u32, 'Z' as u32), ('_' as u32, '_' as u32), ('a' as u32, 'z' as u32)],
+        's' => vec![('\t' as u32, '\r' as u32), (' ' as u32, ' ' as u32)],
+        _ => return None,
+    };
+    Some((ranges, letter.is_ascii_uppercase()))
+}
+
+/**
+    Recursive descent parser of the supported subset of the regex syntax:
+    * literals, ., [classes], \d \w \s and their negations, \b \B, ^ $
+    * groups (...), (?:...), (?P<name>...), alternation |, and the quantifiers * + ? {n} {n,} {n,m}
+    * the case insensitive flag (?i) and (?i:...), for ASCII letters
+    * The first construct that may match other texts than the regex crate is kept in difference
+ **/
+struct PatternParser {
+    chars: Vec<char>,
+    index: usize,
+    alphabet: Alphabet,
+    case_insensitive: bool,
+    difference: Option<PatternError>,
+}
+
+impl PatternParser {
+    fn error<T>(&self, message: &str) -> Result<T, PatternError> {
+        Err(PatternError { position: self.index, message: message.to_string() })
+    }
+
+    fn differs(&mut self, position: usize, message: &str) {
+        if self.difference.is_none() {
+            self.difference = Some(PatternError { position, message: message.to_string() });
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, PatternError> {
+        let mut branches = vec![self.concatenation()?];
+        while self.eat('|') {
+            branches.push(self.concatenation()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alternate(branches) })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, PatternError> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifiers(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.index;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.index += 1;
+        }
+        self.chars[start..self.index].iter().collect::<String>().parse().ok()
+    }
+
+    fn quantifiers(&mut self, mut atom: Node) -> Result<Node, PatternError> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => {
+                    let start = self.index;
+                    self.index += 1;
+                    let Some(min) = self.number() else {
+                        self.index = start;
+                        return self.error("expected a number of repetitions");
+                    };
+                    let max = if self.eat(',') {
+                        if self.peek() == Some('}') { None } else { self.number() }
+                    } else {
+                        Some(min)
+                    };
+                    if self.peek() != Some('}') {
+                        return self.error("expected }");
+                    }
+                    if min > MAX_REPETITION || max.is_some_and(|max| max > MAX_REPETITION || max < min) {
+                        self.index = start;
+                        return self.error("invalid number of repetitions");
+                    }
+                    (min, max)
+                }
+                _ => return Ok(atom),
+            };
+            self.index += 1;
+            // A lazy quantifier changes which match is found, not whether there is one
+            self.eat('?');
+            atom = Node::Repeat(Box::new(atom), min, max);
+        }
+    }
+
+    fn literal(&self, c: char) -> Node {
+        if self.case_insensitive && c.is_ascii_alphabetic() {
+            return self.set(vec![(c as u32, c as u32)], false);
+        }
+        match self.alphabet {
+            Alphabet::Char => Node::Set(vec![(c as u32, c as u32)]),
+            Alphabet::Byte => {
+                let mut buffer = [0; 4];
+                let bytes: Vec<Node> = c.encode_utf8(&mut buffer).bytes()
+                    .map(|byte| Node::Set(vec![(byte as u32, byte as u32)]))
+                    .collect();
+                if bytes.len() == 1 { bytes.into_iter().next().unwrap() } else { Node::Concat(bytes) }
+            }
+        }
+    }
+
+    fn set(&self, mut ranges: Vec<(u32, u32)>, negated: bool) -> Node {
+        if self.case_insensitive {
+            // The other case of the ASCII letters in every range
+            let letters = [('A' as u32, 'Z' as u32, 'a' as u32), ('a' as u32, 'z' as u32, 'A' as u32)];
+            let mut other_case = Vec::new();
+            for &(low, high) in &ranges {
+                for (first, last, other) in letters {
+                    let (from, to) = (low.max(first), high.min(last));
+                    if from <= to {
+                        other_case.push((from - first + other, to - first + other));
+                    }
+                }
+            }
+            ranges.extend(other_case);
+        }
+        let ranges = normalize(ranges);
+        Node::Set(if negated { complement(&ranges, self.alphabet.max_unit()) } else { ranges })
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        let c = self.peek().unwrap();
+        self.index += 1;
+        match c {
+            '(' => {
+                // The flags set inside a group end with the group
+                let case_insensitive = self.case_insensitive;
+                if self.eat('?') {
+                    if self.eat('P') || self.peek() == Some('<') {
+                        if !self.eat('<') {
+                            return self.error("expected <");
+                        }
+                        while self.peek().is_some_and(|c| c != '>') {
+                            self.index += 1;
+                        }
+                        self.eat('>');
+                    } else if self.eat('i') {
+                        self.differs(self.index - 3, "(?i) folds the ASCII letters only, the regex crate folds every letter");
+                        self.case_insensitive = true;
+                        if self.eat(')') {
+                            return Ok(Node::Empty);
+                        } else if !self.eat(':') {
+                            return self.error("only the i flag is supported");
+                        }
+                    } else if !self.eat(':') {
+                        return self.error("flags and lookarounds are not supported");
+                    }
+                }
+                let node = self.alternation()?;
+                if !self.eat(')') {
+                    return self.error("expected )");
+                }
+                self.case_insensitive = case_insensitive;
+                Ok(node)
+            }
+            '[' => self.class(),
+            '.' => {
+                if self.alphabet == Alphabet::Byte {
+                    self.differs(self.index - 1, ". matches one byte, the regex crate matches one character");
+                }
+                Ok(self.set(vec![('\n' as u32, '\n' as u32)], true))
+            }
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '\\' => self.escape(),
+            '*' | '+' | '?' | '{' => {
+                self.index -= 1;
+                self.error("nothing to repeat")
+            }
+            c => Ok(self.literal(c)),
+        }
+    }
+
+    fn escaped_char(&mut self) -> Result<char, PatternError> {
+        let Some(c) = self.peek() else {
+            return self.error("expected an escaped character");
+        };
+        self.index += 1;
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            'x' => {
+                // \x7F or \x{1F600}
+                let braced = self.eat('{');
+                let start = self.index;
+                while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) && (braced || self.index < start + 2) {
+                    self.index += 1;
+                }
+                let digits: String = self.chars[start..self.index].iter().collect();
+                if braced && !self.eat('}') {
+                    return self.error("expected }");
+                }
+                u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+                    .map_or_else(|| self.error("invalid character code"), Ok)
+            }
+            c if c.is_ascii_punctuation() || c == ' ' => Ok(c),
+            _ => {
+                self.index -= 1;
+                self.error("unsupported escape")
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, PatternError> {
+        match self.peek() {
+            Some('b') => {
+                self.index += 1;
+                self.differs(self.index - 2, "\\b uses the ASCII word characters, the regex crate's are Unicode");
+                Ok(Node::Assert(Assertion::WordBoundary))
+            }
+            Some('B') => {
+                self.index += 1;
+                self.differs(self.index - 2, "\\B uses the ASCII word characters, the regex crate's are Unicode");
+                Ok(Node::Assert(Assertion::NotWordBoundary))
+            }
+            Some(letter) if class_escape(letter).is_some() => {
+                self.index += 1;
+                self.differs(self.index - 2, &format!("\\{} is ASCII, the regex crate's is Unicode", letter));
+                let (ranges, negated) = class_escape(letter).unwrap();
+                Ok(self.set(ranges, negated))
+            }
+            _ => {
+                let c = self.escaped_char()?;
+                Ok(self.literal(c))
+            }
+        }
+    }
+
+    fn class(&mut self) -> Result<Node, PatternError> {
+        let negated = self.eat('^');
+        if negated && self.alphabet == Alphabet::Byte {
+            self.differs(self.index - 2, "a negated class matches one byte, the regex crate matches one character");
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let Some(c) = self.peek() else {
+                return self.error("expected ]");
+            };
+            self.index += 1;
+            // A ] right after the [ is a literal
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = match c {
+                '[' if self.peek() == Some(':') => return self.error("named classes are not supported"),
+                '\\' => match self.peek() {
+                    Some(letter) if class_escape(letter).is_some() => {
+                        self.index += 1;
+                        self.differs(self.index - 2, &format!("\\{} is ASCII, the regex crate's is Unicode", letter));
+                        let (class, class_negated) = class_escape(letter).unwrap();
+                        if class_negated {
+                            ranges.extend(complement(&normalize(class), self.alphabet.max_unit()));
+                        } else {
+                            ranges.extend(class);
+                        }
+                        continue;
+                    }
+                    _ => self.escaped_char()?,
+                },
+                c => c,
+            };
+            if self.alphabet == Alphabet::Byte && !low.is_ascii() {
+                return self.error("non-ASCII characters in a class can't be matched byte by byte");
+            }
+            let is_range = self.peek() == Some('-') && self.chars.get(self.index + 1).is_some_and(|&next| next != ']');
+            if is_range {
+                self.index += 1;
+                let high = match self.peek() {
+                    Some('\\') => {
+                        self.index += 1;
+                        self.escaped_char()?
+                    }
+                    Some(high) => {
+                        self.index += 1;
+                        high
+                    }
+                    None => return self.error("expected ]"),
+                };
+                if high < low || (self.alphabet == Alphabet::Byte && !high.is_ascii()) {
+                    return self.error("invalid range");
+                }
+                ranges.push((low as u32, high as u32));
+            } else {
+                ranges.push((low as u32, low as u32));
+            }
+        }
+        Ok(self.set(ranges, negated))
+    }
+}
+
+/**
+    Function to parse a pattern into its syntax tree
+    * @param pattern The pattern, in the supported subset of the regex syntax
+    * @param alphabet Whether the sets of the tree are bytes or characters
+    * @returns The syntax tree, or where and why the pattern isn't supported
+ **/
+pub fn parse_pattern(pattern: &str, alphabet: Alphabet) -> Result<Node, PatternError> {
+    parse(pattern, alphabet).map(|(node, _)| node)
+}
+
+// The syntax tree with the first construct whose meaning differs from the regex crate's
+fn parse(pattern: &str, alphabet: Alphabet) -> Result<(Node, Option<PatternError>), PatternError> {
+    let mut parser = PatternParser { chars: pattern.chars().collect(), index: 0, alphabet, case_insensitive: false, difference: None };
+    let node = parser.alternation()?;
+    if parser.index < parser.chars.len() {
+        return parser.error("unmatched )");
+    }
+    Ok((node, parser.difference))
+}
+
+// The edges of the nondeterministic automata built from the syntax tree
+enum Edge {
+    Epsilon(usize),
+    Assert(Assertion, usize),
+    Set(Vec<(u32, u32)>, usize),
+}
+
+struct Nfa {
+    edges: Vec<Vec<Edge>>,
+}
+
+impl Nfa {
+    fn add_state(&mut self) -> usize {
+        self.edges.push(Vec::new());
+        self.edges.len() - 1
+    }
+
+    // Thompson's construction, returns the state after the node
+    fn add_node(&mut self, node: &Node, start: usize) -> usize {
+        match node {
+            Node::Empty => start,
+            Node::Set(ranges) => {
+                let end = self.add_state();
+                self.edges[start].push(Edge::Set(ranges.clone(), end));
+                end
+            }
+            Node::Assert(assertion) => {
+                let end = self.add_state();
+                self.edges[start].push(Edge::Assert(*assertion, end));
+                end
+            }
+            Node::Concat(nodes) => nodes.iter().fold(start, |state, node| self.add_node(node, state)),
+            Node::Alternate(branches) => {
+                let end = self.add_state();
+                for branch in branches {
+                    let branch_start = self.add_state();
+                    self.edges[start].push(Edge::Epsilon(branch_start));
+                    let branch_end = self.add_node(branch, branch_start);
+                    self.edges[branch_end].push(Edge::Epsilon(end));
+                }
+                end
+            }
+            Node::Repeat(node, min, max) => {
+                let mut state = start;
+                for _ in 0..*min {
+                    state = self.add_node(node, state);
+                }
+                match max {
+                    None => {
+                        let repeat = self.add_state();
+                        self.edges[state].push(Edge::Epsilon(repeat));
+                        let end = self.add_node(node, repeat);
+                        self.edges[end].push(Edge::Epsilon(repeat));
+                        repeat
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let next = self.add_state();
+                            self.edges[state].push(Edge::Epsilon(next));
+                            let end = self.add_node(node, state);
+                            self.edges[end].push(Edge::Epsilon(next));
+                            state = next;
+                        }
+                        state
+                    }
+                }
+            }
+        }
+    }
+
+    fn has_word_assertions(&self) -> bool {
+        self.edges.iter().flatten().any(|edge| matches!(edge, Edge::Assert(Assertion::WordBoundary | Assertion::NotWordBoundary, _)))
+    }
+}
+
+// Where the automata is between two symbols, to decide the assertions
+#[derive(Clone, Copy)]
+struct Position {
+    at_start: bool,
+    previous_word: bool,
+    next_word: Option<bool>,
+}
+
+impl Position {
+    fn holds(&self, assertion: Assertion) -> bool {
+        let boundary = self.previous_word != self.next_word.unwrap_or(false);
+        match assertion {
+            Assertion::Start => self.at_start,
+            Assertion::End => self.next_word.is_none(),
+            Assertion::WordBoundary => boundary,
+            Assertion::NotWordBoundary => !boundary,
+        }
+    }
+}
+
+fn closure(nfa: &Nfa, states: &[usize], position: Position) -> Vec<bool> {
+    let mut reached = vec![false; nfa.edges.len()];
+    let mut stack: Vec<usize> = states.to_vec();
+    while let Some(state) = stack.pop() {
+        if reached[state] {
+            continue;
+        }
+        reached[state] = true;
+        for edge in &nfa.edges[state] {
+            match edge {
+                Edge::Epsilon(to) => stack.push(*to),
+                Edge::Assert(assertion, to) if position.holds(*assertion) => stack.push(*to),
+                _ => {}
+            }
+        }
+    }
+    reached
+}
+
+/**
+    A pattern compiled into a DeterministicFinalAutomata
+    * The symbols of the automata are the classes of characters (or bytes) the pattern can't tell apart,
+    * e.g. for [a-c]x the classes are a-c, x and everything else.
+    * The automata accepts the lines that contain a match, like Regex::is_match; \d, \w, \s and \b are ASCII.
+    * difference The first construct of the pattern that may match other texts than the regex crate, if any
+    * table The next state by state and class, so a line is scanned without hashing; the start state is 0
+ **/
+pub struct DfaPattern {
+    alphabet: Alphabet,
+    difference: Option<PatternError>,
+    boundaries: Vec<u32>,
+    table: Vec<Vec<usize>>,
+    final_states: Vec<bool>,
+    absorbing: Vec<bool>,
+}
+
+impl DfaPattern {
+    /**
+        Function to compile a pattern
+        * @param pattern The regular expression
+        * @param alphabet Whether the automata reads bytes or characters
+        * @returns The compiled pattern, or an error if the pattern uses a feature an automata can't represent
+     **/
+    pub fn new(pattern: &str, alphabet: Alphabet) -> Result<Self, PatternError> {
+        let (node, difference) = parse(pattern, alphabet)?;
+
+        // Any text may come before and after the match
+        let max_unit = alphabet.max_unit();
+        let mut nfa = Nfa { edges: Vec::new() };
+        let prefix = nfa.add_state();
+        let start = nfa.add_state();
+        nfa.edges[prefix].push(Edge::Set(vec![(0, max_unit)], prefix));
+        nfa.edges[prefix].push(Edge::Epsilon(start));
+        let accept = nfa.add_node(&node, start);
+        nfa.edges[accept].push(Edge::Set(vec![(0, max_unit)], accept));
+        let word_assertions = nfa.has_word_assertions();
+
+        // The classes of symbols: every range of a set starts and ends on a boundary
+        let mut boundaries: Vec<u32> = vec![0, max_unit + 1];
+        for edge in nfa.edges.iter().flatten() {
+            if let Edge::Set(ranges, _) = edge {
+                boundaries.extend(ranges.iter().flat_map(|&(low, high)| [low, high + 1]));
+            }
+        }
+        if word_assertions {
+            boundaries.extend(['0', '9', 'A', 'Z', '_', '_', 'a', 'z'].chunks(2).flat_map(|range| [range[0] as u32, range[1] as u32 + 1]));
+        }
+        boundaries.sort();
+        boundaries.dedup();
+        let classes = boundaries.len() - 1;
+
+        // Subset construction; a state is a set of NFA states with the position it was reached at
+        let mut keys: Vec<(Vec<usize>, bool, bool)> = vec![(vec![prefix], true, false)];
+        let mut ids: HashMap<(Vec<usize>, bool, bool), usize> = HashMap::new();
+        ids.insert(keys[0].clone(), 0);
+        let mut table: Vec<Vec<usize>> = Vec::new();
+        let mut final_states = Vec::new();
+        let mut next = 0;
+        while next < keys.len() {
+            let (states, at_start, previous_word) = keys[next].clone();
+            let end = Position { at_start, previous_word, next_word: None };
+            final_states.push(closure(&nfa, &states, end)[accept]);
+
+            let mut row = Vec::with_capacity(classes);
+            for &unit in &boundaries[..classes] {
+                let word = word_assertions && is_word(unit);
+                let reached = closure(&nfa, &states, Position { at_start, previous_word, next_word: Some(word) });
+                let mut targets: Vec<usize> = Vec::new();
+                for (state, _) in reached.iter().enumerate().filter(|(_, reached)| **reached) {
+                    for edge in &nfa.edges[state] {
+                        if let Edge::Set(ranges, to) = edge {
+                            if ranges.iter().any(|&(low, high)| low <= unit && unit <= high) {
+                                targets.push(*to);
+                            }
+                        }
+                    }
+                }
+                targets.sort();
+                targets.dedup();
+                let key = (targets, false, word);
+                let id = match ids.get(&key) {
+                    Some(id) => *id,
+                    None => {
+                        if keys.len() >= MAX_STATES {
+                            return Err(PatternError { position: 0, message: format!("the automata has more than {} states", MAX_STATES) });
+                        }
+                        ids.insert(key.clone(), keys.len());
+                        keys.push(key);
+                        keys.len() - 1
+                    }
+                };
+                row.push(id);
+            }
+            table.push(row);
+            next += 1;
+        }
+
+        // Once the automata is in a final state that loops on every class, the rest of the line can't change the result
+        let absorbing = (0..table.len())
+            .map(|state| final_states[state] && table[state].iter().all(|&to| to == state))
+            .collect();
+        Ok(DfaPattern { alphabet, difference, boundaries, table, final_states, absorbing })
+    }
+
+    /**
+        Function to build the DeterministicFinalAutomata of the pattern, e.g. to print it
+        * @returns The automata; its states are q0 (the start state), q1, ... and its symbols c0, c1, ... are the
+        * classes of characters or bytes
+     **/
+    pub fn to_automata(&self) -> DeterministicFinalAutomata {
+        let name = |state: usize| format!("q{}", state);
+        let symbol = |class: usize| format!("c{}", class);
+        let states = self.table.len();
+        let mut automata = DeterministicFinalAutomata::new(
+            states,
+            (0..states).map(name).collect(),
+            (0..self.boundaries.len() - 1).map(symbol).collect(),
+            name(0),
+            (0..states).filter(|&state| self.final_states[state]).map(name).collect(),
+        );
+        for (from, row) in self.table.iter().enumerate() {
+            for (class, to) in row.iter().enumerate() {
+                automata.add_transition(&name(from), &symbol(class), &name(*to));
+            }
+        }
+        automata
+    }
+
+    /**
+        Function to tell whether the automata may disagree with the regex crate
+        * The automata reads \d, \w, \s, \b and (?i) as ASCII, and with the Byte alphabet . and negated classes
+        * read a single byte, while the regex crate reads them as Unicode characters
+        * @returns The first construct that differs, or None if both engines match the same texts
+     **/
+    pub fn regex_difference(&self) -> Option<&PatternError> {
+        self.difference.as_ref()
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn step(&self, state: usize, unit: u32) -> usize {
+        let class = self.boundaries.partition_point(|&boundary| boundary <= unit) - 1;
+        self.table[state][class]
+    }
+
+    /**
+        Function to check whether a text contains a match of the pattern
+        * @param text The text, e.g. a line of the log
+        * @returns true if the automata accepts the text
+     **/
+    pub fn is_match(&self, text: &str) -> bool {
+        let mut state = 0;
+        let mut units: Box<dyn Iterator<Item = u32>> = match self.alphabet {
+            Alphabet::Byte => Box::new(text.bytes().map(|byte| byte as u32)),
+            Alphabet::Char => Box::new(text.chars().map(|c| c as u32)),
+        };
+        while !self.absorbing[state] {
+            match units.next() {
+                Some(unit) => state = self.step(state, unit),
+                None => break,
+            }
+        }
+        self.final_states[state]
+    }
+}
+
+/**
+    Struct to represent a line where the two engines disagree
+    * pattern The index of the pattern
+    * line_number, text The line
+    * regex Whether the regex crate found a match
+ **/
+#[derive(Debug, Clone)]
+pub struct Disagreement {
+    pub pattern: usize,
+    pub line_number: usize,
+    pub text: String,
+    pub regex: bool,
+}
+
+/**
+    Struct to represent the result of running both engines over a log
+    * lines The number of lines
+    * compiled The number of states of every pattern, or why it couldn't be compiled
+    * regex_matches, automata_matches The number of matching lines of every pattern
+    * disagreements The lines where the engines disagree
+ **/
+pub struct EngineComparison {
+    pub lines: usize,
+    pub compiled: Vec<Result<usize, PatternError>>,
+    pub regex_matches: Vec<usize>,
+    pub automata_matches: Vec<usize>,
+    pub disagreements: Vec<Disagreement>,
+}
+
+/**
+    Function to match every line of a log with the regex crate and with the automata of the patterns
+    * @param file_path The log file
+    * @param patterns The regular expressions
+    * @param alphabet Whether the automata read bytes or characters
+    * @returns The counts and the disagreements; patterns that can't be compiled are skipped
+ **/
+pub fn compare_engines(file_path: &str, patterns: &[&str], alphabet: Alphabet) -> io::Result<EngineComparison> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let regexes = patterns.iter()
+        .map(|pattern| Regex::new(pattern).map_err(|err| invalid(err.to_string())))
+        .collect::<io::Result<Vec<Regex>>>()?;
+    let automata: Vec<Result<DfaPattern, PatternError>> = patterns.iter().map(|pattern| DfaPattern::new(pattern, alphabet)).collect();
+
+    let mut comparison = EngineComparison {
+        lines: 0,
+        compiled: automata.iter().map(|pattern| pattern.as_ref().map(DfaPattern::state_count).map_err(Clone::clone)).collect(),
+        regex_matches: vec![0; patterns.len()],
+        automata_matches: vec![0; patterns.len()],
+        disagreements: Vec::new(),
+    };
+    for (index, line) in BufReader::new(File::open(file_path)?).lines().enumerate() {
+        let line = line?;
+        comparison.lines += 1;
+        for (pattern, (regex, automata)) in regexes.iter().zip(&automata).enumerate() {
+            let Ok(automata) = automata else {
+                continue;
+            };
+            let (regex_match, automata_match) = (regex.is_match(&line), automata.is_match(&line));
+            comparison.regex_matches[pattern] += regex_match as usize;
+            comparison.automata_matches[pattern] += automata_match as usize;
+            if regex_match != automata_match {
+                comparison.disagreements.push(Disagreement { pattern, line_number: index + 1, text: line.clone(), regex: regex_match });
+            }
+        }
+    }
+    Ok(comparison)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERNS: [&str; 9] = [
+        r"ANR in \w+",
+        r"^E/",
+        r"(?:error|fail(?:ed|ure))\b",
+        r"pid=\d{3,5}$",
+        r"[a-c]x|y+z?",
+        r"\bat [A-Za-z.$]+\(",
+        r"a.b",
+        r"(ab)*c",
+        r"\Bdroid",
+    ];
+
+    const TEXTS: [&str; 14] = [
+        "",
+        "ANR in com.example.app",
+        "ANR in ",
+        "E/AndroidRuntime: FATAL EXCEPTION",
+        "W/E/x",
+        "Connection failed: timeout",
+        "failures: 3",
+        "killing pid=1234",
+        "killing pid=12 now",
+        "bx yyy",
+        "    at com.example.Main.run(Main.java:10)",
+        "that com.example(",
+        "ababc axb a\u{e9}b",
+        "android Droid",
+    ];
+
+    #[test]
+    fn automata_agrees_with_regex() {
+        for alphabet in [Alphabet::Byte, Alphabet::Char] {
+            for pattern in PATTERNS {
+                let regex = Regex::new(pattern).unwrap();
+                let automata = DfaPattern::new(pattern, alphabet).unwrap();
+                for text in TEXTS {
+                    assert_eq!(automata.is_match(text), regex.is_match(text), "{:?} {} {:?}", alphabet, pattern, text);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dot_reads_one_character_only_with_the_char_alphabet() {
+        let regex = Regex::new("^a.b$").unwrap();
+        assert!(regex.is_match("a\u{e9}b"));
+        assert!(DfaPattern::new("^a.b$", Alphabet::Char).unwrap().is_match("a\u{e9}b"));
+        assert!(!DfaPattern::new("^a.b$", Alphabet::Byte).unwrap().is_match("a\u{e9}b"));
+    }
+
+    #[test]
+    fn constructs_read_differently_than_the_regex_crate_are_reported() {
+        let difference = |pattern: &str, alphabet: Alphabet| {
+            DfaPattern::new(pattern, alphabet).unwrap().regex_difference().map(|difference| difference.position)
+        };
+        assert_eq!(difference(r"^E/ \w+", Alphabet::Char), Some(4));
+        assert_eq!(difference(r"x\bE", Alphabet::Char), Some(1));
+        assert_eq!(difference("ab(?i)c", Alphabet::Char), Some(2));
+        assert_eq!(difference("a.b", Alphabet::Char), None);
+        assert_eq!(difference("a.b", Alphabet::Byte), Some(1));
+        assert_eq!(difference("[^:]+:", Alphabet::Char), None);
+        assert_eq!(difference("[^:]+:", Alphabet::Byte), Some(0));
+        assert_eq!(difference("^E/[A-Z]+", Alphabet::Byte), None);
+
+        // The reason: the regex crate's \w also matches the letters outside ASCII
+        assert!(Regex::new(r"^\w$").unwrap().is_match("\u{e9}"));
+        assert!(!DfaPattern::new(r"^\w$", Alphabet::Char).unwrap().is_match("\u{e9}"));
+    }
+
+    #[test]
+    fn the_automata_view_accepts_the_language_of_the_pattern() {
+        let automata = |pattern: &str| DfaPattern::new(pattern, Alphabet::Char).unwrap().to_automata();
+        assert!(crate::automata::check_equivalence(&automata("^E/"), &automata("^(?:E|E)/")));
+        assert!(!crate::automata::check_equivalence(&automata("^E/"), &automata("^/E")));
+    }
+
+    #[test]
+    fn lookarounds_are_rejected() {
+        assert!(DfaPattern::new("(?=a)b", Alphabet::Byte).is_err());
+    }
+}
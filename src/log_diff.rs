@@ -0,0 +1,238 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::log_error::LogAnalysisError;
+use crate::log_stats;
+use crate::logcat::{self, LogLine};
+
+/**
+    Replaces the variable parts of messages with placeholders, so the messages that differ only in
+    ids, addresses or numbers get the same template:
+    * UUIDs become <uuid>
+    * hexadecimal numbers (0x1f, or 8+ hex digits with both digits and letters) and object hashes (Change@e5c9d)
+    * become <hex>
+    * paths become <path>: absolute ones (/data/app/...) and relative ones with at least three parts
+    * (device/generic/goldfish/..., so I/O or and/or stay words)
+    * numbers become <num>
+ **/
+pub struct Normalizer {
+    uuid: Regex,
+    hex: Regex,
+    path: Regex,
+    number: Regex,
+}
+
+impl Normalizer {
+    pub fn new() -> Self {
+        Normalizer {
+            uuid: Regex::new(r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b").unwrap(),
+            hex: Regex::new(r"\b0[xX][0-9a-fA-F]+\b|\b[0-9a-fA-F]{8,}\b|@[0-9a-fA-F]{4,}\b").unwrap(),
+            path: Regex::new(r"(?:^|\B)/[\w.@-]+(?:/[\w.@$-]*)+|\b[\w.@-]+(?:/[\w.@$-]+){2,}/?").unwrap(),
+            number: Regex::new(r"\d+(?:\.\d+)?").unwrap(),
+        }
+    }
+
+    /**
+        Function to get the template of a message
+        * @param message The message
+        * @returns The message with placeholders
+     **/
+    pub fn template(&self, message: &str) -> String {
+        let message = self.uuid.replace_all(message, "<uuid>");
+        let message = self.hex.replace_all(&message, |captures: &regex::Captures| {
+            // Words like "deadbeef" and long decimal numbers are not hex ids, they need both digits and letters
+            let text = &captures[0];
+            if text.starts_with('@') {
+                return "@<hex>".to_string();
+            }
+            let is_id = text.chars().any(|c| c.is_ascii_digit()) && text.chars().any(|c| c.is_ascii_alphabetic());
+            if is_id || text.starts_with("0x") || text.starts_with("0X") {
+                "<hex>".to_string()
+            } else {
+                text.to_string()
+            }
+        });
+        let message = self.path.replace_all(&message, "<path>");
+        self.number.replace_all(&message, "<num>").into_owned()
+    }
+}
+
+/**
+    Struct to represent the number of entries of every template in a capture
+    * total The number of entries
+    * counts The number of entries by tag and template
+ **/
+pub struct TemplateCounts {
+    pub total: usize,
+    pub counts: HashMap<(String, String), usize>,
+}
+
+/**
+    Function to count the templates of a log file
+    * @param file_path The log file, in any logcat format
+    * @param normalizer The normalizer of the messages
+    * @returns The counts; continuation lines are part of the message of their entry, only its first line is used
+ **/
+pub fn count_templates(file_path: &str, normalizer: &Normalizer) -> Result<TemplateCounts, LogAnalysisError> {
+    let mut counts = TemplateCounts { total: 0, counts: HashMap::new() };
+    for log_line in logcat::read_log_file(file_path).map_err(LogAnalysisError::io(file_path))? {
+        if let LogLine::Entry(entry) = log_line.map_err(LogAnalysisError::io(file_path))? {
+            let first_line = entry.message.lines().next().unwrap_or("");
+            let key = (entry.tag.clone(), normalizer.template(first_line));
+            *counts.counts.entry(key).or_insert(0) += 1;
+            counts.total += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/**
+    How a template changed between the two captures
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    New,
+    Missing,
+    Increased,
+    Decreased,
+}
+
+/**
+    Struct to represent a template that changed
+    * tag, template The template
+    * before, after The number of entries in the first and the second capture
+    * ratio How many times more frequent it became, relative to the size of the captures
+ **/
+#[derive(Debug, Clone)]
+pub struct TemplateChange {
+    pub kind: ChangeKind,
+    pub tag: String,
+    pub template: String,
+    pub before: usize,
+    pub after: usize,
+    pub ratio: f64,
+}
+
+/**
+    Struct to represent the settings of the comparison
+    * factor How many times the relative frequency has to grow or shrink to be reported
+    * min_count The smallest difference in entries that is reported, so rare templates don't flood the report
+ **/
+#[derive(Debug, Clone, Copy)]
+pub struct DiffSettings {
+    pub factor: f64,
+    pub min_count: usize,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        DiffSettings { factor: 2.0, min_count: 5 }
+    }
+}
+
+/**
+    Function to compare the templates of two captures
+    * The frequencies are compared relative to the number of entries, so a longer capture doesn't make
+    * every template look increased.
+    * @param before The first capture, e.g. a good run
+    * @param after The second capture, e.g. a bad run
+    * @param settings The factor and the minimal count
+    * @returns The new, missing, increased and decreased templates, the biggest changes first
+ **/
+pub fn diff_templates(before: &TemplateCounts, after: &TemplateCounts, settings: &DiffSettings) -> Vec<TemplateChange> {
+    let rate = |count: usize, total: usize| if total == 0 { 0.0 } else { count as f64 / total as f64 };
+    let mut keys: Vec<&(String, String)> = before.counts.keys().chain(after.counts.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut changes: Vec<TemplateChange> = Vec::new();
+    for key in keys {
+        let count_before = before.counts.get(key).cloned().unwrap_or(0);
+        let count_after = after.counts.get(key).cloned().unwrap_or(0);
+        let (rate_before, rate_after) = (rate(count_before, before.total), rate(count_after, after.total));
+        let ratio = if rate_before == 0.0 { f64::INFINITY } else { rate_after / rate_before };
+
+        let kind = if count_before.abs_diff(count_after) < settings.min_count {
+            continue;
+        } else if count_before == 0 {
+            ChangeKind::New
+        } else if count_after == 0 {
+            ChangeKind::Missing
+        } else if ratio >= settings.factor {
+            ChangeKind::Increased
+        } else if ratio <= 1.0 / settings.factor {
+            ChangeKind::Decreased
+        } else {
+            continue;
+        };
+        changes.push(TemplateChange {
+            kind,
+            tag: key.0.clone(),
+            template: key.1.clone(),
+            before: count_before,
+            after: count_after,
+            ratio,
+        });
+    }
+    changes.sort_by_key(|change| std::cmp::Reverse(change.before.abs_diff(change.after)));
+    changes
+}
+
+/**
+    Function to write the changes as one table per kind
+    * @param out Where to write the tables
+    * @param changes The changes, see diff_templates
+    * @param n The number of rows of each table
+ **/
+pub fn write_changes(out: &mut dyn Write, changes: &[TemplateChange], n: usize) -> io::Result<()> {
+    let sections = [
+        (ChangeKind::New, "New templates"),
+        (ChangeKind::Missing, "Missing templates"),
+        (ChangeKind::Increased, "More frequent templates"),
+        (ChangeKind::Decreased, "Less frequent templates"),
+    ];
+    for (kind, title) in sections {
+        let selected: Vec<&TemplateChange> = changes.iter().filter(|change| change.kind == kind).collect();
+        writeln!(out, "{}: {}", title, selected.len())?;
+        let rows: Vec<Vec<String>> = selected.iter().take(n)
+            .map(|change| {
+                let ratio = if change.ratio.is_finite() { format!("{:.2}", change.ratio) } else { "-".to_string() };
+                vec![change.before.to_string(), change.after.to_string(), ratio, change.tag.clone(), change.template.clone()]
+            })
+            .collect();
+        log_stats::write_table(out, &["before", "after", "ratio", "tag", "template"], &rows)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(templates: &[(&str, usize)]) -> TemplateCounts {
+        let counts: HashMap<(String, String), usize> = templates.iter()
+            .map(|(template, count)| (("Tag".to_string(), template.to_string()), *count))
+            .collect();
+        TemplateCounts { total: counts.values().sum(), counts }
+    }
+
+    #[test]
+    fn relative_paths_and_hashes_are_normalized() {
+        let normalizer = Normalizer::new();
+        assert_eq!(normalizer.template("compile_file: device/generic/goldfish/seccomp/mediaswcodec.policy(12)"),
+                   "compile_file: <path>(<num>)");
+        assert_eq!(normalizer.template("open /data/app/base.apk"), "open <path>");
+        assert_eq!(normalizer.template("Adding: config.Change@e5c9d"), "Adding: config.Change@<hex>");
+        assert_eq!(normalizer.template("I/O error, read and/or write"), "I/O error, read and/or write");
+    }
+
+    #[test]
+    fn min_count_applies_to_every_kind() {
+        let before = counts(&[("common", 100), ("rare gone", 2), ("gone", 10)]);
+        let after = counts(&[("common", 100), ("rare new", 1), ("new", 20)]);
+        let changes = diff_templates(&before, &after, &DiffSettings::default());
+        let kinds: Vec<(ChangeKind, &str)> = changes.iter().map(|change| (change.kind, change.template.as_str())).collect();
+        assert_eq!(kinds, vec![(ChangeKind::New, "new"), (ChangeKind::Missing, "gone")]);
+    }
+}
@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/**
+    Error of the log analysis
+    * Io A log, rules or index file couldn't be opened or read
+    * InvalidRules A rules file is not in the expected format, see log_rules::read_rules and
+    * log_redact::read_redaction_rules
+    * InvalidPattern The pattern of a rule is not a valid regular expression
+    * EmptyInput The file has nothing to analyze, e.g. a log without entries or a rules file without rules
+    * Output The results couldn't be written to an output file
+ **/
+#[derive(Debug)]
+pub enum LogAnalysisError {
+    Io { path: String, error: io::Error },
+    InvalidRules { path: String, message: String },
+    InvalidPattern { rule: String, error: regex::Error },
+    EmptyInput { path: String },
+    Output { path: String, error: io::Error },
+}
+
+impl LogAnalysisError {
+    /**
+        Function to create the error of a file that couldn't be read
+        * @param path The file
+        * @returns A function turning the I/O error into a LogAnalysisError, for map_err
+     **/
+    pub fn io(path: &str) -> impl Fn(io::Error) -> LogAnalysisError + '_ {
+        move |error| LogAnalysisError::Io { path: path.to_string(), error }
+    }
+
+    /**
+        Function to create the error of an output file that couldn't be written
+        * @param path The file
+        * @returns A function turning the I/O error into a LogAnalysisError, for map_err
+     **/
+    pub fn output(path: &str) -> impl Fn(io::Error) -> LogAnalysisError + '_ {
+        move |error| LogAnalysisError::Output { path: path.to_string(), error }
+    }
+}
+
+impl fmt::Display for LogAnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogAnalysisError::Io { path, error } => write!(f, "can't read {}: {}", path, error),
+            LogAnalysisError::InvalidRules { path, message } => write!(f, "invalid rules in {}: {}", path, message),
+            LogAnalysisError::InvalidPattern { rule, error } => write!(f, "invalid pattern of the rule {}: {}", rule, error),
+            LogAnalysisError::EmptyInput { path } => write!(f, "nothing to analyze in {}", path),
+            LogAnalysisError::Output { path, error } => write!(f, "can't write {}: {}", path, error),
+        }
+    }
+}
+
+impl Error for LogAnalysisError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LogAnalysisError::Io { error, .. } | LogAnalysisError::Output { error, .. } => Some(error),
+            LogAnalysisError::InvalidPattern { error, .. } => Some(error),
+            LogAnalysisError::InvalidRules { .. } | LogAnalysisError::EmptyInput { .. } => None,
+        }
+    }
+}
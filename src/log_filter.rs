@@ -0,0 +1,423 @@
+use regex::Regex;
+use std::fmt;
+
+use crate::logcat::{LogLevel, LogcatEntry};
+
+/**
+    The fields of a logcat entry that can be used in a filter
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Date,
+    Time,
+    Pid,
+    Tid,
+    Level,
+    Tag,
+    Message,
+}
+
+/**
+    The comparison operators: = != < <= > >=
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/**
+    The syntax tree of a filter expression
+    * Compare A field compared with a value, e.g. level>=W
+    * Matches A field matched with a regular expression, e.g. tag~"ExoPlayer" (negated by !~)
+    * InRange A field between two values, both ends included, e.g. time in 10:18:51..10:19:00
+ **/
+#[derive(Debug, Clone)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Field, Comparison, String),
+    Matches(Field, Regex, bool),
+    InRange(Field, String, String),
+}
+
+/**
+    Error found while parsing a filter expression
+    * position The character position in the expression
+    * message What went wrong
+ **/
+#[derive(Debug, Clone)]
+pub struct FilterError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at position {}: {}", self.position, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Operator(String),
+    Range,
+    Open,
+    Close,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let is_range_at = |i: usize| chars[i] == '.' && i + 1 < chars.len() && chars[i + 1] == '.';
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' {
+            tokens.push((start, if c == '(' { Token::Open } else { Token::Close }));
+            i += 1;
+        } else if c == '"' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                // \" and \\ are escapes, any other backslash is kept for the regular expressions
+                if chars[i] == '\\' && i + 1 < chars.len() && (chars[i + 1] == '"' || chars[i + 1] == '\\') {
+                    i += 1;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err(FilterError { position: start, message: "unterminated string".to_string() });
+            }
+            i += 1;
+            tokens.push((start, Token::Quoted(value)));
+        } else if "=!<>~".contains(c) {
+            let mut operator = c.to_string();
+            if i + 1 < chars.len() && (chars[i + 1] == '=' || (c == '!' && chars[i + 1] == '~')) {
+                operator.push(chars[i + 1]);
+            }
+            i += operator.len();
+            tokens.push((start, Token::Operator(operator)));
+        } else if is_range_at(i) {
+            tokens.push((start, Token::Range));
+            i += 2;
+        } else {
+            let mut value = String::new();
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !"()=!<>~\"".contains(chars[i])
+                && !is_range_at(i) {
+                value.push(chars[i]);
+                i += 1;
+            }
+            tokens.push((start, Token::Word(value)));
+        }
+    }
+    Ok(tokens)
+}
+
+/**
+    Recursive descent parser of the filter language:
+    * expression = and_expression { "or" and_expression }
+    * and_expression = not_expression { "and" not_expression }
+    * not_expression = "not" not_expression | "(" expression ")" | condition
+    * condition = field operator value | field "in" value ".." value
+ **/
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    length: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: &str) -> Result<T, FilterError> {
+        let position = self.tokens.get(self.index).map_or(self.length, |(position, _)| *position);
+        Err(FilterError { position, message: message.to_string() })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.index += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expression(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.and_expression()?;
+        while self.is_keyword("or") {
+            self.index += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.and_expression()?));
+        }
+        Ok(filter)
+    }
+
+    fn and_expression(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.not_expression()?;
+        while self.is_keyword("and") {
+            self.index += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.not_expression()?));
+        }
+        Ok(filter)
+    }
+
+    fn not_expression(&mut self) -> Result<Filter, FilterError> {
+        if self.is_keyword("not") {
+            self.index += 1;
+            return Ok(Filter::Not(Box::new(self.not_expression()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.index += 1;
+            let filter = self.expression()?;
+            if self.next() != Some(Token::Close) {
+                self.index -= 1;
+                return self.error("expected )");
+            }
+            return Ok(filter);
+        }
+        self.condition()
+    }
+
+    fn value(&mut self) -> Result<(usize, String), FilterError> {
+        let position = self.tokens.get(self.index).map_or(self.length, |(position, _)| *position);
+        match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok((position, value)),
+            _ => {
+                self.index -= 1;
+                self.error("expected a value")
+            }
+        }
+    }
+
+    fn condition(&mut self) -> Result<Filter, FilterError> {
+        let field = match self.next() {
+            Some(Token::Word(word)) => match word.to_lowercase().as_str() {
+                "date" => Field::Date,
+                "time" => Field::Time,
+                "pid" => Field::Pid,
+                "tid" => Field::Tid,
+                "level" => Field::Level,
+                "tag" => Field::Tag,
+                "message" => Field::Message,
+                _ => {
+                    self.index -= 1;
+                    return self.error(&format!("unknown field {}", word));
+                }
+            },
+            _ => {
+                self.index -= 1;
+                return self.error("expected a field");
+            }
+        };
+
+        if self.is_keyword("in") {
+            self.index += 1;
+            let (low_position, low) = self.value()?;
+            if self.next() != Some(Token::Range) {
+                self.index -= 1;
+                return self.error("expected ..");
+            }
+            let (high_position, high) = self.value()?;
+            check_value(field, &low, low_position)?;
+            check_value(field, &high, high_position)?;
+            return Ok(Filter::InRange(field, low, high));
+        }
+
+        let operator = match self.next() {
+            Some(Token::Operator(operator)) => operator,
+            _ => {
+                self.index -= 1;
+                return self.error("expected an operator");
+            }
+        };
+        let operator_position = self.tokens[self.index - 1].0;
+        let (position, value) = self.value()?;
+
+        if operator == "~" || operator == "!~" {
+            return match Regex::new(&value) {
+                Ok(regex) => Ok(Filter::Matches(field, regex, operator == "~")),
+                Err(err) => Err(FilterError { position, message: err.to_string() }),
+            };
+        }
+
+        let comparison = match operator.as_str() {
+            "=" | "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            _ => {
+                return Err(FilterError { position: operator_position, message: format!("unknown operator {}", operator) });
+            }
+        };
+        check_value(field, &value, position)?;
+        Ok(Filter::Compare(field, comparison, value))
+    }
+}
+
+// Numbers and levels are checked while parsing, so evaluating can't fail
+fn check_value(field: Field, value: &str, position: usize) -> Result<(), FilterError> {
+    let message = match field {
+        Field::Pid | Field::Tid if value.parse::<u32>().is_err() => format!("{} is not a number", value),
+        Field::Level if parse_level(value).is_none() => format!("{} is not a level", value),
+        _ => return Ok(()),
+    };
+    Err(FilterError { position, message })
+}
+
+fn parse_level(value: &str) -> Option<LogLevel> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) => LogLevel::from_letter(letter.to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+/**
+    Function to parse a filter expression
+    * e.g. level>=W and tag~"ExoPlayer" and pid=221 and time in 10:18:51..10:19:00
+    * Fields: date, time, pid, tid, level, tag, message
+    * Operators: = != < <= > >= (compare), ~ !~ (regular expression), in a..b (range)
+    * Conditions are combined with and, or, not and parentheses
+    * @param expression The expression to parse
+    * @returns The syntax tree of the filter, or the position and reason of the error
+ **/
+pub fn parse_filter(expression: &str) -> Result<Filter, FilterError> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens, index: 0, length: expression.chars().count() };
+    let filter = parser.expression()?;
+    if parser.index < parser.tokens.len() {
+        return parser.error("unexpected input");
+    }
+    Ok(filter)
+}
+
+// Compares a field with a value; dates and times are compared at the precision of the value,
+// so time<=10:19:00 includes 10:19:00.500. None if the entry doesn't have the field (the tid in most formats,
+// the pid in tag format), then no comparison holds
+fn compare(entry: &LogcatEntry, field: Field, value: &str) -> Option<std::cmp::Ordering> {
+    let truncated = |text: &str| text.chars().take(value.chars().count()).collect::<String>();
+    let ordering = match field {
+        Field::Date => truncated(&entry.date).as_str().cmp(value),
+        Field::Time => truncated(&entry.time).as_str().cmp(value),
+        Field::Pid => entry.pid?.cmp(&value.parse().unwrap_or(0)),
+        Field::Tid => entry.tid?.cmp(&value.parse().unwrap_or(0)),
+        Field::Level => entry.level.cmp(&parse_level(value).unwrap_or(LogLevel::Verbose)),
+        Field::Tag => entry.tag.as_str().cmp(value),
+        Field::Message => entry.message.as_str().cmp(value),
+    };
+    Some(ordering)
+}
+
+fn field_text(entry: &LogcatEntry, field: Field) -> String {
+    match field {
+        Field::Date => entry.date.clone(),
+        Field::Time => entry.time.clone(),
+        Field::Pid => entry.pid.map_or(String::new(), |pid| pid.to_string()),
+        Field::Tid => entry.tid.map_or(String::new(), |tid| tid.to_string()),
+        Field::Level => entry.level.to_string(),
+        Field::Tag => entry.tag.clone(),
+        Field::Message => entry.message.clone(),
+    }
+}
+
+impl Filter {
+    /**
+        Function to evaluate the filter on an entry
+        * @param entry The entry to check
+        * @returns true if the entry matches the filter
+     **/
+    pub fn matches(&self, entry: &LogcatEntry) -> bool {
+        use std::cmp::Ordering;
+        match self {
+            Filter::And(left, right) => left.matches(entry) && right.matches(entry),
+            Filter::Or(left, right) => left.matches(entry) || right.matches(entry),
+            Filter::Not(filter) => !filter.matches(entry),
+            Filter::Compare(field, comparison, value) => {
+                let Some(ordering) = compare(entry, *field, value) else {
+                    return false;
+                };
+                match comparison {
+                    Comparison::Equal => ordering == Ordering::Equal,
+                    Comparison::NotEqual => ordering != Ordering::Equal,
+                    Comparison::Less => ordering == Ordering::Less,
+                    Comparison::LessOrEqual => ordering != Ordering::Greater,
+                    Comparison::Greater => ordering == Ordering::Greater,
+                    Comparison::GreaterOrEqual => ordering != Ordering::Less,
+                }
+            }
+            Filter::Matches(field, regex, expected) => regex.is_match(&field_text(entry, *field)) == *expected,
+            Filter::InRange(field, low, high) => {
+                compare(entry, *field, low).is_some_and(|ordering| ordering != Ordering::Less)
+                    && compare(entry, *field, high).is_some_and(|ordering| ordering != Ordering::Greater)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::{LineParser, LogFormat};
+
+    // The shape of the tree, the conditions are written by their field
+    fn shape(filter: &Filter) -> String {
+        match filter {
+            Filter::And(left, right) => format!("({} and {})", shape(left), shape(right)),
+            Filter::Or(left, right) => format!("({} or {})", shape(left), shape(right)),
+            Filter::Not(filter) => format!("not {}", shape(filter)),
+            Filter::Compare(field, _, _) | Filter::Matches(field, _, _) | Filter::InRange(field, _, _) => format!("{:?}", field).to_lowercase(),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or_and_not_tightest() {
+        let cases = [
+            ("pid=1 or tid=2 and level>=W", "(pid or (tid and level))"),
+            ("pid=1 and tid=2 or level>=W", "((pid and tid) or level)"),
+            ("not pid=1 and tag~\"a\"", "(not pid and tag)"),
+            ("not (pid=1 and tag~\"a\")", "not (pid and tag)"),
+            ("(pid=1 or tid=2) and time in 10:18..10:19", "((pid or tid) and time)"),
+            ("pid=1 or tid=2 or message=x", "((pid or tid) or message)"),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(shape(&parse_filter(expression).unwrap()), expected, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn precedence_decides_the_match() {
+        let entry = LineParser::new(LogFormat::ThreadTime).parse("10-15 10:18:47.782  1234  1240 I Tag: message").unwrap();
+        assert!(parse_filter("pid=1234 or pid=1 and level>=E").unwrap().matches(&entry));
+        assert!(!parse_filter("(pid=1234 or pid=1) and level>=E").unwrap().matches(&entry));
+        assert!(!parse_filter("not pid=1234 or level>=E").unwrap().matches(&entry));
+    }
+
+    #[test]
+    fn pid_conditions_need_a_pid() {
+        let entry = LineParser::new(LogFormat::Tag).parse("I/Tag: message").unwrap();
+        for expression in ["pid=0", "pid<1", "pid!=1"] {
+            assert!(!parse_filter(expression).unwrap().matches(&entry), "{}", expression);
+        }
+        assert!(parse_filter("not pid=0").unwrap().matches(&entry));
+    }
+}
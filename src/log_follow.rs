@@ -0,0 +1,445 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::log_error::LogAnalysisError;
+use crate::log_rules::LogRules;
+use crate::logcat::{self, LineParser, LogLevel, LogLine, LogcatEntry};
+
+/**
+    Struct to represent the settings of the follow mode
+    * poll_interval How long to wait before looking for new lines again
+    * idle_timeout Stop after the file didn't change for this long, None to follow forever
+    * from_start Read the lines already in the file, otherwise start at its end like tail -f
+ **/
+#[derive(Debug, Clone, Copy)]
+pub struct FollowSettings {
+    pub poll_interval: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub from_start: bool,
+}
+
+impl Default for FollowSettings {
+    fn default() -> Self {
+        FollowSettings {
+            poll_interval: Duration::from_millis(250),
+            idle_timeout: None,
+            from_start: false,
+        }
+    }
+}
+
+/**
+    Struct to represent the running counters of the follow mode
+    * lines The number of lines read
+    * entries The number of logcat entries
+    * levels The number of entries by level
+    * rule_counts The name of every rule with the number of matching lines
+    * truncations, rotations How many times the file was truncated or replaced
+ **/
+#[derive(Debug, Clone, Default)]
+pub struct FollowCounters {
+    pub lines: usize,
+    pub entries: usize,
+    pub levels: BTreeMap<LogLevel, usize>,
+    pub rule_counts: Vec<(String, usize)>,
+    pub truncations: usize,
+    pub rotations: usize,
+}
+
+/**
+    What the follow mode reports while it runs
+    * Match A line matched a rule
+    * Truncated The file got shorter, it is read again from the beginning
+    * Rotated The file was replaced by a new one, which is read from the beginning
+    * Updated New lines were processed, with the counters after them
+ **/
+pub enum FollowEvent<'a> {
+    Match { rule: &'a str, line: &'a LogLine },
+    Truncated,
+    Rotated,
+    Updated(&'a FollowCounters),
+}
+
+// Identifies the file behind a path, so a rotation can be told apart from new lines
+#[cfg(unix)]
+fn file_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/**
+    Reads the lines appended to a growing log file
+    * A line is only returned once its newline was written.
+    * An entry is returned when the next line arrives or when a poll finds nothing new, so indented
+    * continuation lines that are written right after it are still attached to it.
+    * The format is detected from the first lines that are read, once they have a line that is an entry: until
+    * then (e.g. only a --------- beginning of main marker was written) the lines are returned as unparsed.
+ **/
+pub struct LogFollower {
+    path: String,
+    reader: BufReader<File>,
+    identity: Option<(u64, u64)>,
+    position: u64,
+    partial: String,
+    line_number: usize,
+    parser: Option<LineParser>,
+    pending: Option<LogcatEntry>,
+}
+
+/**
+    What happened to the followed file since the last poll
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileChange {
+    Unchanged,
+    Truncated,
+    Rotated,
+}
+
+impl LogFollower {
+    /**
+        Function to start following a file
+        * @param path The log file
+        * @param from_start Read the lines already in the file, otherwise only the new ones
+        * @returns The follower, or an error if the file can't be opened
+     **/
+    pub fn new(path: &str, from_start: bool) -> Result<Self, LogAnalysisError> {
+        let file = File::open(path).map_err(LogAnalysisError::io(path))?;
+        let metadata = file.metadata().map_err(LogAnalysisError::io(path))?;
+        let mut reader = BufReader::new(file);
+        let position = if from_start { 0 } else { reader.seek(SeekFrom::End(0)).map_err(LogAnalysisError::io(path))? };
+
+        Ok(LogFollower {
+            path: path.to_string(),
+            reader,
+            identity: file_identity(&metadata),
+            position,
+            partial: String::new(),
+            line_number: 0,
+            parser: None,
+            pending: None,
+        })
+    }
+
+    // Checks the file behind the path: a new file means rotation, a shorter one truncation
+    // (like tail -f, a truncated file that grew past the old position before the poll is not noticed)
+    fn check_file(&mut self) -> io::Result<FileChange> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // During a rotation the path may not exist for a moment
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(FileChange::Unchanged),
+            Err(err) => return Err(err),
+        };
+
+        if self.identity.is_some() && file_identity(&metadata) != self.identity {
+            let file = File::open(&self.path)?;
+            self.identity = file_identity(&file.metadata()?);
+            self.reader = BufReader::new(file);
+            self.position = 0;
+            self.partial.clear();
+            return Ok(FileChange::Rotated);
+        }
+        if metadata.len() < self.position {
+            self.reader.seek(SeekFrom::Start(0))?;
+            self.position = 0;
+            self.partial.clear();
+            return Ok(FileChange::Truncated);
+        }
+        Ok(FileChange::Unchanged)
+    }
+
+    fn read_lines(&mut self) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let read = self.reader.read_line(&mut self.partial)?;
+            if read == 0 {
+                return Ok(lines);
+            }
+            self.position += read as u64;
+            if self.partial.ends_with('\n') {
+                let line = self.partial.trim_end_matches(['\n', '\r']).to_string();
+                self.partial.clear();
+                lines.push(line);
+            }
+        }
+    }
+
+    // The lines of the file read so far, what happened to it, and the lines of the new or truncated file
+    fn read_changes(&mut self) -> io::Result<(Vec<String>, FileChange, Vec<String>)> {
+        let lines = self.read_lines()?;
+        let change = self.check_file()?;
+        let new_lines = if change == FileChange::Unchanged { Vec::new() } else { self.read_lines()? };
+        Ok((lines, change, new_lines))
+    }
+
+    // The format is detected from the first lines that have an entry
+    fn parse_lines(&mut self, lines: Vec<String>, log_lines: &mut Vec<LogLine>) {
+        if self.parser.is_none() {
+            let parser = LineParser::new(logcat::detect_format(&lines));
+            if lines.iter().any(|line| parser.parse(line).is_some()) {
+                self.parser = Some(parser);
+            }
+        }
+        for line in lines {
+            self.parse_line(line, log_lines);
+        }
+    }
+
+    fn parse_line(&mut self, line: String, log_lines: &mut Vec<LogLine>) {
+        self.line_number += 1;
+        let Some(parser) = self.parser.as_ref() else {
+            log_lines.push(LogLine::Unparsed { line_number: self.line_number, text: line });
+            return;
+        };
+        if let Some(mut entry) = parser.parse(&line) {
+            entry.line_number = self.line_number;
+            if let Some(previous) = self.pending.replace(entry) {
+                log_lines.push(LogLine::Entry(previous));
+            }
+            return;
+        }
+        if let (Some(entry), true) = (self.pending.as_mut(), line.starts_with([' ', '\t'])) {
+            entry.message.push('\n');
+            entry.message.push_str(line.trim());
+            entry.raw.push('\n');
+            entry.raw.push_str(&line);
+            return;
+        }
+        if let Some(previous) = self.pending.take() {
+            log_lines.push(LogLine::Entry(previous));
+        }
+        log_lines.push(LogLine::Unparsed { line_number: self.line_number, text: line });
+    }
+
+    /**
+        Function to read what was appended since the last poll
+        * After a rotation the rest of the old file is read first, then the new file from the beginning.
+        * After a rotation or a truncation the format is detected again and the lines are numbered from 1.
+        * @returns The new lines and what happened to the file, or an error if the file can't be read
+     **/
+    pub fn poll(&mut self) -> Result<(Vec<LogLine>, FileChange), LogAnalysisError> {
+        let (lines, change, new_lines) = self.read_changes().map_err(LogAnalysisError::io(&self.path))?;
+        let idle = lines.is_empty() && new_lines.is_empty();
+
+        let mut log_lines = Vec::new();
+        self.parse_lines(lines, &mut log_lines);
+        if change != FileChange::Unchanged {
+            // The new content starts at line 1 and may be in another format, the last entry of the old one ends here
+            if let Some(entry) = self.pending.take() {
+                log_lines.push(LogLine::Entry(entry));
+            }
+            self.parser = None;
+            self.line_number = 0;
+        }
+        self.parse_lines(new_lines, &mut log_lines);
+        if idle {
+            if let Some(entry) = self.pending.take() {
+                log_lines.push(LogLine::Entry(entry));
+            }
+        }
+        Ok((log_lines, change))
+    }
+}
+
+/**
+    Function to follow a log file like tail -f, counting and matching the new lines as they arrive
+    * @param path The log file
+    * @param rules The rules to match, see log_rules::read_rules
+    * @param settings The poll interval, the idle timeout and where to start
+    * @param on_event Called with every match, every truncation or rotation, and the counters after every update
+    * @returns The counters when the idle timeout is reached, or an error if the file can't be read
+ **/
+pub fn follow_log_file(
+    path: &str,
+    rules: &LogRules,
+    settings: &FollowSettings,
+    on_event: &mut dyn FnMut(FollowEvent),
+) -> Result<FollowCounters, LogAnalysisError> {
+    let mut follower = LogFollower::new(path, settings.from_start)?;
+    let mut counters = FollowCounters {
+        rule_counts: rules.rules.iter().map(|rule| (rule.name.clone(), 0)).collect(),
+        ..FollowCounters::default()
+    };
+    let mut last_change = Instant::now();
+
+    loop {
+        let (log_lines, change) = follower.poll()?;
+        match change {
+            FileChange::Truncated => {
+                counters.truncations += 1;
+                on_event(FollowEvent::Truncated);
+            }
+            FileChange::Rotated => {
+                counters.rotations += 1;
+                on_event(FollowEvent::Rotated);
+            }
+            FileChange::Unchanged => {}
+        }
+
+        if log_lines.is_empty() && change == FileChange::Unchanged {
+            if settings.idle_timeout.is_some_and(|timeout| last_change.elapsed() >= timeout) {
+                return Ok(counters);
+            }
+            thread::sleep(settings.poll_interval);
+            continue;
+        }
+        last_change = Instant::now();
+
+        for log_line in &log_lines {
+            counters.lines += match log_line {
+                LogLine::Entry(entry) => entry.raw.lines().count(),
+                LogLine::Unparsed { .. } => 1,
+            };
+            if let LogLine::Entry(entry) = log_line {
+                counters.entries += 1;
+                *counters.levels.entry(entry.level).or_insert(0) += 1;
+            }
+            for index in rules.matching_rules(log_line) {
+                counters.rule_counts[index].1 += 1;
+                on_event(FollowEvent::Match { rule: &rules.rules[index].name, line: log_line });
+            }
+        }
+        if !log_lines.is_empty() {
+            on_event(FollowEvent::Updated(&counters));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        path.to_str().unwrap().to_string()
+    }
+
+    fn append(path: &str, text: &str) {
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn texts(log_lines: &[LogLine]) -> Vec<String> {
+        log_lines.iter()
+            .map(|log_line| match log_line {
+                LogLine::Entry(entry) => format!("{}: {}", entry.tag, entry.message),
+                LogLine::Unparsed { text, .. } => text.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn appended_lines_are_parsed_after_the_marker() {
+        let path = temp_path("follow_append.log");
+        fs::write(&path, "--------- beginning of main\n").unwrap();
+        let mut follower = LogFollower::new(&path, true).unwrap();
+        let (log_lines, change) = follower.poll().unwrap();
+        assert_eq!(change, FileChange::Unchanged);
+        assert_eq!(texts(&log_lines), vec!["--------- beginning of main"]);
+
+        // The format is detected from the first entries, not from the marker
+        append(&path, "10-15 10:18:52.101 I/ActivityManager(  515): Start proc\n10-15 10:18:52.340 W/PackageManager(  515): Failure\n    retrieving resources\n");
+        let (log_lines, _) = follower.poll().unwrap();
+        assert_eq!(texts(&log_lines), vec!["ActivityManager: Start proc"]);
+        let (log_lines, _) = follower.poll().unwrap();
+        assert_eq!(texts(&log_lines), vec!["PackageManager: Failure\nretrieving resources"]);
+
+        // A line without its newline is not read yet
+        append(&path, "10-15 10:18:53.000 E/Tag(  515): half");
+        assert!(follower.poll().unwrap().0.is_empty());
+        append(&path, " a line\n");
+        follower.poll().unwrap();
+        assert_eq!(texts(&follower.poll().unwrap().0), vec!["Tag: half a line"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_file_is_an_error_of_that_file() {
+        let path = temp_path("follow_missing.log");
+        assert!(matches!(LogFollower::new(&path, true), Err(LogAnalysisError::Io { path: error_path, .. }) if error_path == path));
+    }
+
+    #[test]
+    fn truncated_file_is_read_again() {
+        let path = temp_path("follow_truncate.log");
+        fs::write(&path, "10-15 10:18:52.101 I/First(  515): one\n10-15 10:18:52.102 I/First(  515): two\n").unwrap();
+        let mut follower = LogFollower::new(&path, true).unwrap();
+        follower.poll().unwrap();
+        follower.poll().unwrap();
+
+        fs::write(&path, "10-15 10:18:53.000 I/Second(  516): three\n").unwrap();
+        let (log_lines, change) = follower.poll().unwrap();
+        assert_eq!(change, FileChange::Truncated);
+        assert!(log_lines.is_empty());
+        assert_eq!(texts(&follower.poll().unwrap().0), vec!["Second: three"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn format_is_detected_again_after_a_truncation() {
+        let path = temp_path("follow_truncate_format.log");
+        fs::write(&path, "10-15 10:18:52.101 I/First(  515): one\n10-15 10:18:52.102 I/First(  515): two\n").unwrap();
+        let mut follower = LogFollower::new(&path, true).unwrap();
+        follower.poll().unwrap();
+
+        // The pending entry of the old content doesn't take the indented line of the new one
+        fs::write(&path, "10-15 10:18:53.000   516   516 W Second: three\n    four\n").unwrap();
+        let (log_lines, change) = follower.poll().unwrap();
+        assert_eq!(change, FileChange::Truncated);
+        assert_eq!(texts(&log_lines), vec!["First: two"]);
+        let log_lines = follower.poll().unwrap().0;
+        assert_eq!(texts(&log_lines), vec!["Second: three\nfour"]);
+        assert!(matches!(&log_lines[0], LogLine::Entry(entry) if entry.line_number == 1 && entry.pid == Some(516)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rotated_file_is_read_after_the_rest_of_the_old_one() {
+        let path = temp_path("follow_rotate.log");
+        let rotated = format!("{}.1", path);
+        fs::write(&path, "10-15 10:18:52.101 I/Old(  515): one\n").unwrap();
+        let mut follower = LogFollower::new(&path, true).unwrap();
+        follower.poll().unwrap();
+        follower.poll().unwrap();
+
+        append(&path, "10-15 10:18:52.102 I/Old(  515): two\n");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "10-15 10:18:53.000 I/New(  516): three\n").unwrap();
+        let (log_lines, change) = follower.poll().unwrap();
+        assert_eq!(change, FileChange::Rotated);
+        assert_eq!(texts(&log_lines), vec!["Old: two"]);
+        assert_eq!(texts(&follower.poll().unwrap().0), vec!["New: three"]);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&rotated).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_is_detected_again_after_a_rotation() {
+        let path = temp_path("follow_rotate_format.log");
+        let rotated = format!("{}.1", path);
+        fs::write(&path, "I/Old(  515): one\n").unwrap();
+        let mut follower = LogFollower::new(&path, true).unwrap();
+        follower.poll().unwrap();
+
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "10-15 10:18:53.000   516   517 E New: two\n").unwrap();
+        let (log_lines, change) = follower.poll().unwrap();
+        assert_eq!(change, FileChange::Rotated);
+        assert_eq!(texts(&log_lines), vec!["Old: one"]);
+        let log_lines = follower.poll().unwrap().0;
+        assert!(matches!(&log_lines[0], LogLine::Entry(entry) if entry.line_number == 1 && entry.tid == Some(517)));
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&rotated).unwrap();
+    }
+}
@@ -0,0 +1,400 @@
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::log_timeline;
+use crate::logcat::{LogLevel, LogcatEntry};
+
+// Number of lines of the same process kept before and after an incident
+const CONTEXT_LINES: usize = 2;
+
+// The tombstone is dumped within seconds of the signal, a later one is about another crash of a reused pid
+const TOMBSTONE_SECONDS: u64 = 10;
+
+/**
+    The kinds of incidents
+    * MainThreadError An error logged on the main thread of a process (pid == tid, so the formats without a tid have none)
+    * FatalException An uncaught Java exception, AndroidRuntime: FATAL EXCEPTION
+    * Anr An application that stopped responding, ActivityManager: ANR in
+    * NativeCrash A native crash: libc: Fatal signal and the DEBUG tombstone dump
+ **/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IncidentKind {
+    MainThreadError,
+    FatalException,
+    Anr,
+    NativeCrash,
+}
+
+impl fmt::Display for IncidentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IncidentKind::MainThreadError => "main thread error",
+            IncidentKind::FatalException => "fatal exception",
+            IncidentKind::Anr => "ANR",
+            IncidentKind::NativeCrash => "native crash",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+    Struct to represent an incident found in the log
+    * kind The kind of the incident
+    * date, time The timestamp of the first line
+    * line_number The line of the log file where the incident starts
+    * pid The process the incident is about, e.g. the crashed process of a tombstone, not the one that dumped it;
+    * None if neither the format nor the incident shows it
+    * tid, tag The thread and tag of the first line
+    * process The name of the process, if the incident names it (e.g. Process: com.example.app, PID: 1345)
+    * summary A one line description, e.g. the exception or the reason of the ANR
+    * lines The lines of the incident
+    * before, after The lines of the same process around the incident
+ **/
+#[derive(Debug, Clone)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    pub date: String,
+    pub time: String,
+    pub line_number: usize,
+    pub pid: Option<u32>,
+    pub tid: Option<u32>,
+    pub tag: String,
+    pub process: Option<String>,
+    pub summary: String,
+    pub lines: Vec<String>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    level: LogLevel,
+}
+
+impl Incident {
+    pub fn timestamp(&self) -> String {
+        format!("{} {}", self.date, self.time)
+    }
+}
+
+impl fmt::Display for Incident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pid = self.pid.map_or("-".to_string(), |pid| pid.to_string());
+        write!(f, "[{}] {} pid {}", self.kind, self.timestamp(), pid)?;
+        if let Some(process) = &self.process {
+            write!(f, " ({})", process)?;
+        }
+        write!(f, ": {}", self.summary)?;
+        for line in &self.before {
+            write!(f, "\n    < {}", line)?;
+        }
+        for line in &self.lines {
+            write!(f, "\n      {}", line)?;
+        }
+        for line in &self.after {
+            write!(f, "\n    > {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+    Finds the incidents of a log, one entry at a time
+    * An incident starts with its first line and is continued by the entries of the same thread with the
+    * same tag and level; entries of other threads may come in between.
+    * The context are the lines of the same process (for tombstones the crashed process) around the incident;
+    * without a pid the process is not known, so there is no context.
+ **/
+pub struct IncidentDetector {
+    open: HashMap<(Option<u32>, Option<u32>, String), Incident>,
+    finished: Vec<Incident>,
+    waiting_for_context: Vec<Incident>,
+    recent: HashMap<u32, VecDeque<String>>,
+    fatal_signal: Regex,
+    tombstone_process: Regex,
+    process_line: Regex,
+}
+
+impl IncidentDetector {
+    pub fn new() -> Self {
+        IncidentDetector {
+            open: HashMap::new(),
+            finished: Vec::new(),
+            waiting_for_context: Vec::new(),
+            recent: HashMap::new(),
+            fatal_signal: Regex::new(r"^Fatal signal .*, pid (\d+) \(([^)]*)\)").unwrap(),
+            tombstone_process: Regex::new(r"^pid: (\d+), tid: \d+, name: .*>>> (\S+) <<<").unwrap(),
+            process_line: Regex::new(r"^Process: ([^,\s]+), PID: (\d+)").unwrap(),
+        }
+    }
+
+    fn incident_kind(entry: &LogcatEntry) -> Option<IncidentKind> {
+        let message = entry.message.as_str();
+        if entry.tag == "AndroidRuntime" && message.starts_with("FATAL EXCEPTION") {
+            Some(IncidentKind::FatalException)
+        } else if entry.tag == "ActivityManager" && message.starts_with("ANR in") {
+            Some(IncidentKind::Anr)
+        } else if (entry.tag == "DEBUG" && message.starts_with("*** *** ***")) || (entry.tag == "libc" && message.starts_with("Fatal signal")) {
+            Some(IncidentKind::NativeCrash)
+        } else if entry.level >= LogLevel::Error && entry.tid.is_some() && entry.tid == entry.pid {
+            // Without a thread id (e.g. brief or time format) it is not known which thread logged the error
+            Some(IncidentKind::MainThreadError)
+        } else {
+            None
+        }
+    }
+
+    fn start(&self, kind: IncidentKind, entry: &LogcatEntry) -> Incident {
+        let mut incident = Incident {
+            kind,
+            date: entry.date.clone(),
+            time: entry.time.clone(),
+            line_number: entry.line_number,
+            pid: entry.pid,
+            tid: entry.tid,
+            tag: entry.tag.clone(),
+            process: None,
+            summary: entry.message.lines().next().unwrap_or("").trim().to_string(),
+            lines: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            level: entry.level,
+        };
+        if let Some(captures) = self.fatal_signal.captures(&entry.message) {
+            incident.pid = captures[1].parse().ok().or(entry.pid);
+            incident.process = Some(captures[2].to_string());
+        }
+        if kind == IncidentKind::Anr {
+            incident.process = entry.message.strip_prefix("ANR in ")
+                .and_then(|rest| rest.split_whitespace().next())
+                .map(|process| process.to_string());
+        }
+        incident
+    }
+
+    // The later lines of a block say which process it is about and why it happened
+    fn update(&self, incident: &mut Incident, entry: &LogcatEntry) {
+        let message = entry.message.trim();
+        let pid = incident.pid;
+        match incident.kind {
+            IncidentKind::FatalException => {
+                if let Some(captures) = self.process_line.captures(message) {
+                    incident.process = Some(captures[1].to_string());
+                    incident.pid = captures[2].parse().ok().or(incident.pid);
+                } else if incident.lines.len() <= 2 && !message.starts_with("at ") {
+                    incident.summary = message.to_string();
+                }
+            }
+            IncidentKind::Anr => {
+                if let Some(pid) = message.strip_prefix("PID: ") {
+                    incident.pid = pid.trim().parse().ok().or(incident.pid);
+                } else if let Some(reason) = message.strip_prefix("Reason: ") {
+                    incident.summary = reason.to_string();
+                }
+            }
+            IncidentKind::NativeCrash => {
+                if let Some(captures) = self.tombstone_process.captures(message) {
+                    incident.pid = captures[1].parse().ok().or(incident.pid);
+                    incident.process = Some(captures[2].to_string());
+                } else if message.starts_with("signal ") {
+                    incident.summary = message.to_string();
+                }
+            }
+            IncidentKind::MainThreadError => {}
+        }
+        if incident.pid != pid {
+            incident.before = self.recent_lines(incident.pid);
+            incident.after.clear();
+        }
+        incident.lines.extend(entry.message.lines().map(|line| line.to_string()));
+    }
+
+    fn recent_lines(&self, pid: Option<u32>) -> Vec<String> {
+        pid.and_then(|pid| self.recent.get(&pid)).map(|lines| lines.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    // Whether the tombstone dumped by DEBUG belongs to the libc: Fatal signal incident
+    fn is_tombstone_of(signal: &Incident, tombstone: &Incident) -> bool {
+        let seconds = |incident: &Incident| log_timeline::timestamp_seconds(&incident.date, &incident.time);
+        signal.tag == "libc" && tombstone.tag == "DEBUG" && tombstone.kind == IncidentKind::NativeCrash
+            && match (seconds(signal), seconds(tombstone)) {
+                (Some(signal), Some(tombstone)) => signal <= tombstone && tombstone - signal <= TOMBSTONE_SECONDS,
+                _ => false,
+            }
+    }
+
+    fn close(&mut self, key: &(Option<u32>, Option<u32>, String)) {
+        if let Some(incident) = self.open.remove(key) {
+            self.waiting_for_context.push(incident);
+        }
+    }
+
+    /**
+        Function to process the next entry of the log
+        * @param entry The entry
+     **/
+    pub fn push(&mut self, entry: &LogcatEntry) {
+        let key = (entry.pid, entry.tid, entry.tag.clone());
+        let kind = Self::incident_kind(entry);
+
+        let continues = self.open.get(&key).is_some_and(|incident| {
+            incident.level == entry.level && !matches!(kind, Some(IncidentKind::FatalException | IncidentKind::Anr))
+        });
+        if continues {
+            let mut incident = self.open.remove(&key).unwrap();
+            self.update(&mut incident, entry);
+            self.open.insert(key, incident);
+            return;
+        }
+
+        // The other lines of the process are the context after its incidents
+        let incidents = self.open.values_mut().chain(self.waiting_for_context.iter_mut());
+        for incident in incidents.filter(|incident| incident.pid.is_some() && incident.pid == entry.pid && incident.after.len() < CONTEXT_LINES) {
+            incident.after.push(entry.raw.clone());
+        }
+        let (complete, waiting): (Vec<Incident>, Vec<Incident>) = self.waiting_for_context.drain(..)
+            .partition(|incident| incident.after.len() >= CONTEXT_LINES);
+        self.waiting_for_context = waiting;
+        self.finished.extend(complete);
+
+        // Another line of the same thread ends its incident
+        let same_thread: Vec<(Option<u32>, Option<u32>, String)> = self.open.keys()
+            .filter(|open_key| open_key.0 == entry.pid && open_key.1 == entry.tid)
+            .cloned()
+            .collect();
+        for open_key in same_thread {
+            self.close(&open_key);
+        }
+
+        if let Some(kind) = kind {
+            let mut incident = self.start(kind, entry);
+            incident.before = self.recent_lines(incident.pid);
+            incident.lines.extend(entry.message.lines().map(|line| line.to_string()));
+            self.open.insert(key, incident);
+        } else if let Some(pid) = entry.pid {
+            let recent = self.recent.entry(pid).or_default();
+            recent.push_back(entry.raw.clone());
+            if recent.len() > CONTEXT_LINES {
+                recent.pop_front();
+            }
+        }
+    }
+
+    /**
+        Function to close the incidents that are still open
+        * A tombstone is merged into the libc: Fatal signal incident of the same process, if that is the last native
+        * crash of the pid, has no tombstone yet and the tombstone follows it within TOMBSTONE_SECONDS
+        * @returns The incidents in the order they started in the log; the timestamps aren't compared, they may go
+        * back, e.g. after a reboot
+     **/
+    pub fn finish(mut self) -> Vec<Incident> {
+        let keys: Vec<(Option<u32>, Option<u32>, String)> = self.open.keys().cloned().collect();
+        for key in keys {
+            self.close(&key);
+        }
+        let mut incidents = self.finished;
+        incidents.append(&mut self.waiting_for_context);
+        incidents.sort_by_key(|incident| incident.line_number);
+
+        let mut merged: Vec<Incident> = Vec::new();
+        let mut has_tombstone: Vec<bool> = Vec::new();
+        for incident in incidents {
+            let signal = merged.iter()
+                .rposition(|previous| previous.kind == IncidentKind::NativeCrash && previous.pid.is_some() && previous.pid == incident.pid)
+                .filter(|&index| !has_tombstone[index] && Self::is_tombstone_of(&merged[index], &incident));
+            match signal {
+                Some(index) => {
+                    has_tombstone[index] = true;
+                    let previous = &mut merged[index];
+                    previous.summary = incident.summary;
+                    previous.lines.extend(incident.lines);
+                    previous.after = incident.after;
+                }
+                None => {
+                    merged.push(incident);
+                    has_tombstone.push(false);
+                }
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::{LineParser, LogFormat};
+
+    fn incidents(format: LogFormat, lines: &[&str]) -> Vec<Incident> {
+        let parser = LineParser::new(format);
+        let mut detector = IncidentDetector::new();
+        for (line, line_number) in lines.iter().zip(1..) {
+            let mut entry = parser.parse(line).unwrap();
+            entry.line_number = line_number;
+            detector.push(&entry);
+        }
+        detector.finish()
+    }
+
+    #[test]
+    fn main_thread_errors_need_a_tid() {
+        let threadtime = incidents(LogFormat::ThreadTime, &[
+            "10-15 11:02:10.412  1289  1289 E chromium: Could not make the context current",
+            "10-15 11:02:10.500  1289  1302 E chromium: Request failed on a background thread",
+        ]);
+        assert_eq!(threadtime.len(), 1);
+        assert_eq!((threadtime[0].pid, threadtime[0].tid), (Some(1289), Some(1289)));
+
+        let time = incidents(LogFormat::Time, &["10-15 11:02:10.412 E/chromium( 1289): Could not make the context current"]);
+        assert!(time.is_empty());
+    }
+
+    #[test]
+    fn tombstone_is_merged_into_the_last_crash_of_its_pid_only() {
+        let signal = |time: &str, tid: u32| format!("10-15 {}  3120  {} F libc    : Fatal signal 11 (SIGSEGV), code 1, fault addr 0x0 in tid {} (player), pid 3120 (player)", time, tid, tid);
+        let tombstone = |time: &str, dumper: u32| [
+            format!("10-15 {}  {}  {} F DEBUG   : *** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***", time, dumper, dumper),
+            format!("10-15 {}  {}  {} F DEBUG   : pid: 3120, tid: 3120, name: player  >>> player <<<", time, dumper, dumper),
+            format!("10-15 {}  {}  {} F DEBUG   : signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0", time, dumper, dumper),
+        ];
+        let native_crashes = |lines: Vec<String>| {
+            let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+            incidents(LogFormat::ThreadTime, &lines).into_iter()
+                .filter(|incident| incident.kind == IncidentKind::NativeCrash)
+                .map(|incident| (incident.tag, incident.summary))
+                .collect::<Vec<(String, String)>>()
+        };
+
+        let mut lines = vec![signal("11:02:41.300", 3120)];
+        lines.extend(tombstone("11:02:41.402", 3150));
+        let merged = native_crashes(lines);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].1, "signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0");
+
+        // The pid was reused, the tombstone a minute later is about a crash without a signal line
+        let mut lines = vec![signal("11:02:41.300", 3120)];
+        lines.extend(tombstone("11:03:41.402", 3150));
+        assert_eq!(native_crashes(lines).len(), 2);
+
+        // Only the last crash of the pid takes the tombstone
+        let mut lines = vec![signal("11:02:41.300", 3120), signal("11:02:41.350", 3121)];
+        lines.extend(tombstone("11:02:41.402", 3150));
+        let crashes = native_crashes(lines);
+        assert_eq!(crashes.len(), 2);
+        assert!(crashes[0].1.starts_with("Fatal signal") && crashes[1].1.starts_with("signal 11"));
+
+        // A signal takes one tombstone, the second one is about a crash logged without a signal line
+        let mut lines = vec![signal("11:02:41.300", 3120)];
+        lines.extend(tombstone("11:02:41.402", 3150));
+        lines.extend(tombstone("11:02:42.000", 3160));
+        let crashes = native_crashes(lines);
+        assert_eq!(crashes.iter().map(|(tag, _)| tag.as_str()).collect::<Vec<&str>>(), ["libc", "DEBUG"]);
+    }
+
+    #[test]
+    fn incidents_keep_the_order_of_the_log_when_the_time_goes_back() {
+        let incidents = incidents(LogFormat::ThreadTime, &[
+            "12-31 23:59:59.000  1289  1289 E chromium: Before the reboot",
+            "01-01 00:00:05.000  1300  1300 E chromium: After the reboot",
+        ]);
+        let summaries: Vec<&str> = incidents.iter().map(|incident| incident.summary.as_str()).collect();
+        assert_eq!(summaries, ["Before the reboot", "After the reboot"]);
+    }
+}
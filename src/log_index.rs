@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::time::UNIX_EPOCH;
+
+use crate::log_error::LogAnalysisError;
+use crate::logcat::{self, LogLevel, LogLine, LogcatEntry};
+
+// First line of the index files, changed when the layout changes
+const INDEX_HEADER: &str = "logcat-index 3";
+
+/**
+    Struct to represent one entry of the index
+    * offset, length Where the entry is in the log file, continuation lines included
+    * line_number The line where the entry starts
+    * timestamp The date and time, e.g. 10-15 10:18:47.782
+ **/
+#[derive(Debug, Clone)]
+pub struct IndexedEntry {
+    pub offset: u64,
+    pub length: u64,
+    pub line_number: usize,
+    pub timestamp: String,
+    pub pid: Option<u32>,
+    pub tid: Option<u32>,
+    pub level: LogLevel,
+    pub tag: String,
+}
+
+/**
+    Struct to represent a query against the index, every given condition has to hold
+    * tag The exact tag
+    * pid The process id
+    * min_level The lowest level, e.g. W for warnings and above
+    * from, to The time range, both ends included and compared at their own precision (10:19 includes 10:19:59.9)
+    * tokens Words that all have to be in the message, case insensitive
+ **/
+#[derive(Debug, Clone, Default)]
+pub struct IndexQuery {
+    pub tag: Option<String>,
+    pub pid: Option<u32>,
+    pub min_level: Option<LogLevel>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub tokens: Vec<String>,
+}
+
+/**
+    Struct to represent an inverted index of a log file
+    * The entries are indexed by tag, pid, level, time and the words of their message.
+    * The index is saved next to the log (<log>.idx) with the size and modification time of the log,
+    * so it is rebuilt when the log changes.
+ **/
+pub struct LogIndex {
+    log_file: String,
+    fingerprint: (u64, u64, u32),
+    pub entries: Vec<IndexedEntry>,
+    by_tag: HashMap<String, Vec<usize>>,
+    by_pid: HashMap<u32, Vec<usize>>,
+    by_level: BTreeMap<LogLevel, Vec<usize>>,
+    by_time: Vec<(String, usize)>,
+    by_token: HashMap<String, Vec<usize>>,
+}
+
+impl IndexQuery {
+    /**
+        Function to check an entry without the index, the same way the index answers the query
+        * @param entry The entry
+        * @returns True if every given condition holds
+     **/
+    pub fn matches(&self, entry: &LogcatEntry) -> bool {
+        let timestamp = format!("{} {}", entry.date, entry.time);
+        let tokens = tokenize(&entry.message);
+        self.tag.as_ref().is_none_or(|tag| &entry.tag == tag)
+            && self.pid.is_none_or(|pid| entry.pid == Some(pid))
+            && self.min_level.is_none_or(|level| entry.level >= level)
+            && self.from.as_ref().is_none_or(|from| timestamp.as_str() >= from.as_str())
+            && self.to.as_ref().is_none_or(|to| &timestamp[..timestamp.len().min(to.len())] <= to.as_str())
+            && self.tokens.iter().all(|token| tokens.contains(&token.to_lowercase()))
+    }
+}
+
+// The size and the modification time identify a version of the log
+fn file_fingerprint(path: &str) -> io::Result<(u64, u64, u32)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+}
+
+fn index_path(log_file: &str) -> String {
+    format!("{}.idx", log_file)
+}
+
+/**
+    Function to split a message into the words that are indexed
+    * @param message The message
+    * @returns The lowercase words with at least 2 letters or digits
+ **/
+pub fn tokenize(message: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = message
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.chars().count() >= 2)
+        .map(|token| token.to_lowercase())
+        .collect();
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+// Both lists are sorted, so they are intersected by walking them together
+fn intersect(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+impl LogIndex {
+    fn new(log_file: &str, fingerprint: (u64, u64, u32)) -> Self {
+        LogIndex {
+            log_file: log_file.to_string(),
+            fingerprint,
+            entries: Vec::new(),
+            by_tag: HashMap::new(),
+            by_pid: HashMap::new(),
+            by_level: BTreeMap::new(),
+            by_time: Vec::new(),
+            by_token: HashMap::new(),
+        }
+    }
+
+    // The tag, pid, level and time lists are built from the entries, only the tokens are stored
+    fn build_lists(&mut self) {
+        for (id, entry) in self.entries.iter().enumerate() {
+            self.by_tag.entry(entry.tag.clone()).or_default().push(id);
+            if let Some(pid) = entry.pid {
+                self.by_pid.entry(pid).or_default().push(id);
+            }
+            self.by_level.entry(entry.level).or_default().push(id);
+            self.by_time.push((entry.timestamp.clone(), id));
+        }
+        self.by_time.sort();
+    }
+
+    /**
+        Function to build the index of a log file
+        * @param log_file The log file, in any logcat format
+        * @returns The index, or an error if the log can't be read
+     **/
+    pub fn build(log_file: &str) -> Result<Self, LogAnalysisError> {
+        LogIndex::read_log(log_file).map_err(LogAnalysisError::io(log_file))
+    }
+
+    fn read_log(log_file: &str) -> io::Result<Self> {
+        let mut index = LogIndex::new(log_file, file_fingerprint(log_file)?);
+
+        // The offset of every line, so an entry can be read back without scanning the log
+        let mut reader = BufReader::new(File::open(log_file)?);
+        let mut line_offsets: Vec<u64> = vec![0];
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            line_offsets.push(line_offsets[line_offsets.len() - 1] + read as u64);
+        }
+
+        for log_line in logcat::read_log_file(log_file)? {
+            let LogLine::Entry(entry) = log_line? else {
+                continue;
+            };
+            let first = entry.line_number - 1;
+            let last = (first + entry.raw.lines().count().max(1)).min(line_offsets.len() - 1);
+            let id = index.entries.len();
+            for token in tokenize(&entry.message) {
+                index.by_token.entry(token).or_default().push(id);
+            }
+            index.entries.push(IndexedEntry {
+                offset: line_offsets[first],
+                length: line_offsets[last] - line_offsets[first],
+                line_number: entry.line_number,
+                timestamp: format!("{} {}", entry.date, entry.time),
+                pid: entry.pid,
+                tid: entry.tid,
+                level: entry.level,
+                tag: entry.tag,
+            });
+        }
+        index.build_lists();
+        Ok(index)
+    }
+
+    /**
+        Function to save the index next to the log file, as <log>.idx
+        * Every entry is one line: offset length line pid tid (- if the format doesn't show it) level date time tag;
+        * then every token is one line: the token and the ids of its entries.
+     **/
+    pub fn save(&self) -> Result<(), LogAnalysisError> {
+        let path = index_path(&self.log_file);
+        self.write_index(&path).map_err(LogAnalysisError::output(&path))
+    }
+
+    fn write_index(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", INDEX_HEADER)?;
+        writeln!(out, "{} {} {}", self.fingerprint.0, self.fingerprint.1, self.fingerprint.2)?;
+        writeln!(out, "{}", self.entries.len())?;
+        for entry in &self.entries {
+            let id = |id: Option<u32>| id.map_or("-".to_string(), |id| id.to_string());
+            writeln!(out, "{} {} {} {} {} {} {} {}", entry.offset, entry.length, entry.line_number,
+                     id(entry.pid), id(entry.tid), entry.level, entry.timestamp, entry.tag)?;
+        }
+        let mut tokens: Vec<(&String, &Vec<usize>)> = self.by_token.iter().collect();
+        tokens.sort();
+        for (token, ids) in tokens {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            writeln!(out, "{} {}", token, ids.join(" "))?;
+        }
+        out.flush()
+    }
+
+    /**
+        Function to load the saved index of a log file
+        * @param log_file The log file
+        * @returns The index, None if there is no saved index or the log changed since it was saved; an error if
+        * the log or the index can't be read
+     **/
+    pub fn load(log_file: &str) -> Result<Option<Self>, LogAnalysisError> {
+        let path = index_path(log_file);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(LogAnalysisError::Io { path, error: err }),
+        };
+        let current = file_fingerprint(log_file).map_err(LogAnalysisError::io(log_file))?;
+        LogIndex::read_index(log_file, file, current).map_err(LogAnalysisError::io(&path))
+    }
+
+    fn read_index(log_file: &str, file: File, current: (u64, u64, u32)) -> io::Result<Option<Self>> {
+        let mut lines = BufReader::new(file).lines();
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid index");
+        let mut next_line = || -> io::Result<String> { lines.next().ok_or_else(invalid)? };
+
+        if next_line()? != INDEX_HEADER {
+            return Ok(None);
+        }
+        let fingerprint: Vec<u64> = next_line()?.split(' ').map(|value| value.parse().map_err(|_| invalid())).collect::<io::Result<_>>()?;
+        if fingerprint != [current.0, current.1, current.2 as u64] {
+            return Ok(None);
+        }
+
+        let mut index = LogIndex::new(log_file, current);
+        let count: usize = next_line()?.parse().map_err(|_| invalid())?;
+        for _ in 0..count {
+            let line = next_line()?;
+            let fields: Vec<&str> = line.splitn(9, ' ').collect();
+            if fields.len() < 8 {
+                return Err(invalid());
+            }
+            let number = |field: &str| field.parse::<u64>().map_err(|_| invalid());
+            let id = |field: &str| -> io::Result<Option<u32>> {
+                if field == "-" { Ok(None) } else { Ok(Some(number(field)? as u32)) }
+            };
+            index.entries.push(IndexedEntry {
+                offset: number(fields[0])?,
+                length: number(fields[1])?,
+                line_number: number(fields[2])? as usize,
+                pid: id(fields[3])?,
+                tid: id(fields[4])?,
+                level: fields[5].chars().next().and_then(LogLevel::from_letter).ok_or_else(invalid)?,
+                timestamp: format!("{} {}", fields[6], fields[7]),
+                tag: fields.get(8).unwrap_or(&"").to_string(),
+            });
+        }
+        for line in lines {
+            let line = line?;
+            let mut fields = line.split(' ');
+            let token = fields.next().ok_or_else(invalid)?.to_string();
+            let ids = fields.map(|id| id.parse().map_err(|_| invalid())).collect::<io::Result<Vec<usize>>>()?;
+            index.by_token.insert(token, ids);
+        }
+        index.build_lists();
+        Ok(Some(index))
+    }
+
+    /**
+        Function to load the saved index of a log file, or build and save it if it is missing or outdated
+        * @param log_file The log file
+        * @returns The index and whether it had to be built, or an error if the log or the index can't be read or
+        * the index can't be saved
+     **/
+    pub fn open(log_file: &str) -> Result<(Self, bool), LogAnalysisError> {
+        if let Some(index) = LogIndex::load(log_file)? {
+            return Ok((index, false));
+        }
+        let index = LogIndex::build(log_file)?;
+        index.save()?;
+        Ok((index, true))
+    }
+
+    /**
+        Function to find the entries that match a query
+        * @param query The conditions
+        * @returns The ids of the matching entries, in the order of the log
+     **/
+    pub fn query(&self, query: &IndexQuery) -> Vec<usize> {
+        let mut candidates: Option<Vec<usize>> = None;
+        let mut restrict = |ids: Vec<usize>| {
+            candidates = Some(match candidates.take() {
+                Some(current) => intersect(&current, &ids),
+                None => ids,
+            });
+        };
+
+        if let Some(tag) = &query.tag {
+            restrict(self.by_tag.get(tag).cloned().unwrap_or_default());
+        }
+        if let Some(pid) = query.pid {
+            restrict(self.by_pid.get(&pid).cloned().unwrap_or_default());
+        }
+        if let Some(min_level) = query.min_level {
+            let mut ids: Vec<usize> = self.by_level.range(min_level..).flat_map(|(_, ids)| ids.iter().cloned()).collect();
+            ids.sort();
+            restrict(ids);
+        }
+        if query.from.is_some() || query.to.is_some() {
+            // A bound is compared at its own precision, so the end of the range is extended to the last match
+            let from = query.from.as_deref().unwrap_or("");
+            let start = self.by_time.partition_point(|(timestamp, _)| timestamp.as_str() < from);
+            let end = match query.to.as_deref() {
+                Some(to) => self.by_time.partition_point(|(timestamp, _)| &timestamp[..timestamp.len().min(to.len())] <= to),
+                None => self.by_time.len(),
+            };
+            let mut ids: Vec<usize> = self.by_time[start..end.max(start)].iter().map(|(_, id)| *id).collect();
+            ids.sort();
+            restrict(ids);
+        }
+        for token in &query.tokens {
+            restrict(self.by_token.get(&token.to_lowercase()).cloned().unwrap_or_default());
+        }
+
+        candidates.unwrap_or_else(|| (0..self.entries.len()).collect())
+    }
+
+    /**
+        Function to read the text of an entry from the log file
+        * @param file The log file, opened by the caller so it is opened once for many entries
+        * @param id The id of the entry
+        * @returns The lines of the entry
+     **/
+    pub fn read_entry(&self, file: &mut File, id: usize) -> Result<String, LogAnalysisError> {
+        let entry = &self.entries[id];
+        let mut text = vec![0; entry.length as usize];
+        file.seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| file.read_exact(&mut text))
+            .map_err(LogAnalysisError::io(&self.log_file))?;
+        Ok(String::from_utf8_lossy(&text).trim_end().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_fields(entry: &IndexedEntry) -> (u64, u64, usize, String, Option<u32>, Option<u32>, LogLevel, String) {
+        (entry.offset, entry.length, entry.line_number, entry.timestamp.clone(), entry.pid, entry.tid, entry.level, entry.tag.clone())
+    }
+
+    #[test]
+    fn saved_index_is_loaded_unchanged() {
+        for sample in ["sample_incidents.log", "sample_time.log", "sample_long.log", "sample_tag.log"] {
+            let log_file = std::env::temp_dir().join(format!("{}_{}", std::process::id(), sample));
+            let log_file = log_file.to_str().unwrap();
+            fs::copy(format!("src/resources/{}", sample), log_file).unwrap();
+
+            let built = LogIndex::build(log_file).unwrap();
+            built.save().unwrap();
+            let loaded = LogIndex::load(log_file).unwrap().expect("the saved index is current");
+            assert_eq!(loaded.entries.iter().map(entry_fields).collect::<Vec<_>>(),
+                       built.entries.iter().map(entry_fields).collect::<Vec<_>>(), "{}", sample);
+
+            let mut log = File::open(log_file).unwrap();
+            let first = &built.entries[0];
+            let queries = [
+                IndexQuery::default(),
+                IndexQuery { tag: Some(first.tag.clone()), ..Default::default() },
+                IndexQuery { pid: first.pid, min_level: Some(LogLevel::Warning), ..Default::default() },
+                IndexQuery { tokens: tokenize(&loaded.read_entry(&mut log, 0).unwrap()), ..Default::default() },
+            ];
+            for query in &queries {
+                assert_eq!(loaded.query(query), built.query(query), "{} {:?}", sample, query);
+            }
+
+            // A changed log makes the saved index outdated
+            fs::OpenOptions::new().append(true).open(log_file).unwrap().write_all(b"\n").unwrap();
+            assert!(LogIndex::load(log_file).unwrap().is_none());
+            fs::remove_file(log_file).unwrap();
+            fs::remove_file(index_path(log_file)).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_damaged_index_is_an_error_of_the_index_file() {
+        let log_file = std::env::temp_dir().join(format!("{}_damaged_index.log", std::process::id()));
+        let log_file = log_file.to_str().unwrap();
+        fs::copy("src/resources/sample_time.log", log_file).unwrap();
+        LogIndex::build(log_file).unwrap().save().unwrap();
+
+        // The header and the fingerprint are kept, the number of entries is not a number
+        let saved = fs::read_to_string(index_path(log_file)).unwrap();
+        let damaged: Vec<&str> = saved.lines().take(2).chain(["many"]).collect();
+        fs::write(index_path(log_file), damaged.join("\n")).unwrap();
+        assert!(matches!(LogIndex::load(log_file), Err(LogAnalysisError::Io { path, .. }) if path == index_path(log_file)));
+        fs::remove_file(log_file).unwrap();
+        fs::remove_file(index_path(log_file)).unwrap();
+    }
+}
@@ -0,0 +1,531 @@
+use regex::Regex;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::log_automata::{self, Alphabet, Assertion, Node, PatternError};
+use crate::log_error::LogAnalysisError;
+use crate::log_rules::{LogRule, LogRules};
+use crate::logcat::{self, LogLevel, LogLine};
+
+// Number of example lines kept for every rule
+const EXAMPLES: usize = 3;
+
+// The letters of the log levels, see logcat::LogLevel
+const LEVEL_LETTERS: &str = "VDIWEFA";
+
+/**
+    The problems the linter looks for
+    * LeadingWildcard The pattern starts with .*, which changes nothing since patterns are searched anywhere in the line
+    * TrailingWildcard The pattern ends with .*, which changes nothing either
+    * TrailingSpace The pattern ends with a space, so the match has to be followed by one
+    * LevelLetter A level letter between spaces or word boundaries, which also matches the same letter in the message
+    * NoMatches The pattern matches no line of the sample log
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintKind {
+    LeadingWildcard,
+    TrailingWildcard,
+    TrailingSpace,
+    LevelLetter,
+    NoMatches,
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LintKind::LeadingWildcard => "leading wildcard",
+            LintKind::TrailingWildcard => "trailing wildcard",
+            LintKind::TrailingSpace => "trailing space",
+            LintKind::LevelLetter => "level letter",
+            LintKind::NoMatches => "no matches",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+    Struct to represent a warning about a pattern
+    * kind The problem
+    * message What it means for this pattern and how to fix it
+ **/
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    pub kind: LintKind,
+    pub message: String,
+}
+
+/**
+    Struct to represent the lint of one rule
+    * name, pattern The rule
+    * explanation The pattern in words, or why it can't be explained
+    * warnings The problems found
+    * pattern_matches The lines of the sample log matching the pattern
+    * rule_matches The lines matching the whole rule, with its level, tag, pid and filter constraints
+    * examples The first few lines matching the rule
+ **/
+#[derive(Debug, Clone)]
+pub struct RuleLint {
+    pub name: String,
+    pub pattern: String,
+    pub explanation: Result<String, PatternError>,
+    pub warnings: Vec<LintWarning>,
+    pub pattern_matches: usize,
+    pub rule_matches: usize,
+    pub examples: Vec<String>,
+}
+
+fn is_any_character(ranges: &[(u32, u32)]) -> bool {
+    let newline = '\n' as u32;
+    ranges == [(0, char::MAX as u32)] || ranges == [(0, newline - 1), (newline + 1, char::MAX as u32)]
+}
+
+fn is_any_text(node: &Node) -> bool {
+    matches!(node, Node::Repeat(inner, 0, None) if matches!(inner.as_ref(), Node::Set(ranges) if is_any_character(ranges)))
+}
+
+fn single_char(node: &Node) -> Option<char> {
+    match node {
+        Node::Set(ranges) if ranges.len() == 1 && ranges[0].0 == ranges[0].1 => char::from_u32(ranges[0].0),
+        _ => None,
+    }
+}
+
+// A character of a text, and whether it is a letter in both cases, from the case insensitive flag
+fn text_char(node: &Node) -> Option<(char, bool)> {
+    if let Some(c) = single_char(node) {
+        return Some((c, false));
+    }
+    match node {
+        Node::Set(ranges) if ranges.len() == 2 && ranges[0].0 == ranges[0].1 && ranges[1].0 == ranges[1].1 => {
+            let (upper, lower) = (char::from_u32(ranges[0].0)?, char::from_u32(ranges[1].0)?);
+            (upper.is_ascii_uppercase() && upper.to_ascii_lowercase() == lower).then_some((lower, true))
+        }
+        _ => None,
+    }
+}
+
+// A space, written in any way, or a repetition that needs at least one, e.g. " +"
+fn ends_with_space(node: &Node) -> bool {
+    match node {
+        Node::Repeat(inner, min, _) => *min > 0 && single_char(inner) == Some(' '),
+        node => single_char(node) == Some(' '),
+    }
+}
+
+// The groups of a concatenation are only parentheses, their items belong to the concatenation
+fn flatten(node: &Node) -> Vec<&Node> {
+    match node {
+        Node::Concat(nodes) => nodes.iter().flat_map(flatten).collect(),
+        Node::Empty => Vec::new(),
+        node => vec![node],
+    }
+}
+
+fn describe_char(c: char) -> String {
+    match c {
+        ' ' => "space".to_string(),
+        c => format!("\"{}\"", c.escape_debug()),
+    }
+}
+
+fn describe_set(ranges: &[(u32, u32)]) -> String {
+    let known = [
+        (vec![('0' as u32, '9' as u32)], "a digit"),
+        (vec![('0' as u32, '9' as u32), ('A' as u32, 'Z' as u32), ('_' as u32, '_' as u32), ('a' as u32, 'z' as u32)], "a word character"),
+        (vec![('\t' as u32, '\r' as u32), (' ' as u32, ' ' as u32)], "a whitespace"),
+    ];
+    if is_any_character(ranges) {
+        return "any character".to_string();
+    }
+    if let Some(c) = single_char(&Node::Set(ranges.to_vec())) {
+        return if c == ' ' { "a space".to_string() } else { describe_char(c) };
+    }
+    if let Some((_, name)) = known.iter().find(|(known, _)| known == ranges) {
+        return name.to_string();
+    }
+
+    let describe = |ranges: &[(u32, u32)]| {
+        ranges.iter()
+            .filter_map(|&(low, high)| {
+                let (low, high) = (char::from_u32(low)?, char::from_u32(high)?);
+                Some(if low == high { describe_char(low) } else { format!("{}-{}", describe_char(low), describe_char(high)) })
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    // A negated class reaches the last character, it is shorter to list what it doesn't match
+    if ranges.last().is_some_and(|&(_, high)| high == char::MAX as u32) {
+        let mut excluded = Vec::new();
+        let mut next = 0;
+        for &(low, high) in ranges {
+            if low > next {
+                excluded.push((next, low - 1));
+            }
+            next = high.saturating_add(1);
+        }
+        return format!("any character except {}", describe(&excluded));
+    }
+    format!("one of {}", describe(ranges))
+}
+
+fn describe_repeat(inner: &Node, min: u32, max: Option<u32>) -> String {
+    if let Node::Set(ranges) = inner {
+        if is_any_character(ranges) {
+            match (min, max) {
+                (0, None) => return "anything".to_string(),
+                (1, None) => return "at least one character".to_string(),
+                _ => {}
+            }
+        }
+    }
+    let text = match inner {
+        // "one or more of one of "a", "b"" reads better without the second "one of"
+        Node::Set(_) => describe(inner).strip_prefix("one of ").map(|text| text.to_string()).unwrap_or_else(|| describe(inner)),
+        Node::Assert(_) => describe(inner),
+        _ => format!("({})", describe(inner)),
+    };
+    match (min, max) {
+        (0, None) => format!("any number of {}", text),
+        (1, None) => format!("one or more of {}", text),
+        (0, Some(1)) => format!("optionally {}", text),
+        (min, None) => format!("{} at least {} times", text, min),
+        (min, Some(max)) if min == max => format!("{} {} times", text, min),
+        (min, Some(max)) => format!("{} {} to {} times", text, min, max),
+    }
+}
+
+fn describe(node: &Node) -> String {
+    match node {
+        Node::Empty => "nothing".to_string(),
+        Node::Set(ranges) => describe_set(ranges),
+        Node::Assert(Assertion::Start) => "the start of the line".to_string(),
+        Node::Assert(Assertion::End) => "the end of the line".to_string(),
+        Node::Assert(Assertion::WordBoundary) => "a word boundary".to_string(),
+        Node::Assert(Assertion::NotWordBoundary) => "no word boundary".to_string(),
+        Node::Alternate(branches) => {
+            let branches: Vec<String> = branches.iter().map(describe).collect();
+            format!("either {}", branches.join(" or "))
+        }
+        Node::Repeat(inner, min, max) => describe_repeat(inner, *min, *max),
+        Node::Concat(_) => {
+            let nodes = flatten(node);
+            let mut parts: Vec<String> = Vec::new();
+            let mut index = 0;
+            while index < nodes.len() {
+                // The characters in a row are one text
+                let chars: Vec<(char, bool)> = nodes[index..].iter().map_while(|node| text_char(node)).collect();
+                let (part, length) = if chars.len() > 1 || chars.iter().any(|&(_, any_case)| any_case) {
+                    let text: String = chars.iter().map(|&(c, _)| c).collect();
+                    let any_case = if chars.iter().any(|&(_, any_case)| any_case) { " in any case" } else { "" };
+                    (format!("\"{}\"{}", text.escape_debug(), any_case), chars.len())
+                } else {
+                    (describe(nodes[index]), 1)
+                };
+                index += length;
+                parts.push(part);
+            }
+            parts.join(", then ")
+        }
+    }
+}
+
+/**
+    Function to explain a pattern in words
+    * @param pattern The pattern, in the syntax supported by log_automata
+    * @returns The explanation, or where and why the pattern can't be explained
+ **/
+pub fn explain_pattern(pattern: &str) -> Result<String, PatternError> {
+    if pattern.is_empty() {
+        return Ok("every line".to_string());
+    }
+    let node = log_automata::parse_pattern(pattern, Alphabet::Char)?;
+    let nodes = flatten(&node);
+    // "a word boundary, then "E", then a word boundary" reads better as a whole word
+    if let [Node::Assert(Assertion::WordBoundary), middle @ .., Node::Assert(Assertion::WordBoundary)] = nodes.as_slice() {
+        if !middle.is_empty() {
+            let inner = Node::Concat(middle.iter().map(|&node| node.clone()).collect());
+            return Ok(format!("{} as a whole word, anywhere in the line", describe(&inner)));
+        }
+    }
+    let explanation = describe(&node);
+    if matches!(nodes.first(), Some(Node::Assert(Assertion::Start))) {
+        Ok(explanation)
+    } else {
+        Ok(format!("{}, anywhere in the line", explanation))
+    }
+}
+
+// The level letters standing alone in the pattern, after a space or a word boundary, that could as well be in the
+// message: none if everything before them is anchored to the start and can't go past the level column
+fn level_letters(nodes: &[&Node]) -> Option<Vec<LogLevel>> {
+    for (index, node) in nodes.iter().enumerate() {
+        let Node::Set(ranges) = node else { continue };
+        let letters: Option<Vec<char>> = ranges.iter()
+            .flat_map(|&(low, high)| low..=high)
+            .map(|unit| char::from_u32(unit).filter(|c| LEVEL_LETTERS.contains(*c)))
+            .collect();
+        let Some(letters) = letters else { continue };
+        let separated = |node: Option<&&Node>| match node {
+            None => true,
+            Some(Node::Assert(Assertion::WordBoundary)) => true,
+            Some(node) => single_char(node) == Some(' '),
+        };
+        // A letter followed by anything is also the first letter of any word, e.g. [EW].* matches " Error"
+        let followed = separated(nodes.get(index + 1)) || nodes.get(index + 1).is_some_and(|node| is_any_text(node));
+        if index == 0 || !separated(nodes.get(index - 1)) || !followed {
+            continue;
+        }
+        let anchored = matches!(nodes.first(), Some(Node::Assert(Assertion::Start)))
+            && !nodes[..index].iter().any(|node| matches!(node, Node::Repeat(inner, _, None) if matches!(inner.as_ref(), Node::Set(ranges)
+                if ranges.iter().any(|&(low, high)| low <= 'a' as u32 && 'a' as u32 <= high))));
+        if !anchored {
+            return Some(letters.into_iter().filter_map(LogLevel::from_letter).collect());
+        }
+    }
+    None
+}
+
+fn describe_constraints(rule: &LogRule) -> Option<String> {
+    let mut constraints = Vec::new();
+    if let Some(levels) = &rule.levels {
+        constraints.push(format!("with level {}", levels.iter().map(|level| level.letter()).collect::<String>()));
+    }
+    if let Some(tag) = &rule.tag {
+        constraints.push(format!("with tag {}", tag));
+    }
+    if let Some(pid) = rule.pid {
+        constraints.push(format!("of pid {}", pid));
+    }
+    if rule.filter.is_some() {
+        constraints.push("matching the filter".to_string());
+    }
+    (!constraints.is_empty()).then(|| format!("only entries {}", constraints.join(", ")))
+}
+
+/**
+    Function to lint the patterns of the rules and count their matches in a sample log
+    * @param rules The rules, see log_rules::read_rules
+    * @param sample_path The log file the patterns are tried on
+    * @returns The lint of every rule, in the order of the rules, or an error if the sample can't be read
+ **/
+pub fn lint_rules(rules: &LogRules, sample_path: &str) -> Result<Vec<RuleLint>, LogAnalysisError> {
+    let regexes = rules.rules.iter()
+        .map(|rule| Regex::new(&rule.pattern).map_err(|error| LogAnalysisError::InvalidPattern { rule: rule.name.clone(), error }))
+        .collect::<Result<Vec<Regex>, LogAnalysisError>>()?;
+
+    let mut lints: Vec<RuleLint> = Vec::new();
+    let mut level_rules = Vec::new();
+    for rule in &rules.rules {
+        let mut warnings = Vec::new();
+        let parsed = log_automata::parse_pattern(&rule.pattern, Alphabet::Char);
+        let nodes = parsed.as_ref().map(flatten).unwrap_or_default();
+        if nodes.first().is_some_and(|node| is_any_text(node)) {
+            warnings.push(LintWarning {
+                kind: LintKind::LeadingWildcard,
+                message: "starts with .* but the pattern is searched anywhere in the line, remove it".to_string(),
+            });
+        }
+        if nodes.len() > 1 && nodes.last().is_some_and(|node| is_any_text(node)) {
+            warnings.push(LintWarning {
+                kind: LintKind::TrailingWildcard,
+                message: "ends with .* which matches anything, even nothing, remove it".to_string(),
+            });
+        }
+        if nodes.last().is_some_and(|node| ends_with_space(node)) {
+            warnings.push(LintWarning {
+                kind: LintKind::TrailingSpace,
+                message: "ends with a space, so the match has to be followed by one and never ends the line; use \\b or $ if that isn't intended".to_string(),
+            });
+        }
+        let levels = level_letters(&nodes).filter(|_| rule.levels.is_none());
+        if let Some(levels) = &levels {
+            let letters: String = levels.iter().map(|level| level.letter()).collect();
+            warnings.push(LintWarning {
+                kind: LintKind::LevelLetter,
+                message: format!("{} can be matched in the tag or the message too, use level = {} instead", letters, letters),
+            });
+        }
+        level_rules.push(levels);
+
+        let mut explanation = explain_pattern(&rule.pattern);
+        if let (Ok(text), Some(constraints)) = (&mut explanation, describe_constraints(rule)) {
+            *text = format!("{}; {}", text, constraints);
+        }
+        lints.push(RuleLint {
+            name: rule.name.clone(),
+            pattern: rule.pattern.clone(),
+            explanation,
+            warnings,
+            pattern_matches: 0,
+            rule_matches: 0,
+            examples: Vec::new(),
+        });
+    }
+
+    // The entries of another level matching a level letter pattern, the first one is shown
+    let mut other_levels = vec![(0, None); lints.len()];
+    for log_line in logcat::read_log_file(sample_path).map_err(LogAnalysisError::io(sample_path))? {
+        let log_line = log_line.map_err(LogAnalysisError::io(sample_path))?;
+        let text = match &log_line {
+            LogLine::Entry(entry) => entry.raw.as_str(),
+            LogLine::Unparsed { text, .. } => text.as_str(),
+        };
+        for (index, regex) in regexes.iter().enumerate() {
+            if !regex.is_match(text) {
+                continue;
+            }
+            lints[index].pattern_matches += 1;
+            if let (Some(levels), LogLine::Entry(entry)) = (&level_rules[index], &log_line) {
+                if !levels.contains(&entry.level) {
+                    let (count, example) = &mut other_levels[index];
+                    *count += 1;
+                    example.get_or_insert_with(|| text.lines().next().unwrap_or("").to_string());
+                }
+            }
+        }
+        for index in rules.matching_rules(&log_line) {
+            let lint = &mut lints[index];
+            lint.rule_matches += 1;
+            if lint.examples.len() < EXAMPLES {
+                lint.examples.push(text.lines().next().unwrap_or("").to_string());
+            }
+        }
+    }
+
+    for (lint, (count, example)) in lints.iter_mut().zip(other_levels) {
+        if let (Some(warning), Some(example)) = (lint.warnings.iter_mut().find(|warning| warning.kind == LintKind::LevelLetter), example) {
+            warning.message = format!("{}; {} matching lines are of another level, e.g. {}", warning.message, count, example);
+        }
+        if lint.pattern_matches == 0 {
+            lint.warnings.push(LintWarning { kind: LintKind::NoMatches, message: "no line of the sample log matches the pattern".to_string() });
+        }
+    }
+    Ok(lints)
+}
+
+/**
+    Function to write the lints, one block per rule
+    * @param out Where to write
+    * @param lints The lints, see lint_rules
+ **/
+pub fn write_lints(out: &mut dyn Write, lints: &[RuleLint]) -> io::Result<()> {
+    for lint in lints {
+        writeln!(out, "[{}]", lint.name)?;
+        writeln!(out, "    pattern: {:?}", lint.pattern)?;
+        match &lint.explanation {
+            Ok(explanation) => writeln!(out, "    matches: {}", explanation)?,
+            Err(err) => writeln!(out, "    can't be explained {}", err)?,
+        }
+        writeln!(out, "    matching lines: {} (pattern), {} (rule)", lint.pattern_matches, lint.rule_matches)?;
+        for warning in &lint.warnings {
+            writeln!(out, "    warning ({}): {}", warning.kind, warning.message)?;
+        }
+        for example in &lint.examples {
+            writeln!(out, "    e.g. {}", example)?;
+        }
+    }
+    let warnings: usize = lints.iter().map(|lint| lint.warnings.len()).sum();
+    writeln!(out, "{} warnings in {} rules", warnings, lints.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, pattern: &str) -> LogRule {
+        LogRule { name: name.to_string(), pattern: pattern.to_string(), levels: None, tag: None, pid: None, filter: None }
+    }
+
+    fn lint(rules: Vec<LogRule>, log: &str) -> Vec<RuleLint> {
+        let path = std::env::temp_dir().join(format!("{}_{}_lint.log", std::process::id(), rules[0].name));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, log).unwrap();
+        let lints = lint_rules(&LogRules::new(rules).unwrap(), path);
+        std::fs::remove_file(path).unwrap();
+        lints.unwrap()
+    }
+
+    fn kinds(lint: &RuleLint) -> Vec<LintKind> {
+        lint.warnings.iter().map(|warning| warning.kind).collect()
+    }
+
+    #[test]
+    fn trailing_space_is_found_however_it_is_written() {
+        let patterns = ["Wifi ", "Wifi +", "Wifi[ ]", "Wifi\\ ", "Wifi\\\\ ", "Wifi\\b", "Wifi\\s", "Wifi *", "(Wifi |Audio )"];
+        let lints = lint(patterns.iter().map(|pattern| rule("space", pattern)).collect(), "I/Wifi ( 100): connected \n");
+        let warned: Vec<&str> = lints.iter()
+            .filter(|lint| kinds(lint).contains(&LintKind::TrailingSpace))
+            .map(|lint| lint.pattern.as_str())
+            .collect();
+        assert_eq!(warned, ["Wifi ", "Wifi +", "Wifi[ ]", "Wifi\\ ", "Wifi\\\\ "]);
+    }
+
+    #[test]
+    fn whole_words_and_anchored_patterns_are_explained() {
+        assert_eq!(explain_pattern("").unwrap(), "every line");
+        assert_eq!(explain_pattern("\\bE\\b").unwrap(), "\"E\" as a whole word, anywhere in the line");
+        assert_eq!(explain_pattern("\\b(E|W)\\b").unwrap(), "either \"E\" or \"W\" as a whole word, anywhere in the line");
+        assert_eq!(explain_pattern("\\bx").unwrap(), "a word boundary, then \"x\", anywhere in the line");
+        assert_eq!(explain_pattern("^E/").unwrap(), "the start of the line, then \"E/\"");
+        assert_eq!(explain_pattern("a(").unwrap_err().message, "expected )");
+    }
+
+    #[test]
+    fn wildcards_level_letters_and_unmatched_patterns_are_warned_about() {
+        let log = "\
+10-15 10:18:40.000   100   100 E Wifi: lost
+10-15 10:18:41.000   100   100 I Wifi: E is the name of the network
+10-15 10:18:42.000   100   100 W Audio: underrun
+";
+        let mut with_level = rule("with level", " E ");
+        with_level.levels = Some(vec![LogLevel::Error]);
+        let lints = lint(vec![
+            rule("leading", ".*Wifi"),
+            rule("trailing", "Wifi.*"),
+            rule("anything", ".*"),
+            rule("letter", " E "),
+            rule("letters", "\\b[EW]\\b"),
+            rule("anchored", "^[0-9-]+ [0-9:.]+ +[0-9]+ +[0-9]+ E "),
+            with_level,
+            rule("missing", "Bluetooth"),
+        ], log);
+
+        assert_eq!(kinds(&lints[0]), [LintKind::LeadingWildcard]);
+        assert_eq!(kinds(&lints[1]), [LintKind::TrailingWildcard]);
+        assert_eq!(kinds(&lints[2]), [LintKind::LeadingWildcard]);
+        assert_eq!(kinds(&lints[3]), [LintKind::TrailingSpace, LintKind::LevelLetter]);
+        assert_eq!(lints[3].warnings[1].message,
+                   "E can be matched in the tag or the message too, use level = E instead; \
+                    1 matching lines are of another level, e.g. 10-15 10:18:41.000   100   100 I Wifi: E is the name of the network");
+        assert_eq!(kinds(&lints[4]), [LintKind::LevelLetter]);
+        assert!(lints[4].warnings[0].message.starts_with("EW can be matched"));
+        assert_eq!(kinds(&lints[5]), [LintKind::TrailingSpace]);
+        assert_eq!(kinds(&lints[6]), [LintKind::TrailingSpace]);
+        assert_eq!(kinds(&lints[7]), [LintKind::NoMatches]);
+
+        // The letter is found in two lines, the level constraint keeps the entry of level E only
+        assert_eq!((lints[6].pattern_matches, lints[6].rule_matches), (2, 1));
+        assert_eq!(lints[6].explanation.as_ref().unwrap(), "\" E \", anywhere in the line; only entries with level E");
+        assert_eq!(lints[0].examples.len(), 2);
+    }
+
+    #[test]
+    fn lints_are_written_one_block_per_rule() {
+        let lints = lint(vec![rule("errors", "^E/"), rule("multiline", "(?m)^W")], "E/Wifi( 100): lost\n");
+        let mut out = Vec::new();
+        write_lints(&mut out, &lints).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("\
+[errors]
+    pattern: \"^E/\"
+    matches: the start of the line, then \"E/\"
+    matching lines: 1 (pattern), 1 (rule)
+    e.g. E/Wifi( 100): lost
+[multiline]
+    pattern: \"(?m)^W\"
+    can't be explained {}
+    matching lines: 0 (pattern), 0 (rule)
+    warning (no matches): no line of the sample log matches the pattern
+1 warnings in 2 rules
+", lints[1].explanation.as_ref().unwrap_err()));
+    }
+}
@@ -0,0 +1,247 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::log_error::LogAnalysisError;
+use crate::logcat::{self, LogFormat, LogLine, LogcatEntry};
+
+// Number of regressions listed in the summary
+const LISTED_REGRESSIONS: usize = 10;
+
+/**
+    Function to get the buffer of a logcat buffer marker line
+    * @param text The line, e.g. --------- beginning of system
+    * @returns The name of the buffer, None if the line is not a marker
+ **/
+pub fn buffer_marker(text: &str) -> Option<&str> {
+    let rest = text.trim().strip_prefix("---------")?.trim_start();
+    rest.strip_prefix("beginning of ")
+        .or_else(|| rest.strip_prefix("switch to "))
+        .map(|buffer| buffer.trim())
+}
+
+/**
+    Struct to represent a part of a file between two buffer markers
+    * file The index of the file
+    * buffer The buffer named by the marker, empty before the first marker
+    * first_line The line where the segment starts
+    * entries The number of entries in the segment
+ **/
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub file: usize,
+    pub buffer: String,
+    pub first_line: usize,
+    pub entries: usize,
+}
+
+/**
+    Struct to represent an entry that is older than an earlier entry of its segment
+    * file, line_number Where the entry is
+    * previous The latest timestamp before the entry
+    * timestamp The timestamp of the entry
+    * tag The tag of the entry
+ **/
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub file: usize,
+    pub line_number: usize,
+    pub previous: String,
+    pub timestamp: String,
+    pub tag: String,
+}
+
+/**
+    Struct to represent one entry of the merged stream
+    * file The index of the file the entry comes from
+    * segment The index of its segment in MergedLog::segments
+    * entry The entry
+ **/
+#[derive(Debug, Clone)]
+pub struct MergedEntry {
+    pub file: usize,
+    pub segment: usize,
+    pub entry: LogcatEntry,
+}
+
+/**
+    Struct to represent several log files merged into one time-ordered stream
+    * files The paths of the files, in the order they were given
+    * formats The detected format of every file
+    * entries The entries of every file, ordered by timestamp
+    * segments The segments of every file
+    * regressions The entries that go back in time within their segment
+ **/
+pub struct MergedLog {
+    pub files: Vec<String>,
+    pub formats: Vec<LogFormat>,
+    pub entries: Vec<MergedEntry>,
+    pub segments: Vec<Segment>,
+    pub regressions: Vec<Regression>,
+}
+
+// The fraction of the seconds is padded, so timestamps with a different precision compare correctly
+fn timestamp_key(entry: &LogcatEntry) -> String {
+    let (seconds, fraction) = entry.time.split_once('.').unwrap_or((&entry.time, ""));
+    format!("{} {}.{:0<9}", entry.date, seconds, fraction)
+}
+
+/**
+    Function to merge log files into one stream ordered by timestamp
+    * Entries with the same timestamp keep their order: first by the order of the files, then by their
+    * position in the file. Entries without a timestamp (e.g. brief format) stay after the entry before them.
+    * The buffer markers split the files into segments; an entry older than an earlier entry of the same
+    * segment is a regression. Every buffer starts again from its own oldest line, so the comparison
+    * restarts at each marker.
+    * @param paths The log files, in any logcat format
+    * @returns The merged log, or the first file that couldn't be read
+ **/
+pub fn merge_log_files(paths: &[&str]) -> Result<MergedLog, LogAnalysisError> {
+    let mut keyed: Vec<(String, usize, usize, MergedEntry)> = Vec::new();
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut regressions: Vec<Regression> = Vec::new();
+    let mut formats: Vec<LogFormat> = Vec::new();
+
+    for (file, path) in paths.iter().enumerate() {
+        segments.push(Segment { file, buffer: String::new(), first_line: 1, entries: 0 });
+        let mut latest: Option<(String, String)> = None;
+        let mut key = String::new();
+
+        let reader = logcat::read_log_file(path).map_err(LogAnalysisError::io(path))?;
+        formats.push(reader.format());
+        for (position, log_line) in reader.enumerate() {
+            let entry = match log_line.map_err(LogAnalysisError::io(path))? {
+                LogLine::Entry(entry) => entry,
+                LogLine::Unparsed { line_number, text } => {
+                    if let Some(buffer) = buffer_marker(&text) {
+                        // A marker on the first line names the buffer of the implicit first segment
+                        let segment = segments.last_mut().unwrap();
+                        if segment.file == file && segment.entries == 0 {
+                            segment.buffer = buffer.to_string();
+                            segment.first_line = line_number;
+                        } else {
+                            segments.push(Segment { file, buffer: buffer.to_string(), first_line: line_number, entries: 0 });
+                        }
+                        latest = None;
+                    }
+                    continue;
+                }
+            };
+
+            if !entry.time.is_empty() {
+                key = timestamp_key(&entry);
+                let timestamp = format!("{} {}", entry.date, entry.time);
+                match &latest {
+                    Some((latest_key, previous)) if key < *latest_key => regressions.push(Regression {
+                        file,
+                        line_number: entry.line_number,
+                        previous: previous.clone(),
+                        timestamp,
+                        tag: entry.tag.clone(),
+                    }),
+                    Some((latest_key, _)) if key == *latest_key => {}
+                    _ => latest = Some((key.clone(), timestamp)),
+                }
+            }
+
+            let segment = segments.len() - 1;
+            segments[segment].entries += 1;
+            keyed.push((key.clone(), file, position, MergedEntry { file, segment, entry }));
+        }
+    }
+
+    keyed.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    Ok(MergedLog {
+        files: paths.iter().map(|path| path.to_string()).collect(),
+        formats,
+        entries: keyed.into_iter().map(|(_, _, _, entry)| entry).collect(),
+        segments,
+        regressions,
+    })
+}
+
+impl MergedLog {
+    /**
+        Function to write the summary of the merge: the segments and the regressions
+        * @param out Where to write the summary
+     **/
+    pub fn write_summary(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "Merged {} entries from {} files", self.entries.len(), self.files.len())?;
+        for segment in &self.segments {
+            let buffer = if segment.buffer.is_empty() { "(no marker)" } else { segment.buffer.as_str() };
+            writeln!(out, "{} line {}: buffer {}, {} entries",
+                     self.files[segment.file], segment.first_line, buffer, segment.entries)?;
+        }
+        writeln!(out, "Timestamp regressions: {}", self.regressions.len())?;
+        for regression in self.regressions.iter().take(LISTED_REGRESSIONS) {
+            writeln!(out, "{} line {}: {} {} after {}",
+                     self.files[regression.file], regression.line_number, regression.timestamp, regression.tag, regression.previous)?;
+        }
+        if self.regressions.len() > LISTED_REGRESSIONS {
+            writeln!(out, "... and {} more", self.regressions.len() - LISTED_REGRESSIONS)?;
+        }
+        Ok(())
+    }
+
+    /**
+        Function to save the merged stream
+        * The entries are written after a marker, exactly as they were read (with their continuation lines, an
+        * empty line after the entries of long format), so the merged file can be read and analyzed again when the
+        * files have the same format
+        * @param path The output file
+     **/
+    pub fn save(&self, path: &str) -> Result<(), LogAnalysisError> {
+        let write = || -> io::Result<()> {
+            let mut out = BufWriter::new(File::create(path)?);
+            writeln!(out, "--------- beginning of merged")?;
+            for merged in &self.entries {
+                writeln!(out, "{}", merged.entry.raw)?;
+                if self.formats[merged.file] == LogFormat::Long {
+                    writeln!(out)?;
+                }
+            }
+            out.flush()
+        };
+        write().map_err(LogAnalysisError::output(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved_and_read_again(path: &str) -> (Vec<LogcatEntry>, Vec<LogLine>) {
+        let merged = merge_log_files(&[path]).unwrap();
+        let output = std::env::temp_dir().join(format!("{}_{}", std::process::id(), path.replace('/', "_")));
+        let output = output.to_str().unwrap();
+        merged.save(output).unwrap();
+        let lines = logcat::read_log_file(output).unwrap().collect::<io::Result<Vec<LogLine>>>().unwrap();
+        std::fs::remove_file(output).unwrap();
+        (merged.entries.into_iter().map(|merged| merged.entry).collect(), lines)
+    }
+
+    #[test]
+    fn a_missing_file_is_named_in_the_error() {
+        let result = merge_log_files(&["src/resources/sample_time.log", "src/resources/missing.log"]);
+        assert!(matches!(result, Err(LogAnalysisError::Io { path, .. }) if path == "src/resources/missing.log"));
+    }
+
+    #[test]
+    fn saved_entries_are_read_back_unchanged() {
+        for path in ["src/resources/sample_brief.log", "src/resources/sample_long.log", "src/resources/sample_time.log"] {
+            let (entries, lines) = saved_and_read_again(path);
+            let read: Vec<&LogcatEntry> = lines.iter()
+                .filter_map(|line| match line {
+                    LogLine::Entry(entry) => Some(entry),
+                    LogLine::Unparsed { text, .. } => {
+                        assert!(buffer_marker(text).is_some(), "{}: unparsed line {}", path, text);
+                        None
+                    }
+                })
+                .collect();
+            assert_eq!(read.len(), entries.len(), "{}", path);
+            for (read, entry) in read.iter().zip(&entries) {
+                assert_eq!((&read.time, &read.tag, &read.message), (&entry.time, &entry.tag, &entry.message), "{}", path);
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::logcat::LogcatEntry;
+
+/**
+    Struct to represent the lifetime of one process
+    * pid The process id
+    * name The package or process name, None until an event names it
+    * start The timestamp of the fork, empty if the process was running when the capture started
+    * end The timestamp of its death, None if it was still running at the end of the capture
+ **/
+#[derive(Debug, Clone)]
+pub struct ProcessLifetime {
+    pub pid: u32,
+    pub name: Option<String>,
+    pub start: String,
+    pub end: Option<String>,
+}
+
+impl ProcessLifetime {
+    pub fn contains(&self, timestamp: &str) -> bool {
+        self.start.as_str() <= timestamp && self.end.as_ref().is_none_or(|end| timestamp <= end.as_str())
+    }
+}
+
+enum ProcessEvent {
+    Forked(u32),
+    Named(u32, String),
+    Died(u32, Option<String>),
+}
+
+/**
+    Builds the pid -> process name timeline from the lifecycle events of the log:
+    * Zygote: Forked child process 804 starts a process
+    * ActivityManager: Start proc 804:com.android.systemui/u0a77 for ... names it
+    * ActivityManager: Killing 804:com.android.systemui/u0a77 (adj 250): ... names it
+    * ActivityManager: Process com.android.systemui (pid 804) has died ends it
+    * Zygote: Process 804 exited due to signal 9 (Killed) ends it
+    * The zygote and the system server name themselves when they start.
+    * A pid that is forked again after its process ended gets a new lifetime, so reused pids are resolved
+    * by the time of the entry.
+ **/
+pub struct ProcessNames {
+    lifetimes: Vec<ProcessLifetime>,
+    open: HashMap<u32, usize>,
+    start_proc: Regex,
+    killing: Regex,
+    has_died: Regex,
+    forked: Regex,
+    exited: Regex,
+}
+
+impl ProcessNames {
+    pub fn new() -> Self {
+        ProcessNames {
+            lifetimes: Vec::new(),
+            open: HashMap::new(),
+            start_proc: Regex::new(r"^Start proc (\d+):([^/\s]+)").unwrap(),
+            killing: Regex::new(r"^Killing (\d+):([^/\s]+)").unwrap(),
+            has_died: Regex::new(r"^Process (\S+) \(pid (\d+)\) has died").unwrap(),
+            forked: Regex::new(r"^Forked child process (\d+)").unwrap(),
+            exited: Regex::new(r"^Process (\d+) exited").unwrap(),
+        }
+    }
+
+    fn event(&self, entry: &LogcatEntry) -> Option<ProcessEvent> {
+        let message = entry.message.as_str();
+        let number = |text: &str| text.parse::<u32>().ok();
+        match entry.tag.as_str() {
+            "ActivityManager" => {
+                if let Some(captures) = self.start_proc.captures(message).or_else(|| self.killing.captures(message)) {
+                    return Some(ProcessEvent::Named(number(&captures[1])?, captures[2].to_string()));
+                }
+                let captures = self.has_died.captures(message)?;
+                Some(ProcessEvent::Died(number(&captures[2])?, Some(captures[1].to_string())))
+            }
+            "Zygote" => {
+                if let Some(captures) = self.forked.captures(message) {
+                    return Some(ProcessEvent::Forked(number(&captures[1])?));
+                }
+                let captures = self.exited.captures(message)?;
+                Some(ProcessEvent::Died(number(&captures[1])?, None))
+            }
+            "AndroidRuntime" if message.contains("START com.android.internal.os.ZygoteInit") => {
+                Some(ProcessEvent::Named(entry.pid?, "zygote".to_string()))
+            }
+            "SystemServer" if message.starts_with("Entered the Android system server") => {
+                Some(ProcessEvent::Named(entry.pid?, "system_server".to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, pid: u32, start: String) -> usize {
+        self.lifetimes.push(ProcessLifetime { pid, name: None, start, end: None });
+        self.open.insert(pid, self.lifetimes.len() - 1);
+        self.lifetimes.len() - 1
+    }
+
+    // The first lifetime of a pid starts with the capture, later ones when they are seen
+    fn open_lifetime(&mut self, pid: u32, timestamp: &str) -> usize {
+        if let Some(&index) = self.open.get(&pid) {
+            return index;
+        }
+        let seen = self.lifetimes.iter().any(|lifetime| lifetime.pid == pid);
+        self.start(pid, if seen { timestamp.to_string() } else { String::new() })
+    }
+
+    /**
+        Function to process an entry, only the lifecycle events change the timeline
+        * @param entry The entry
+     **/
+    pub fn add(&mut self, entry: &LogcatEntry) {
+        let Some(event) = self.event(entry) else {
+            return;
+        };
+        let timestamp = format!("{} {}", entry.date, entry.time);
+        match event {
+            ProcessEvent::Forked(pid) => {
+                if let Some(index) = self.open.remove(&pid) {
+                    self.lifetimes[index].end = Some(timestamp.clone());
+                }
+                self.start(pid, timestamp);
+            }
+            ProcessEvent::Named(pid, name) => {
+                let mut index = self.open_lifetime(pid, &timestamp);
+                // A different name without a death in between means the pid was reused
+                if self.lifetimes[index].name.as_ref().is_some_and(|current| *current != name) {
+                    self.lifetimes[index].end = Some(timestamp.clone());
+                    index = self.start(pid, timestamp);
+                }
+                self.lifetimes[index].name = Some(name);
+            }
+            ProcessEvent::Died(pid, name) => {
+                let index = self.open_lifetime(pid, &timestamp);
+                let lifetime = &mut self.lifetimes[index];
+                if lifetime.name.is_none() {
+                    lifetime.name = name;
+                }
+                lifetime.end = Some(timestamp);
+                self.open.remove(&pid);
+            }
+        }
+    }
+
+    /**
+        Function to resolve a pid at a given time
+        * @param pid The process id
+        * @param timestamp The time of the entry, e.g. 10-15 10:18:51.651
+        * @returns The name of the process that had this pid at that time, None if it is unknown
+     **/
+    pub fn name_at(&self, pid: u32, timestamp: &str) -> Option<&str> {
+        self.lifetimes.iter()
+            .filter(|lifetime| lifetime.pid == pid && lifetime.contains(timestamp))
+            .filter_map(|lifetime| lifetime.name.as_deref())
+            .next_back()
+    }
+
+    /**
+        Function to get every name a pid had during the capture
+        * @param pid The process id
+        * @returns The names in chronological order, empty if the pid was never named
+     **/
+    pub fn names_of(&self, pid: u32) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for name in self.lifetimes.iter().filter(|lifetime| lifetime.pid == pid).filter_map(|lifetime| lifetime.name.as_deref()) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /**
+        Function to describe a pid for the reports, e.g. 804 (com.android.systemui)
+        * @param pid The process id
+        * @returns The pid with its names, or only the pid if it was never named
+     **/
+    pub fn describe(&self, pid: u32) -> String {
+        let names = self.names_of(pid);
+        if names.is_empty() {
+            pid.to_string()
+        } else {
+            format!("{} ({})", pid, names.join(", "))
+        }
+    }
+
+    /**
+        Function to describe a pid at a given time, e.g. 804 (com.android.systemui)
+        * @param pid The process id
+        * @param timestamp The time of the entry
+        * @returns The pid with the name it had at that time, or only the pid if it is unknown
+     **/
+    pub fn describe_at(&self, pid: u32, timestamp: &str) -> String {
+        match self.name_at(pid, timestamp) {
+            Some(name) => format!("{} ({})", pid, name),
+            None => pid.to_string(),
+        }
+    }
+
+    /**
+        Function to get the lifetimes that have a name
+        * @returns The lifetimes in the order they started
+     **/
+    pub fn lifetimes(&self) -> Vec<&ProcessLifetime> {
+        self.lifetimes.iter().filter(|lifetime| lifetime.name.is_some()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::{LineParser, LogFormat};
+
+    fn process_names(lines: &[&str]) -> ProcessNames {
+        let parser = LineParser::new(LogFormat::ThreadTime);
+        let mut names = ProcessNames::new();
+        for line in lines {
+            names.add(&parser.parse(line).unwrap());
+        }
+        names
+    }
+
+    #[test]
+    fn reused_pid_is_resolved_by_the_time_of_the_entry() {
+        let names = process_names(&[
+            "10-15 10:18:40.000   500   520 I ActivityManager: Killing 804:com.example.mail/u0a70 (adj 900): empty",
+            "10-15 10:18:41.000   500   520 I ActivityManager: Process com.example.mail (pid 804) has died: cch",
+            "10-15 10:18:42.000   300   300 D Zygote: Forked child process 804",
+            "10-15 10:18:42.100   500   520 I ActivityManager: Start proc 804:com.example.maps/u0a71 for activity",
+            "10-15 10:18:45.000   300   300 I Zygote: Process 804 exited due to signal 9 (Killed)",
+        ]);
+        // The first lifetime started before the capture
+        assert_eq!(names.name_at(804, "10-15 10:18:30.000"), Some("com.example.mail"));
+        assert_eq!(names.name_at(804, "10-15 10:18:43.000"), Some("com.example.maps"));
+        assert_eq!(names.name_at(804, "10-15 10:18:46.000"), None);
+        assert_eq!(names.names_of(804), ["com.example.mail", "com.example.maps"]);
+        assert_eq!(names.describe(804), "804 (com.example.mail, com.example.maps)");
+        assert_eq!(names.describe_at(804, "10-15 10:18:43.000"), "804 (com.example.maps)");
+        assert_eq!(names.describe(805), "805");
+
+        let lifetimes = names.lifetimes();
+        assert_eq!(lifetimes.len(), 2);
+        assert_eq!((lifetimes[0].start.as_str(), lifetimes[0].end.as_deref()), ("", Some("10-15 10:18:41.000")));
+        assert_eq!((lifetimes[1].start.as_str(), lifetimes[1].end.as_deref()), ("10-15 10:18:42.000", Some("10-15 10:18:45.000")));
+    }
+
+    #[test]
+    fn new_name_without_a_death_starts_a_new_lifetime() {
+        let names = process_names(&[
+            "10-15 10:18:40.000   500   520 I ActivityManager: Start proc 900:com.example.old/u0a70 for service",
+            "10-15 10:18:50.000   500   520 I ActivityManager: Start proc 900:com.example.new/u0a72 for service",
+            "10-15 10:18:51.000   900   900 I AndroidRuntime: >>>>>> START com.android.internal.os.ZygoteInit uid 0 <<<<<<",
+        ]);
+        assert_eq!(names.name_at(900, "10-15 10:18:45.000"), Some("com.example.old"));
+        assert_eq!(names.name_at(900, "10-15 10:18:50.500"), Some("com.example.new"));
+        assert_eq!(names.name_at(900, "10-15 10:18:52.000"), Some("zygote"));
+        assert_eq!(names.lifetimes().len(), 3);
+    }
+}
@@ -0,0 +1,255 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use crate::log_error::LogAnalysisError;
+use crate::logcat::{self, LineParser, DETECTION_LINES};
+
+/**
+    Struct to represent one redaction rule
+    * name The name of the rule, used in the pseudonyms, e.g. <email-1>
+    * pattern The regular expression of the sensitive values
+    * skip_dotted_numbers Whether a match that is part of a longer dotted number is kept, see is_part_of_dotted_number
+ **/
+pub struct RedactionRule {
+    pub name: String,
+    pub pattern: Regex,
+    pub skip_dotted_numbers: bool,
+}
+
+// The rule of the rules file whose matches inside a longer dotted number are kept
+const DOTTED_NUMBER_RULE: &str = "ipv4";
+
+/**
+    Replaces sensitive values with pseudonyms
+    * The same value always gets the same pseudonym, so the lines about the same account or address
+    * can still be correlated after the redaction.
+ **/
+pub struct Redactor {
+    rules: Vec<RedactionRule>,
+    pseudonyms: HashMap<(usize, String), String>,
+    next_number: Vec<usize>,
+    replacements: Vec<usize>,
+}
+
+// An address that is part of a longer dotted number (e.g. 1.2.3.4 in the version 1.2.3.4.5) is not replaced:
+// the regex crate has no lookaround, so the characters around the match are checked here
+fn is_part_of_dotted_number(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let mut after = text[end..].chars();
+    before.is_some_and(|c| c.is_ascii_digit() || c == '.')
+        || match after.next() {
+            Some('.') => after.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+}
+
+impl Redactor {
+    pub fn new(rules: Vec<RedactionRule>) -> Self {
+        let rule_count = rules.len();
+        Redactor {
+            rules,
+            pseudonyms: HashMap::new(),
+            next_number: vec![0; rule_count],
+            replacements: vec![0; rule_count],
+        }
+    }
+
+    fn pseudonym(&mut self, rule: usize, value: &str) -> String {
+        if let Some(pseudonym) = self.pseudonyms.get(&(rule, value.to_string())) {
+            return pseudonym.clone();
+        }
+        self.next_number[rule] += 1;
+        let pseudonym = format!("<{}-{}>", self.rules[rule].name, self.next_number[rule]);
+        self.pseudonyms.insert((rule, value.to_string()), pseudonym.clone());
+        pseudonym
+    }
+
+    /**
+        Function to redact a text with every rule
+        * @param text The text
+        * @returns The text with the sensitive values replaced
+     **/
+    pub fn redact_text(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in 0..self.rules.len() {
+            // The ranges are collected first, the pseudonyms need the redactor mutably
+            let skip_dotted_numbers = self.rules[rule].skip_dotted_numbers;
+            let ranges: Vec<(usize, usize)> = self.rules[rule].pattern.captures_iter(&text)
+                .filter_map(|captures| captures.name("value").or_else(|| captures.get(0)))
+                .map(|value| (value.start(), value.end()))
+                .filter(|&(start, end)| start < end && !(skip_dotted_numbers && is_part_of_dotted_number(&text, start, end)))
+                .collect();
+            if ranges.is_empty() {
+                continue;
+            }
+
+            let mut redacted = String::with_capacity(text.len());
+            let mut last = 0;
+            for (start, end) in ranges {
+                redacted.push_str(&text[last..start]);
+                redacted.push_str(&self.pseudonym(rule, &text[start..end]));
+                self.replacements[rule] += 1;
+                last = end;
+            }
+            redacted.push_str(&text[last..]);
+            text = redacted;
+        }
+        text
+    }
+
+    /**
+        Function to redact one line of a log
+        * Only the message of an entry is redacted, so the timestamp, the ids, the level and the tag stay
+        * parseable, also where the tag follows the message (process format); lines that are not entries
+        * (e.g. continuation lines) are redacted whole.
+        * @param line The line
+        * @param parser The parser of the format of the log
+        * @returns The redacted line
+     **/
+    pub fn redact_line(&mut self, line: &str, parser: &LineParser) -> String {
+        match parser.message_range(line) {
+            Some(range) => format!("{}{}{}", &line[..range.start], self.redact_text(&line[range.clone()]), &line[range.end..]),
+            None => self.redact_text(line),
+        }
+    }
+
+    /**
+        Function to get the statistics of the redaction
+        * @returns The name of every rule with the number of replaced values and distinct values
+     **/
+    pub fn counts(&self) -> Vec<(String, usize, usize)> {
+        self.rules.iter().enumerate()
+            .map(|(index, rule)| (rule.name.clone(), self.replacements[index], self.next_number[index]))
+            .collect()
+    }
+}
+
+/**
+    Function to read the redaction rules from a file
+    * @param filename The name of the file to read from
+    * @returns The rules in the order of the file, or why the file couldn't be read
+
+    * Every line is name = regular expression; if the expression has a group named value, only that
+    * group is replaced. A match of the ipv4 rule that is part of a longer dotted number (e.g. a version)
+    * is not replaced, so its pattern only has to match the address itself. Empty lines and lines starting
+    * with # are skipped.
+ **/
+pub fn read_redaction_rules(filename: &str) -> Result<Vec<RedactionRule>, LogAnalysisError> {
+    let reader = BufReader::new(File::open(filename).map_err(LogAnalysisError::io(filename))?);
+    let invalid = |message: String| LogAnalysisError::InvalidRules { path: filename.to_string(), message };
+
+    let mut rules = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(LogAnalysisError::io(filename))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (name, pattern) = trimmed.split_once('=')
+            .ok_or_else(|| invalid(format!("line {}: expected name = pattern", index + 1)))?;
+        let name = name.trim().to_string();
+        let pattern = Regex::new(pattern.trim())
+            .map_err(|error| LogAnalysisError::InvalidPattern { rule: name.clone(), error })?;
+        let skip_dotted_numbers = name == DOTTED_NUMBER_RULE;
+        rules.push(RedactionRule { name, pattern, skip_dotted_numbers });
+    }
+    Ok(rules)
+}
+
+/**
+    Function to write a redacted copy of a log file
+    * Every line is written, in the same order, so the copy has the same structure as the original
+    * @param input_path The log file, in any logcat format
+    * @param output_path The redacted copy
+    * @param redactor The redactor with the rules
+    * @returns The number of lines that changed
+ **/
+pub fn redact_log_file(input_path: &str, output_path: &str, redactor: &mut Redactor) -> Result<usize, LogAnalysisError> {
+    let read_error = LogAnalysisError::io(input_path);
+    let write_error = LogAnalysisError::output(output_path);
+    let mut lines = BufReader::new(File::open(input_path).map_err(&read_error)?).lines();
+    let mut first_lines = Vec::new();
+    while first_lines.len() < DETECTION_LINES {
+        match lines.next() {
+            Some(line) => first_lines.push(line.map_err(&read_error)?),
+            None => break,
+        }
+    }
+    let parser = LineParser::new(logcat::detect_format(&first_lines));
+
+    let mut out = BufWriter::new(File::create(output_path).map_err(&write_error)?);
+    let mut changed = 0;
+    for line in first_lines.into_iter().map(Ok).chain(lines) {
+        let line = line.map_err(&read_error)?;
+        let redacted = redactor.redact_line(&line, &parser);
+        if redacted != line {
+            changed += 1;
+        }
+        writeln!(out, "{}", redacted).map_err(&write_error)?;
+    }
+    out.flush().map_err(&write_error)?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor::new(read_redaction_rules("src/resources/redaction_rules.txt").unwrap())
+    }
+
+    #[test]
+    fn rules_file_errors_name_the_line_or_the_rule() {
+        let path = std::env::temp_dir().join(format!("{}_redaction_rules.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "# comment\nno pattern here\n").unwrap();
+        assert!(matches!(read_redaction_rules(path), Err(LogAnalysisError::InvalidRules { message, .. }) if message.starts_with("line 2:")));
+        std::fs::write(path, "serial = SN([0-9]+\n").unwrap();
+        assert!(matches!(read_redaction_rules(path), Err(LogAnalysisError::InvalidPattern { rule, .. }) if rule == "serial"));
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(read_redaction_rules(path), Err(LogAnalysisError::Io { .. })));
+    }
+
+    #[test]
+    fn adjacent_addresses_are_both_redacted() {
+        let mut redactor = redactor();
+        assert_eq!(redactor.redact_text("dns 1.2.3.4,5.6.7.8"), "dns <ipv4-1>,<ipv4-2>");
+        assert_eq!(redactor.redact_text("from 10.0.0.1."), "from <ipv4-3>.");
+    }
+
+    #[test]
+    fn versions_are_not_addresses() {
+        let mut redactor = redactor();
+        assert_eq!(redactor.redact_text("version 1.2.3.4.5"), "version 1.2.3.4.5");
+        assert_eq!(redactor.redact_text("build 256.1.2.3"), "build 256.1.2.3");
+    }
+
+    #[test]
+    fn other_rules_redact_values_next_to_dotted_numbers() {
+        let rules = vec![
+            RedactionRule { name: "ipv4".to_string(), pattern: Regex::new(r"\d+\.\d+\.\d+\.\d+").unwrap(), skip_dotted_numbers: true },
+            RedactionRule { name: "serial".to_string(), pattern: Regex::new(r"SN\d+").unwrap(), skip_dotted_numbers: false },
+        ];
+        let mut redactor = Redactor::new(rules);
+        assert_eq!(redactor.redact_text("v1.2.3.4.5 SN123.4"), "v1.2.3.4.5 <serial-1>.4");
+    }
+
+    #[test]
+    fn only_the_message_of_a_process_line_is_redacted() {
+        let mut redactor = redactor();
+        let parser = LineParser::new(logcat::LogFormat::Process);
+        assert_eq!(
+            redactor.redact_line("I(  515) Connected to 192.168.1.20  (WifiService)", &parser),
+            "I(  515) Connected to <ipv4-1>  (WifiService)",
+        );
+        // The tag after the message is kept even if a rule matches it
+        assert_eq!(
+            redactor.redact_line("I(  515) Connected  (10.0.0.1)", &parser),
+            "I(  515) Connected  (10.0.0.1)",
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use crate::log_automata::PatternError;
+use crate::log_error::LogAnalysisError;
+use crate::log_incidents::{Incident, IncidentKind};
+use crate::log_processes::ProcessNames;
+use crate::log_sessions::{SessionSummary, Sessions};
+use crate::log_stack_traces::CrashGroup;
+use crate::log_stats::{self, LogStatistics};
+use crate::log_timeline::{Burst, Timeline};
+
+// Number of rows of the rankings in the text report
+const TOP_N: usize = 10;
+
+/**
+    Struct to represent the results of the log analysis
+    * log_file The analyzed log file
+    * errors The problems of the tasks that failed, e.g. invalid rules or a line that couldn't be read; the other
+    * results are still complete, or cover the log up to the line that couldn't be read
+    * automata_fallbacks The rules matched by the regex engine although the automata were asked for, with the reason
+    * rule_counts The name of every counting rule with the number of matching lines
+    * unparsed_lines The line numbers of the lines that are not logcat entries
+    * stack_trace_count The number of stack traces found
+    * crash_groups The stack traces deduplicated by their fingerprint
+    * unique_process_ids The process ids found in the log
+    * most_common_error_process The process with the most errors, None if there are no errors
+    * incidents The main thread errors, fatal exceptions, ANRs and native crashes
+    * statistics The counts by pid, tid, tag and level
+    * timeline The counts by time bucket and level
+    * bursts The time windows with unusually many warnings and errors
+    * process_names The names of the processes, resolved from the lifecycle events of the log
+    * sessions The device boots and app lifecycles of the capture, each with its own summary
+ **/
+pub struct LogReport {
+    pub log_file: String,
+    pub errors: Vec<LogAnalysisError>,
+    pub automata_fallbacks: Vec<(String, PatternError)>,
+    pub rule_counts: Vec<(String, usize)>,
+    pub unparsed_lines: Vec<usize>,
+    pub stack_trace_count: usize,
+    pub crash_groups: Vec<CrashGroup>,
+    pub unique_process_ids: Vec<u32>,
+    pub most_common_error_process: Option<u32>,
+    pub incidents: Vec<Incident>,
+    pub statistics: LogStatistics,
+    pub timeline: Timeline,
+    pub bursts: Vec<Burst>,
+    pub process_names: ProcessNames,
+    pub sessions: Sessions,
+}
+
+/**
+    Writes a report in one output format
+    * The header is written once, at the beginning of a new or empty file, the reports after it
+ **/
+pub trait ReportWriter {
+    fn write_header(&self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_report(&self, report: &LogReport, out: &mut dyn Write) -> io::Result<()>;
+}
+
+/**
+    The output formats of the report
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    pub fn writer(&self) -> Box<dyn ReportWriter> {
+        match self {
+            ReportFormat::Text => Box::new(TextWriter),
+            ReportFormat::Json => Box::new(JsonWriter),
+            ReportFormat::Csv => Box::new(CsvWriter),
+        }
+    }
+}
+
+/**
+    What happens with an existing output file
+    * Overwrite The file is truncated
+    * Append The report is added to the end of the file
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteMode {
+    Overwrite,
+    Append,
+}
+
+/**
+    Plain text, the same lines that are printed on the screen
+ **/
+pub struct TextWriter;
+
+impl ReportWriter for TextWriter {
+    fn write_report(&self, report: &LogReport, out: &mut dyn Write) -> io::Result<()> {
+        for err in &report.errors {
+            writeln!(out, "Error: {}", err)?;
+        }
+        for (name, count) in &report.rule_counts {
+            writeln!(out, "{}: {}", name, count)?;
+        }
+        writeln!(out, "Lines that are not logcat entries: {:?}", report.unparsed_lines)?;
+        writeln!(out, "Stack traces: {}, distinct crashes: {}", report.stack_trace_count, report.crash_groups.len())?;
+        let names = &report.process_names;
+        for group in &report.crash_groups {
+            let process = group.example.pid.map_or("an unknown process".to_string(), |pid| names.describe_at(pid, &group.example.timestamp()));
+            writeln!(out, "Crash {} (x{}): {} in {}", group.fingerprint, group.count, group.exception_type, process)?;
+            writeln!(out, "{}", group.example)?;
+        }
+        writeln!(out, "Unique process ids: {:?}", report.unique_process_ids)?;
+        writeln!(out, "Process names:")?;
+        let rows: Vec<Vec<String>> = names.lifetimes().iter()
+            .map(|lifetime| vec![
+                lifetime.pid.to_string(),
+                lifetime.name.clone().unwrap_or_default(),
+                if lifetime.start.is_empty() { "before the capture".to_string() } else { lifetime.start.clone() },
+                lifetime.end.clone().unwrap_or_else(|| "running".to_string()),
+            ])
+            .collect();
+        log_stats::write_table(out, &["pid", "name", "started", "ended"], &rows)?;
+        match report.most_common_error_process {
+            Some(pid) => writeln!(out, "Most common error process: {}", names.describe(pid))?,
+            None => writeln!(out, "Most common error process: none")?,
+        }
+        let count = |kind: IncidentKind| report.incidents.iter().filter(|incident| incident.kind == kind).count();
+        writeln!(out, "Incidents: {} main thread errors, {} fatal exceptions, {} ANRs, {} native crashes",
+                 count(IncidentKind::MainThreadError), count(IncidentKind::FatalException),
+                 count(IncidentKind::Anr), count(IncidentKind::NativeCrash))?;
+        for incident in &report.incidents {
+            writeln!(out, "{}", incident)?;
+        }
+        report.statistics.write_tables(out, TOP_N, names)?;
+        report.timeline.write_sparklines(out)?;
+        writeln!(out, "Warning and error bursts: {}", report.bursts.len())?;
+        for burst in &report.bursts {
+            writeln!(out, "{} - {}: {} warnings and errors, baseline {:.1} per {}",
+                     burst.start, burst.end, burst.count, burst.baseline, report.timeline.bucket_size.name())?;
+        }
+        report.sessions.write_tables(out)
+    }
+}
+
+/**
+    JSON Lines, one JSON object per report on one line, so the reports appended to a file stay readable
+ **/
+pub struct JsonWriter;
+
+fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn json_name(name: Option<&str>) -> String {
+    name.map_or("null".to_string(), json_string)
+}
+
+fn json_summary(summary: &SessionSummary) -> String {
+    format!("\"start\": {}, \"end\": {}, \"duration\": {}, \"entries\": {}, \"errors\": {}, \"crashes\": {}, \"anrs\": {}",
+            json_string(&summary.start), json_string(&summary.end), summary.duration().map_or("null".to_string(), |seconds| seconds.to_string()),
+            summary.entries, summary.errors, summary.crashes, summary.anrs)
+}
+
+fn json_array<T>(values: &[T], to_json: impl Fn(&T) -> String) -> String {
+    let values: Vec<String> = values.iter().map(to_json).collect();
+    format!("[{}]", values.join(", "))
+}
+
+impl ReportWriter for JsonWriter {
+    fn write_report(&self, report: &LogReport, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{{")?;
+        write!(out, "\"log_file\": {}, ", json_string(&report.log_file))?;
+        write!(out, "\"errors\": {}, ", json_array(&report.errors, |err| json_string(&err.to_string())))?;
+        write!(out, "\"rule_counts\": {}, ", json_array(&report.rule_counts, |(name, count)| {
+            format!("{{\"name\": {}, \"count\": {}}}", json_string(name), count)
+        }))?;
+        write!(out, "\"unparsed_lines\": {}, ", json_array(&report.unparsed_lines, |line| line.to_string()))?;
+        write!(out, "\"stack_trace_count\": {}, ", report.stack_trace_count)?;
+        write!(out, "\"crash_groups\": {}, ", json_array(&report.crash_groups, |group| {
+            format!(
+                "{{\"fingerprint\": {}, \"exception_type\": {}, \"count\": {}, \"process\": {}, \"frames\": {}, \"causes\": {}, \"example\": {}}}",
+                json_string(&group.fingerprint),
+                json_string(&group.exception_type),
+                group.count,
+                json_name(group.example.pid.and_then(|pid| report.process_names.name_at(pid, &group.example.timestamp()))),
+                json_array(&group.example.frames(), |frame| json_string(frame)),
+                json_array(&group.example.causes(), |cause| json_string(cause)),
+                json_string(&group.example.to_string()),
+            )
+        }))?;
+        write!(out, "\"unique_process_ids\": {}, ", json_array(&report.unique_process_ids, |pid| pid.to_string()))?;
+        let most_common = report.most_common_error_process.map_or("null".to_string(), |pid| pid.to_string());
+        write!(out, "\"most_common_error_process\": {}, ", most_common)?;
+        write!(out, "\"process_names\": {}, ", json_array(&report.process_names.lifetimes(), |lifetime| {
+            format!("{{\"pid\": {}, \"name\": {}, \"start\": {}, \"end\": {}}}",
+                    lifetime.pid, json_name(lifetime.name.as_deref()), json_name(Some(lifetime.start.as_str()).filter(|start| !start.is_empty())),
+                    json_name(lifetime.end.as_deref()))
+        }))?;
+        write!(out, "\"incidents\": {}, ", json_array(&report.incidents, |incident| {
+            format!(
+                "{{\"kind\": {}, \"timestamp\": {}, \"pid\": {}, \"tid\": {}, \"tag\": {}, \"process\": {}, \"summary\": {}, \"lines\": {}, \"before\": {}, \"after\": {}}}",
+                json_string(&incident.kind.to_string()),
+                json_string(&incident.timestamp()),
+                incident.pid.map_or("null".to_string(), |pid| pid.to_string()),
+                incident.tid.map_or("null".to_string(), |tid| tid.to_string()),
+                json_string(&incident.tag),
+                json_name(incident.process.as_deref()),
+                json_string(&incident.summary),
+                json_array(&incident.lines, |line| json_string(line)),
+                json_array(&incident.before, |line| json_string(line)),
+                json_array(&incident.after, |line| json_string(line)),
+            )
+        }))?;
+
+        let statistics = &report.statistics;
+        let levels: Vec<String> = statistics.levels.iter()
+            .map(|(level, count)| format!("{}: {}", json_string(&level.to_string()), count))
+            .collect();
+        let processes = json_array(&statistics.top_processes(statistics.processes.len()), |(pid, process)| {
+            format!("{{\"pid\": {}, \"names\": {}, \"entries\": {}, \"errors\": {}, \"first_seen\": {}, \"last_seen\": {}}}",
+                    pid, json_array(&report.process_names.names_of(*pid), |name| json_string(name)), process.entries, process.errors, json_string(&process.first_seen), json_string(&process.last_seen))
+        });
+        let threads = json_array(&statistics.top_threads(statistics.threads.len()), |(tid, count)| {
+            format!("{{\"tid\": {}, \"entries\": {}}}", tid, count)
+        });
+        let tags = json_array(&statistics.top_tags(statistics.tags.len()), |(tag, stats)| {
+            format!("{{\"tag\": {}, \"entries\": {}, \"errors\": {}, \"error_rate\": {:.4}}}",
+                    json_string(tag), stats.entries, stats.errors, stats.error_rate())
+        });
+        write!(out, "\"statistics\": {{\"total\": {}, \"levels\": {{{}}}, \"processes\": {}, \"threads\": {}, \"tags\": {}}}, ",
+               statistics.total, levels.join(", "), processes, threads, tags)?;
+
+        let timeline = &report.timeline;
+        let buckets = json_array(&timeline.buckets(), |(start, counts)| {
+            let levels: Vec<String> = counts.iter()
+                .map(|(level, count)| format!("{}: {}", json_string(&level.to_string()), count))
+                .collect();
+            format!("{{\"start\": {}, \"levels\": {{{}}}}}", json_string(&timeline.label(*start)), levels.join(", "))
+        });
+        write!(out, "\"timeline\": {{\"bucket_size\": {}, \"buckets\": {}}}, ", json_string(timeline.bucket_size.name()), buckets)?;
+        write!(out, "\"bursts\": {}, ", json_array(&report.bursts, |burst| {
+            format!("{{\"start\": {}, \"end\": {}, \"count\": {}, \"baseline\": {:.4}}}",
+                    json_string(&burst.start), json_string(&burst.end), burst.count, burst.baseline)
+        }))?;
+        let boots = json_array(&report.sessions.boots, |boot| {
+            format!("{{\"reason\": {}, \"first_line\": {}, \"last_line\": {}, {}}}",
+                    json_string(&boot.reason.to_string()), boot.first_line, boot.last_line, json_summary(&boot.summary))
+        });
+        let apps = json_array(&report.sessions.apps, |app| {
+            format!("{{\"pid\": {}, \"name\": {}, \"started\": {}, \"ended\": {}, {}}}",
+                    app.pid, json_string(&app.name), app.started, app.ended, json_summary(&app.summary))
+        });
+        write!(out, "\"sessions\": {{\"boots\": {}, \"apps\": {}}}", boots, apps)?;
+        writeln!(out, "}}")
+    }
+}
+
+/**
+    One row per value: section,key,value
+ **/
+pub struct CsvWriter;
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl ReportWriter for CsvWriter {
+    fn write_header(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "section,key,value")
+    }
+
+    fn write_report(&self, report: &LogReport, out: &mut dyn Write) -> io::Result<()> {
+        let mut row = |section: &str, key: &str, value: &str| {
+            writeln!(out, "{},{},{}", csv_field(section), csv_field(key), csv_field(value))
+        };
+        row("log_file", "", &report.log_file)?;
+        for err in &report.errors {
+            row("error", "", &err.to_string())?;
+        }
+        for (name, count) in &report.rule_counts {
+            row("rule", name, &count.to_string())?;
+        }
+        for line in &report.unparsed_lines {
+            row("unparsed_line", "", &line.to_string())?;
+        }
+        row("stack_trace_count", "", &report.stack_trace_count.to_string())?;
+        for group in &report.crash_groups {
+            row("crash_count", &group.fingerprint, &group.count.to_string())?;
+            row("crash_exception_type", &group.fingerprint, &group.exception_type)?;
+            let process = group.example.pid.and_then(|pid| report.process_names.name_at(pid, &group.example.timestamp()));
+            row("crash_process", &group.fingerprint, process.unwrap_or(""))?;
+            row("crash_example", &group.fingerprint, &group.example.to_string())?;
+        }
+        for pid in &report.unique_process_ids {
+            row("process_id", "", &pid.to_string())?;
+        }
+        for lifetime in report.process_names.lifetimes() {
+            let key = format!("{} {}", lifetime.pid, lifetime.start);
+            row("process_name", &key, lifetime.name.as_deref().unwrap_or(""))?;
+            row("process_end", &key, lifetime.end.as_deref().unwrap_or(""))?;
+        }
+        if let Some(pid) = report.most_common_error_process {
+            row("most_common_error_process", "", &pid.to_string())?;
+        }
+        for incident in &report.incidents {
+            let key = format!("{} {}", incident.timestamp(), incident.pid.map_or("-".to_string(), |pid| pid.to_string()));
+            row("incident_kind", &key, &incident.kind.to_string())?;
+            row("incident_process", &key, incident.process.as_deref().unwrap_or(""))?;
+            row("incident_summary", &key, &incident.summary)?;
+            row("incident_lines", &key, &incident.lines.join("\n"))?;
+        }
+
+        let statistics = &report.statistics;
+        for (level, count) in &statistics.levels {
+            row("level_entries", &level.to_string(), &count.to_string())?;
+        }
+        for (pid, process) in statistics.top_processes(statistics.processes.len()) {
+            row("process_entries", &pid.to_string(), &process.entries.to_string())?;
+            row("process_errors", &pid.to_string(), &process.errors.to_string())?;
+            row("process_first_seen", &pid.to_string(), &process.first_seen)?;
+            row("process_last_seen", &pid.to_string(), &process.last_seen)?;
+        }
+        for (tid, count) in statistics.top_threads(statistics.threads.len()) {
+            row("thread_entries", &tid.to_string(), &count.to_string())?;
+        }
+        for (tag, stats) in statistics.top_tags(statistics.tags.len()) {
+            row("tag_entries", &tag, &stats.entries.to_string())?;
+            row("tag_errors", &tag, &stats.errors.to_string())?;
+            row("tag_error_rate", &tag, &format!("{:.4}", stats.error_rate()))?;
+        }
+
+        let timeline = &report.timeline;
+        for (start, counts) in timeline.buckets() {
+            for (level, count) in counts {
+                row("bucket_level_entries", &format!("{} {}", timeline.label(start), level), &count.to_string())?;
+            }
+        }
+        for burst in &report.bursts {
+            row("burst_end", &burst.start, &burst.end)?;
+            row("burst_count", &burst.start, &burst.count.to_string())?;
+            row("burst_baseline", &burst.start, &format!("{:.4}", burst.baseline))?;
+        }
+
+        let sessions = report.sessions.boots.iter().enumerate()
+            .map(|(index, boot)| ("boot_session", (index + 1).to_string(), boot.reason.to_string(), &boot.summary))
+            .chain(report.sessions.apps.iter().map(|app| ("app_session", format!("{} {}", app.pid, app.summary.start), app.name.clone(), &app.summary)));
+        for (section, key, name, summary) in sessions {
+            row(&format!("{}_name", section), &key, &name)?;
+            row(&format!("{}_start", section), &key, &summary.start)?;
+            row(&format!("{}_end", section), &key, &summary.end)?;
+            row(&format!("{}_duration", section), &key, &summary.duration().map_or(String::new(), |seconds| seconds.to_string()))?;
+            row(&format!("{}_entries", section), &key, &summary.entries.to_string())?;
+            row(&format!("{}_errors", section), &key, &summary.errors.to_string())?;
+            row(&format!("{}_crashes", section), &key, &summary.crashes.to_string())?;
+            row(&format!("{}_anrs", section), &key, &summary.anrs.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/**
+    Function to save a report to a file
+    * @param report The report to save
+    * @param path The path of the output file, it is created if it doesn't exist
+    * @param format The output format
+    * @param mode Whether an existing file is overwritten or appended to; the header of the format (the CSV
+    * column names) is only written if the file is empty
+    * @returns An Output error if the file couldn't be opened or written
+ **/
+pub fn save_report(report: &LogReport, path: &str, format: ReportFormat, mode: WriteMode) -> Result<(), LogAnalysisError> {
+    let mut options = OpenOptions::new();
+    options.create(true);
+    match mode {
+        WriteMode::Overwrite => options.write(true).truncate(true),
+        WriteMode::Append => options.append(true),
+    };
+    let mut file = options.open(path).map_err(LogAnalysisError::output(path))?;
+    let writer = format.writer();
+    if file.metadata().map_err(LogAnalysisError::output(path))?.len() == 0 {
+        writer.write_header(&mut file).map_err(LogAnalysisError::output(path))?;
+    }
+    writer.write_report(report, &mut file).map_err(LogAnalysisError::output(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_sessions::SessionTracker;
+    use crate::log_timeline::BucketSize;
+    use crate::logcat::{LogLine, LogcatReader};
+    use std::io::Cursor;
+
+    // A report of a few lines, without the analysis of a whole file
+    fn report() -> LogReport {
+        let log = "--------- beginning of main\n\
+                   10-15 11:40:02.118   601   630 I ActivityManager: Start proc 4210:com.example.player/u0a91 for activity {com.example.player/.MainActivity}\n\
+                   10-15 11:40:03.002  4210  4232 E ExoPlayerImplInternal: Playback error\n";
+        let mut statistics = LogStatistics::new();
+        let mut timeline = Timeline::new(BucketSize::Second);
+        let mut process_names = ProcessNames::new();
+        let mut session_tracker = SessionTracker::new();
+        for log_line in LogcatReader::new(Cursor::new(log)).unwrap() {
+            let log_line = log_line.unwrap();
+            session_tracker.push(&log_line);
+            if let LogLine::Entry(entry) = &log_line {
+                statistics.add(entry);
+                timeline.add(entry);
+                process_names.add(entry);
+            }
+        }
+        let sessions = session_tracker.finish(&[], &process_names);
+        LogReport {
+            log_file: "test.log".to_string(),
+            errors: Vec::new(),
+            automata_fallbacks: Vec::new(),
+            rule_counts: vec![("Errors".to_string(), 1)],
+            unparsed_lines: vec![1],
+            stack_trace_count: 0,
+            crash_groups: Vec::new(),
+            unique_process_ids: statistics.process_ids(),
+            most_common_error_process: Some(4210),
+            incidents: Vec::new(),
+            statistics,
+            timeline,
+            bursts: Vec::new(),
+            process_names,
+            sessions,
+        }
+    }
+
+    fn saved_twice(format: ReportFormat, name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        let path = path.to_str().unwrap();
+        let report = report();
+        save_report(&report, path, format, WriteMode::Overwrite).unwrap();
+        save_report(&report, path, format, WriteMode::Append).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        text
+    }
+
+    #[test]
+    fn appended_csv_has_one_header() {
+        let text = saved_twice(ReportFormat::Csv, "report.csv");
+        assert!(text.starts_with("section,key,value\n"));
+        assert_eq!(text.matches("section,key,value").count(), 1);
+        assert!(text.lines().any(|line| line.starts_with("boot_session_name,1,")));
+    }
+
+    #[test]
+    fn appended_json_is_one_report_per_line() {
+        let text = saved_twice(ReportFormat::Json, "report.json");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.starts_with("{\"log_file\": ") && line.ends_with("}")));
+    }
+}
@@ -0,0 +1,322 @@
+use regex::{Regex, RegexSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::log_automata::{Alphabet, DfaPattern, PatternError};
+use crate::log_error::LogAnalysisError;
+use crate::log_filter::{self, Filter};
+use crate::logcat::{LogLevel, LogLine};
+
+/**
+    Struct to represent one counting rule of the log analysis
+    * name The name of the rule, printed next to its count
+    * pattern The regular expression matched against the text of the line
+    * levels If set, only entries with one of these levels match
+    * tag If set, only entries with this tag match
+    * pid If set, only entries of this process match
+    * filter If set, only entries matching this filter expression match
+ **/
+pub struct LogRule {
+    pub name: String,
+    pub pattern: String,
+    pub levels: Option<Vec<LogLevel>>,
+    pub tag: Option<String>,
+    pub pid: Option<u32>,
+    pub filter: Option<Filter>,
+}
+
+impl LogRule {
+    fn new(name: &str) -> Self {
+        LogRule {
+            name: name.to_string(),
+            pattern: String::new(),
+            levels: None,
+            tag: None,
+            pid: None,
+            filter: None,
+        }
+    }
+
+    fn has_constraints(&self) -> bool {
+        self.levels.is_some() || self.tag.is_some() || self.pid.is_some() || self.filter.is_some()
+    }
+
+    /**
+        Function to check the level, tag, pid and filter constraints of the rule
+        * Lines that are not logcat entries only match rules without constraints
+        * @param log_line The line to check
+        * @returns true if the line satisfies every constraint
+     **/
+    pub fn accepts(&self, log_line: &LogLine) -> bool {
+        let entry = match log_line {
+            LogLine::Entry(entry) => entry,
+            LogLine::Unparsed { .. } => return !self.has_constraints(),
+        };
+        self.levels.as_ref().is_none_or(|levels| levels.contains(&entry.level))
+            && self.tag.as_ref().is_none_or(|tag| tag == &entry.tag)
+            && self.pid.is_none_or(|pid| entry.pid == Some(pid))
+            && self.filter.as_ref().is_none_or(|filter| filter.matches(entry))
+    }
+}
+
+/**
+    Struct to represent the rules of the log analysis
+    * rules The rules in the order of the file
+    * set The patterns of every rule, compiled together so a line is matched only once
+    * automata The patterns compiled into automata, if use_automata was called; None for the rules
+    * that couldn't be compiled, those are still matched by the regex engine
+ **/
+pub struct LogRules {
+    pub rules: Vec<LogRule>,
+    set: RegexSet,
+    automata: Vec<Option<DfaPattern>>,
+}
+
+impl LogRules {
+    /**
+        Function to create the rules, compiling their patterns
+        * @param rules The rules
+        * @returns The compiled rules, or the first rule whose pattern is not a valid regular expression
+     **/
+    pub fn new(rules: Vec<LogRule>) -> Result<Self, LogAnalysisError> {
+        // Compiled one by one first, so the error names the rule
+        for rule in &rules {
+            Regex::new(&rule.pattern)
+                .map_err(|error| LogAnalysisError::InvalidPattern { rule: rule.name.clone(), error })?;
+        }
+        let set = RegexSet::new(rules.iter().map(|rule| &rule.pattern)).map_err(|error| {
+            let names: Vec<&str> = rules.iter().map(|rule| rule.name.as_str()).collect();
+            LogAnalysisError::InvalidPattern { rule: names.join(", "), error }
+        })?;
+        Ok(LogRules { rules, set, automata: Vec::new() })
+    }
+
+    /**
+        Function to match the patterns with this crate's automata instead of the regex engine
+        * @param alphabet Whether the automata read bytes or characters
+        * The patterns the automata would read differently, see DfaPattern::regex_difference, keep the regex engine
+        * so the counts don't depend on the engine
+        * @returns The rules that couldn't be compiled, with the reason; they keep using the regex engine
+     **/
+    pub fn use_automata(&mut self, alphabet: Alphabet) -> Vec<(String, PatternError)> {
+        let mut errors = Vec::new();
+        self.automata = self.rules.iter()
+            .map(|rule| match DfaPattern::new(&rule.pattern, alphabet) {
+                Ok(pattern) => match pattern.regex_difference() {
+                    Some(difference) => {
+                        errors.push((rule.name.clone(), difference.clone()));
+                        None
+                    }
+                    None => Some(pattern),
+                },
+                Err(err) => {
+                    errors.push((rule.name.clone(), err));
+                    None
+                }
+            })
+            .collect();
+        errors
+    }
+
+    fn matching_line_rules(&self, text: &str) -> Vec<usize> {
+        if self.automata.is_empty() {
+            return self.set.matches(text).into_iter().collect();
+        }
+
+        // The regex set is only needed for the rules without an automata
+        let set_matches = self.automata.iter().any(Option::is_none).then(|| self.set.matches(text));
+        (0..self.rules.len())
+            .filter(|&index| match &self.automata[index] {
+                Some(automata) => automata.is_match(text),
+                None => set_matches.as_ref().is_some_and(|matches| matches.matched(index)),
+            })
+            .collect()
+    }
+
+    /**
+        Function to find the rules that match the physical lines of a log line
+        * The patterns are matched against every line of an entry on its own, the header and each continuation
+        * line, so the counts are counts of lines and ^ anchors at the start of every line. The level, tag, pid
+        * and filter constraints of a rule are checked on the entry, they hold for all of its lines.
+        * @param log_line The log line to match
+        * @returns The index of a rule once for every line it matches
+     **/
+    pub fn matching_rules(&self, log_line: &LogLine) -> Vec<usize> {
+        let text = match log_line {
+            LogLine::Entry(entry) => entry.raw.as_str(),
+            LogLine::Unparsed { text, .. } => text.as_str(),
+        };
+        text.split('\n')
+            .flat_map(|line| self.matching_line_rules(line))
+            .filter(|&index| self.rules[index].accepts(log_line))
+            .collect()
+    }
+}
+
+/**
+    Function to read the rules of the log analysis from a file
+    * @param filename The name of the file to read from
+    * @returns The compiled rules, or why the file couldn't be read or compiled
+
+    * The format of the file should be the following:
+    * Every rule starts with its name in brackets: [Number of lines containing E]
+    * The following lines are key = value pairs:
+    * pattern = the regular expression (required, unless there is a filter)
+    * level = the accepted level letters, e.g. EW (optional)
+    * tag = the exact tag (optional)
+    * pid = the process id (optional)
+    * filter = a filter expression, see log_filter::parse_filter (optional)
+    * Empty lines and lines starting with # are skipped
+ **/
+pub fn read_rules(filename: &str) -> Result<LogRules, LogAnalysisError> {
+    let file = File::open(filename).map_err(LogAnalysisError::io(filename))?;
+    let reader = BufReader::new(file);
+    let invalid = |message: String| LogAnalysisError::InvalidRules { path: filename.to_string(), message };
+
+    let mut rules: Vec<LogRule> = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(LogAnalysisError::io(filename))?;
+        let line_number = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            rules.push(LogRule::new(name));
+            continue;
+        }
+
+        let rule = rules.last_mut()
+            .ok_or_else(|| invalid(format!("line {}: key outside of a rule", line_number)))?;
+        let (key, value) = line.trim_start().split_once('=')
+            .ok_or_else(|| invalid(format!("line {}: expected key = value", line_number)))?;
+        // Only the space around the = is removed, the pattern may end in a space
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        match key.trim() {
+            "pattern" => rule.pattern = value.to_string(),
+            "level" => {
+                let levels = value.trim().chars()
+                    .map(LogLevel::from_letter)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid(format!("line {}: unknown level in {}", line_number, value)))?;
+                rule.levels = Some(levels);
+            }
+            "tag" => rule.tag = Some(value.trim().to_string()),
+            "pid" => {
+                let pid = value.trim().parse()
+                    .map_err(|_| invalid(format!("line {}: invalid pid {}", line_number, value)))?;
+                rule.pid = Some(pid);
+            }
+            "filter" => {
+                let filter = log_filter::parse_filter(value.trim())
+                    .map_err(|err| invalid(format!("line {}: invalid filter {}", line_number, err)))?;
+                rule.filter = Some(filter);
+            }
+            other => return Err(invalid(format!("line {}: unknown key {}", line_number, other))),
+        }
+    }
+
+    if rules.is_empty() {
+        return Err(LogAnalysisError::EmptyInput { path: filename.to_string() });
+    }
+    // A rule with only a filter matches every line with its (empty) pattern
+    if let Some(rule) = rules.iter().find(|rule| rule.pattern.is_empty() && rule.filter.is_none()) {
+        return Err(invalid(format!("rule {} has no pattern", rule.name)));
+    }
+    LogRules::new(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::LogcatReader;
+    use std::io::Cursor;
+
+    fn rule(name: &str, pattern: &str) -> LogRule {
+        let mut rule = LogRule::new(name);
+        rule.pattern = pattern.to_string();
+        rule
+    }
+
+    #[test]
+    fn every_physical_line_is_matched_on_its_own() {
+        let log = "10-15 10:18:47.782  1234  1234 E AndroidRuntime: java.lang.IllegalStateException: boom\n\
+                   \tat com.example.Main.run(Main.java:10)\n\
+                   \tat com.example.Main.main(Main.java:3)\n\
+                   10-15 10:18:48.000  1234  1234 I Tag: at the end\n";
+        let mut error_rule = rule("errors", "Exception|Main");
+        error_rule.levels = Some(vec![LogLevel::Error]);
+        let rules = LogRules::new(vec![rule("frames", r"^\s+at "), rule("headers", "^10-15"), error_rule]).unwrap();
+
+        let mut counts = [0; 3];
+        for log_line in LogcatReader::new(Cursor::new(log)).unwrap() {
+            for index in rules.matching_rules(&log_line.unwrap()) {
+                counts[index] += 1;
+            }
+        }
+        // The frames are continuation lines of the first entry, each is counted and ^ anchors at its start
+        assert_eq!(counts, [2, 2, 3]);
+    }
+
+    #[test]
+    fn both_engines_count_the_same_lines() {
+        let count = |alphabet: Option<Alphabet>| {
+            let mut rules = read_rules("src/resources/log_rules.txt").unwrap();
+            let kept = match alphabet {
+                Some(alphabet) => rules.rules.len() - rules.use_automata(alphabet).len(),
+                None => 0,
+            };
+            let mut counts = vec![0; rules.rules.len()];
+            for log_line in crate::logcat::read_log_file("src/resources/sample_reboot.log").unwrap() {
+                for index in rules.matching_rules(&log_line.unwrap()) {
+                    counts[index] += 1;
+                }
+            }
+            (counts, kept)
+        };
+        let (regex_counts, _) = count(None);
+        for alphabet in [Alphabet::Char, Alphabet::Byte] {
+            let (counts, kept) = count(Some(alphabet));
+            assert!(kept > 0, "{:?}", alphabet);
+            assert_eq!(counts, regex_counts, "{:?}", alphabet);
+        }
+    }
+
+    #[test]
+    fn patterns_read_differently_keep_the_regex_engine() {
+        let mut rules = LogRules::new(vec![rule("word", r"\w+"), rule("any", "a.b"), rule("plain", "^E/")]).unwrap();
+        let names = |errors: Vec<(String, PatternError)>| errors.into_iter().map(|(name, _)| name).collect::<Vec<String>>();
+        assert_eq!(names(rules.use_automata(Alphabet::Char)), ["word"]);
+        assert_eq!(names(rules.use_automata(Alphabet::Byte)), ["word", "any"]);
+    }
+
+    #[test]
+    fn rules_file_errors_name_the_line_or_the_rule() {
+        let path = std::env::temp_dir().join(format!("{}_log_rules.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let message = |contents: &str| {
+            std::fs::write(path, contents).unwrap();
+            match read_rules(path) {
+                Err(LogAnalysisError::InvalidRules { message, .. }) => message,
+                Err(error) => panic!("{}", error),
+                Ok(_) => panic!("{:?} was read", contents),
+            }
+        };
+        assert_eq!(message("pattern = E/\n"), "line 1: key outside of a rule");
+        assert_eq!(message("# comment\n[errors]\npattern E/\n"), "line 3: expected key = value");
+        assert_eq!(message("[errors]\npattern = E/\ncolor = red\n"), "line 3: unknown key color");
+        assert_eq!(message("[errors]\nlevel = EX\n"), "line 2: unknown level in EX");
+        assert_eq!(message("[errors]\n\npid = first\n"), "line 3: invalid pid first");
+        assert!(message("[errors]\nfilter = level >\n").starts_with("line 2: invalid filter"));
+        assert_eq!(message("[errors]\nlevel = E\n"), "rule errors has no pattern");
+
+        std::fs::write(path, "[broken]\npattern = E/(\n").unwrap();
+        assert!(matches!(read_rules(path), Err(LogAnalysisError::InvalidPattern { rule, .. }) if rule == "broken"));
+        std::fs::write(path, "# no rules\n\n").unwrap();
+        assert!(matches!(read_rules(path), Err(LogAnalysisError::EmptyInput { .. })));
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(read_rules(path), Err(LogAnalysisError::Io { .. })));
+    }
+}
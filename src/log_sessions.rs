@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::log_incidents::{Incident, IncidentKind};
+use crate::log_merge;
+use crate::log_processes::ProcessNames;
+use crate::log_stats;
+use crate::log_timeline;
+use crate::logcat::{LogLevel, LogLine, LogcatEntry};
+
+// A timestamp that goes back more than this many seconds means the device clock started again
+const RESET_SECONDS: u64 = 60;
+
+/**
+    Why a boot session started
+    * CaptureStart The first session of the capture
+    * LogStart Logcat started again, --------- beginning of main
+    * InitStart The first stage of init started
+    * ZygoteStart The zygote started (AndroidRuntime: START com.android.internal.os.ZygoteInit), a
+    * soft reboot if it happens without init
+    * TimestampReset The timestamps jumped back
+ **/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BootReason {
+    CaptureStart,
+    LogStart,
+    InitStart,
+    ZygoteStart,
+    TimestampReset,
+}
+
+impl fmt::Display for BootReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BootReason::CaptureStart => "capture start",
+            BootReason::LogStart => "logcat start",
+            BootReason::InitStart => "init start",
+            BootReason::ZygoteStart => "zygote start",
+            BootReason::TimestampReset => "timestamp reset",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/**
+    Struct to represent the summary of a session
+    * start, end The timestamps of the first and the last entry, or of the start and the death of the process
+    * entries, errors The number of entries and of entries with level E or above
+    * crashes The number of fatal exceptions and native crashes
+    * anrs The number of ANRs
+ **/
+#[derive(Debug, Clone, Default)]
+pub struct SessionSummary {
+    pub start: String,
+    pub end: String,
+    pub entries: usize,
+    pub errors: usize,
+    pub crashes: usize,
+    pub anrs: usize,
+}
+
+impl SessionSummary {
+    fn add(&mut self, timestamp: &str, error: bool) {
+        if self.start.is_empty() {
+            self.start = timestamp.to_string();
+        }
+        self.end = timestamp.to_string();
+        self.entries += 1;
+        if error {
+            self.errors += 1;
+        }
+    }
+
+    fn add_incident(&mut self, incident: &Incident) {
+        match incident.kind {
+            IncidentKind::FatalException | IncidentKind::NativeCrash => self.crashes += 1,
+            IncidentKind::Anr => self.anrs += 1,
+            IncidentKind::MainThreadError => {}
+        }
+    }
+
+    /**
+        Function to get the length of the session
+        * @returns The seconds between the start and the end, None without timestamps
+     **/
+    pub fn duration(&self) -> Option<u64> {
+        let seconds = |timestamp: &str| {
+            let (date, time) = timestamp.split_once(' ')?;
+            log_timeline::timestamp_seconds(date, time)
+        };
+        Some(seconds(&self.end)?.saturating_sub(seconds(&self.start)?))
+    }
+
+    fn columns(&self) -> Vec<String> {
+        let duration = self.duration().map_or("-".to_string(), |seconds| format!("{}:{:02}", seconds / 60, seconds % 60));
+        vec![self.start.clone(), self.end.clone(), duration, self.entries.to_string(), self.errors.to_string(),
+             self.crashes.to_string(), self.anrs.to_string()]
+    }
+}
+
+/**
+    Struct to represent the part of a capture between two boots of the device
+    * reason Why the session started
+    * first_line, last_line The lines of the log file in the session
+ **/
+#[derive(Debug, Clone)]
+pub struct BootSession {
+    pub reason: BootReason,
+    pub first_line: usize,
+    pub last_line: usize,
+    pub summary: SessionSummary,
+}
+
+/**
+    Struct to represent the lifecycle of an app, from its process start to its death
+    * pid, name The process
+    * started, ended Whether the start and the death are in the capture
+ **/
+#[derive(Debug, Clone)]
+pub struct AppSession {
+    pub pid: u32,
+    pub name: String,
+    pub started: bool,
+    pub ended: bool,
+    pub summary: SessionSummary,
+}
+
+/**
+    Struct to represent the sessions of a capture
+    * boots The device boots, in the order of the log
+    * apps The app lifecycles, in the order they started
+ **/
+#[derive(Debug, Clone, Default)]
+pub struct Sessions {
+    pub boots: Vec<BootSession>,
+    pub apps: Vec<AppSession>,
+}
+
+/**
+    Splits a capture into sessions, one line at a time
+    * A boot ends when logcat, init or the zygote starts again, or when the timestamps jump back.
+    * Every boot goes through the stages logcat start, init start and zygote start; reaching a stage
+    * the current boot has already reached or passed starts a new one.
+    * The app lifecycles come from the process names, see log_processes::ProcessNames.
+ **/
+pub struct SessionTracker {
+    boots: Vec<BootSession>,
+    stage: Option<BootReason>,
+    latest: Option<u64>,
+    entries_by_pid: HashMap<u32, Vec<(String, bool)>>,
+}
+
+impl SessionTracker {
+    pub fn new() -> Self {
+        SessionTracker {
+            boots: vec![BootSession { reason: BootReason::CaptureStart, first_line: 1, last_line: 0, summary: SessionSummary::default() }],
+            stage: None,
+            latest: None,
+            entries_by_pid: HashMap::new(),
+        }
+    }
+
+    fn boot_stage(entry: &LogcatEntry) -> Option<BootReason> {
+        let message = entry.message.as_str();
+        if entry.tag == "init" && message.starts_with("init first stage started") {
+            Some(BootReason::InitStart)
+        } else if entry.tag == "AndroidRuntime" && message.contains("START com.android.internal.os.ZygoteInit") {
+            Some(BootReason::ZygoteStart)
+        } else {
+            None
+        }
+    }
+
+    // A stage only starts a new boot if the current one already has entries; before the first stage
+    // only logcat and init can start one, the capture may have started in the middle of a boot
+    fn enter_stage(&mut self, stage: BootReason, line_number: usize) {
+        let current = self.boots.last().unwrap();
+        let new_boot = match self.stage {
+            Some(seen) => stage <= seen,
+            None => stage != BootReason::ZygoteStart,
+        };
+        if current.summary.entries > 0 && new_boot {
+            self.start_boot(stage, line_number);
+        }
+        self.stage = Some(stage);
+    }
+
+    fn start_boot(&mut self, reason: BootReason, line_number: usize) {
+        self.boots.push(BootSession { reason, first_line: line_number, last_line: line_number, summary: SessionSummary::default() });
+        self.stage = None;
+        self.latest = None;
+    }
+
+    /**
+        Function to process the next line of the log
+        * @param log_line The line
+     **/
+    pub fn push(&mut self, log_line: &LogLine) {
+        let entry = match log_line {
+            LogLine::Entry(entry) => entry,
+            LogLine::Unparsed { line_number, text } => {
+                if text.contains("beginning of") && log_merge::buffer_marker(text) == Some("main") {
+                    self.enter_stage(BootReason::LogStart, *line_number);
+                }
+                self.boots.last_mut().unwrap().last_line = *line_number;
+                return;
+            }
+        };
+
+        if let Some(seconds) = log_timeline::timestamp_seconds(&entry.date, &entry.time) {
+            if self.latest.is_some_and(|latest| seconds + RESET_SECONDS < latest) {
+                self.start_boot(BootReason::TimestampReset, entry.line_number);
+            }
+            self.latest = Some(self.latest.map_or(seconds, |latest| latest.max(seconds)));
+        }
+        if let Some(stage) = Self::boot_stage(entry) {
+            self.enter_stage(stage, entry.line_number);
+        }
+
+        let timestamp = format!("{} {}", entry.date, entry.time);
+        let error = entry.level >= LogLevel::Error;
+        let boot = self.boots.last_mut().unwrap();
+        boot.summary.add(&timestamp, error);
+        boot.last_line = entry.line_number + entry.raw.lines().count().max(1) - 1;
+        if let Some(pid) = entry.pid {
+            self.entries_by_pid.entry(pid).or_default().push((timestamp, error));
+        }
+    }
+
+    /**
+        Function to get the sessions once the whole log was processed
+        * @param incidents The incidents of the log, counted in the sessions they happened in
+        * @param names The process names, their lifetimes are the app sessions
+        * @returns The sessions
+     **/
+    pub fn finish(mut self, incidents: &[Incident], names: &ProcessNames) -> Sessions {
+        for incident in incidents {
+            let boot = self.boots.iter_mut()
+                .find(|boot| boot.first_line <= incident.line_number && incident.line_number <= boot.last_line);
+            if let Some(boot) = boot {
+                boot.summary.add_incident(incident);
+            }
+        }
+
+        let mut apps = Vec::new();
+        for lifetime in names.lifetimes() {
+            let mut summary = SessionSummary::default();
+            let entries = self.entries_by_pid.get(&lifetime.pid).map(|entries| entries.as_slice()).unwrap_or_default();
+            for (timestamp, error) in entries.iter().filter(|(timestamp, _)| lifetime.contains(timestamp)) {
+                summary.add(timestamp, *error);
+            }
+            for incident in incidents.iter().filter(|incident| incident.pid == Some(lifetime.pid) && lifetime.contains(&incident.timestamp())) {
+                summary.add_incident(incident);
+            }
+            if !lifetime.start.is_empty() {
+                summary.start = lifetime.start.clone();
+            }
+            if let Some(end) = &lifetime.end {
+                summary.end = end.clone();
+            }
+            apps.push(AppSession {
+                pid: lifetime.pid,
+                name: lifetime.name.clone().unwrap_or_default(),
+                started: !lifetime.start.is_empty(),
+                ended: lifetime.end.is_some(),
+                summary,
+            });
+        }
+        Sessions { boots: self.boots, apps }
+    }
+}
+
+impl Sessions {
+    /**
+        Function to write the boot and app sessions as two tables
+        * @param out Where to write the tables
+     **/
+    pub fn write_tables(&self, out: &mut dyn Write) -> io::Result<()> {
+        let summary_headers = ["start", "end", "duration", "entries", "errors", "crashes", "ANRs"];
+
+        writeln!(out, "Boot sessions: {}", self.boots.len())?;
+        let rows: Vec<Vec<String>> = self.boots.iter().enumerate()
+            .map(|(index, boot)| {
+                let mut row = vec![(index + 1).to_string(), boot.reason.to_string(), format!("{}-{}", boot.first_line, boot.last_line)];
+                row.extend(boot.summary.columns());
+                row
+            })
+            .collect();
+        let headers: Vec<&str> = ["boot", "reason", "lines"].iter().chain(&summary_headers).cloned().collect();
+        log_stats::write_table(out, &headers, &rows)?;
+
+        writeln!(out, "App sessions: {}", self.apps.len())?;
+        let rows: Vec<Vec<String>> = self.apps.iter()
+            .map(|app| {
+                let mut row = vec![app.pid.to_string(), app.name.clone()];
+                row.extend(app.summary.columns());
+                if !app.started {
+                    row[2] = format!("before {}", row[2]);
+                }
+                if !app.ended {
+                    row[3] = format!("running {}", row[3]);
+                }
+                row
+            })
+            .collect();
+        let headers: Vec<&str> = ["pid", "name"].iter().chain(&summary_headers).cloned().collect();
+        log_stats::write_table(out, &headers, &rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::LogcatReader;
+    use std::io::Cursor;
+
+    fn sessions(log: &str) -> Sessions {
+        let mut tracker = SessionTracker::new();
+        let mut names = ProcessNames::new();
+        for log_line in LogcatReader::new(Cursor::new(log)).unwrap() {
+            let log_line = log_line.unwrap();
+            if let LogLine::Entry(entry) = &log_line {
+                names.add(entry);
+            }
+            tracker.push(&log_line);
+        }
+        tracker.finish(&[], &names)
+    }
+
+    fn boots(sessions: &Sessions) -> Vec<(BootReason, usize, usize)> {
+        sessions.boots.iter().map(|boot| (boot.reason, boot.first_line, boot.last_line)).collect()
+    }
+
+    #[test]
+    fn timestamps_going_back_start_a_new_boot() {
+        let sessions = sessions("\
+01-01 10:18:40.000   100   100 I Tag: first boot
+01-01 10:18:50.000   100   100 E Tag: still the first boot
+01-01 10:18:10.000   100   100 I Tag: lines out of order by less than a minute
+01-01 00:00:05.000   200   200 I Tag: the clock started again
+01-01 00:00:06.000   200   200 I Tag: second boot
+");
+        assert_eq!(boots(&sessions), [(BootReason::CaptureStart, 1, 3), (BootReason::TimestampReset, 4, 5)]);
+        let first = &sessions.boots[0].summary;
+        assert_eq!((first.entries, first.errors), (3, 1));
+        assert_eq!((sessions.boots[1].summary.start.as_str(), sessions.boots[1].summary.duration()), ("01-01 00:00:05.000", Some(1)));
+    }
+
+    #[test]
+    fn boot_stages_start_a_new_boot_only_when_they_repeat() {
+        let sessions = sessions("\
+01-01 10:00:00.000   300   300 I Tag: the capture started in the middle of a boot
+01-01 10:00:01.000     1     1 I AndroidRuntime: >>>>>> START com.android.internal.os.ZygoteInit uid 0 <<<<<<
+--------- beginning of main
+01-01 10:00:02.000     1     1 I init: init first stage started!
+01-01 10:00:03.000   400   400 I AndroidRuntime: >>>>>> START com.android.internal.os.ZygoteInit uid 0 <<<<<<
+01-01 10:00:04.000   400   400 I Tag: running
+01-01 10:00:05.000   500   500 I AndroidRuntime: >>>>>> START com.android.internal.os.ZygoteInit uid 0 <<<<<<
+");
+        // The first zygote start comes before any stage, logcat, init and zygote starts are one boot, the next zygote is a soft reboot
+        assert_eq!(boots(&sessions), [
+            (BootReason::CaptureStart, 1, 2),
+            (BootReason::LogStart, 3, 6),
+            (BootReason::ZygoteStart, 7, 7),
+        ]);
+        assert_eq!(sessions.apps.iter().map(|app| (app.pid, app.name.as_str())).collect::<Vec<_>>(),
+                   [(1, "zygote"), (400, "zygote"), (500, "zygote")]);
+    }
+}
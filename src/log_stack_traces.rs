@@ -0,0 +1,334 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::logcat::{LogLevel, LogcatEntry};
+
+// Number of frames taken into account by the fingerprint
+const FINGERPRINT_FRAMES: usize = 3;
+
+/**
+    Struct to represent a stack trace collected from the log
+    * date, time The timestamp of the first line
+    * pid, tid, tag The process, thread and tag that logged the trace
+    * lines The lines of the trace: the header, the frames and the Caused by: chain
+ **/
+#[derive(Debug, Clone)]
+pub struct StackTrace {
+    pub date: String,
+    pub time: String,
+    pub pid: Option<u32>,
+    pub tid: Option<u32>,
+    pub tag: String,
+    pub lines: Vec<String>,
+    level: LogLevel,
+    order: usize,
+    confirmed: bool,
+}
+
+// Frames, Caused by: chains and indented lines continue a trace
+fn is_frame_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    line.starts_with([' ', '\t'])
+        || trimmed.starts_with("at ")
+        || trimmed.starts_with("Caused by:")
+        || trimmed.starts_with("Suppressed:")
+        || trimmed.starts_with("... ")
+}
+
+fn is_at_frame(line: &str) -> bool {
+    line.trim().starts_with("at ")
+}
+
+// A qualified class name ending in Exception, Error or Throwable, e.g. java.lang.NullPointerException: ...
+// or Caused by: java.io.IOException
+fn is_exception_class(line: &str) -> bool {
+    let trimmed = line.trim();
+    let header = trimmed.strip_prefix("Caused by:").unwrap_or(trimmed).trim_start();
+    let class_name = header.split(": ").next().unwrap_or(header);
+    class_name.contains('.')
+        && class_name.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '$' || c == '_')
+        && ["Exception", "Error", "Throwable"].iter().any(|suffix| class_name.ends_with(suffix))
+}
+
+fn is_exception_header(line: &str) -> bool {
+    line.trim().starts_with("FATAL EXCEPTION") || is_exception_class(line)
+}
+
+impl StackTrace {
+    fn new(entry: &LogcatEntry, order: usize) -> Self {
+        StackTrace {
+            date: entry.date.clone(),
+            time: entry.time.clone(),
+            pid: entry.pid,
+            tid: entry.tid,
+            tag: entry.tag.clone(),
+            lines: Vec::new(),
+            level: entry.level,
+            order,
+            confirmed: false,
+        }
+    }
+
+    pub fn timestamp(&self) -> String {
+        format!("{} {}", self.date, self.time)
+    }
+
+    fn has_frames(&self) -> bool {
+        self.lines.iter().any(|line| is_at_frame(line))
+    }
+
+    /**
+        Function to get the frames of the top exception, before the first Caused by:
+        * @returns The at ... lines, trimmed
+     **/
+    pub fn frames(&self) -> Vec<&str> {
+        self.lines.iter()
+            .map(|line| line.trim())
+            .take_while(|line| !line.starts_with("Caused by:"))
+            .filter(|line| line.starts_with("at "))
+            .collect()
+    }
+
+    /**
+        Function to get the Caused by: chain of the trace
+        * @returns The causes, without the Caused by: prefix
+     **/
+    pub fn causes(&self) -> Vec<&str> {
+        self.lines.iter()
+            .filter_map(|line| line.trim().strip_prefix("Caused by:"))
+            .map(|cause| cause.trim())
+            .collect()
+    }
+
+    /**
+        Function to get the type of the exception
+        * It is taken from the last header line before the frames that names an exception class, e.g.
+        * java.lang.NullPointerException: Attempt to invoke ... gives java.lang.NullPointerException;
+        * if there is none (e.g. an obfuscated class), from the last header line
+        * @returns The exception type
+     **/
+    pub fn exception_type(&self) -> String {
+        let headers: Vec<&str> = self.lines.iter()
+            .take_while(|line| !is_frame_line(line))
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let header = headers.iter().rev()
+            .find(|line| is_exception_class(line))
+            .or(headers.last())
+            .map_or("", |line| line);
+        header.split(": ").next().unwrap_or(header).to_string()
+    }
+
+    /**
+        Function to compute the fingerprint of the trace
+        * Built from the exception type and the top frames, so the same crash always gets the same fingerprint
+        * @returns A 16 digit hexadecimal FNV-1a hash
+     **/
+    pub fn fingerprint(&self) -> String {
+        let mut key = self.exception_type();
+        for frame in self.frames().iter().take(FINGERPRINT_FRAMES) {
+            key.push('\n');
+            key.push_str(frame);
+        }
+
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in key.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = |id: Option<u32>| id.map_or("-".to_string(), |id| id.to_string());
+        write!(f, "{} {} {}/{} {}:", self.date, self.time, id(self.pid), id(self.tid), self.tag)?;
+        for line in &self.lines {
+            write!(f, "\n    {}", line.trim())?;
+        }
+        Ok(())
+    }
+}
+
+/**
+    Collects the stack traces from the entries of a log, one entry at a time
+    * A trace starts with an error entry that is an exception header (FATAL EXCEPTION, an exception class, Caused by:),
+    * or that is followed by an at ... frame, e.g. the message of an obfuscated exception class.
+    * It is continued by the entries of the same thread with the same tag and level: first the header lines (e.g.
+    * the Process: line after FATAL EXCEPTION, or the lines of a long exception message), then the frames and the
+    * Caused by: chain.
+    * It doesn't matter if the frames are continuation lines or repeat the pid/tid/tag prefix.
+    * Entries of other threads may come in between, only a line of the same thread ends the trace.
+    * Errors without any frames are not stack traces, they are dropped.
+ **/
+pub struct StackTraceCollector {
+    open: HashMap<(Option<u32>, Option<u32>), StackTrace>,
+    finished: Vec<StackTrace>,
+    started: usize,
+}
+
+impl StackTraceCollector {
+    pub fn new() -> Self {
+        StackTraceCollector {
+            open: HashMap::new(),
+            finished: Vec::new(),
+            started: 0,
+        }
+    }
+
+    fn close(&mut self, key: (Option<u32>, Option<u32>)) {
+        if let Some(trace) = self.open.remove(&key) {
+            if trace.confirmed && trace.has_frames() {
+                self.finished.push(trace);
+            }
+        }
+    }
+
+    pub fn push(&mut self, entry: &LogcatEntry) {
+        let key = (entry.pid, entry.tid);
+        let lines: Vec<&str> = entry.message.split('\n').collect();
+
+        if let Some(trace) = self.open.get_mut(&key) {
+            let continues = trace.tag == entry.tag
+                && trace.level == entry.level
+                && if !trace.confirmed {
+                    is_at_frame(lines[0])
+                } else if trace.has_frames() {
+                    lines.iter().all(|line| is_frame_line(line))
+                } else {
+                    true
+                };
+            if continues {
+                trace.lines.extend(lines.iter().map(|line| line.to_string()));
+                trace.confirmed = true;
+                return;
+            }
+            self.close(key);
+        }
+
+        // An error that is not an exception header stays open only until its next line shows if it is a trace
+        let confirmed = is_exception_header(lines[0]) || lines.get(1).is_some_and(|line| is_at_frame(line));
+        if entry.level >= LogLevel::Error && (confirmed || lines.len() == 1) {
+            let mut trace = StackTrace::new(entry, self.started);
+            trace.lines.extend(lines.iter().map(|line| line.to_string()));
+            trace.confirmed = confirmed;
+            self.started += 1;
+            self.open.insert(key, trace);
+        }
+    }
+
+    /**
+        Function to close the traces that are still open
+        * @returns The stack traces in the order they started
+     **/
+    pub fn finish(mut self) -> Vec<StackTrace> {
+        let keys: Vec<(Option<u32>, Option<u32>)> = self.open.keys().cloned().collect();
+        for key in keys {
+            self.close(key);
+        }
+        self.finished.sort_by_key(|trace| trace.order);
+        self.finished
+    }
+}
+
+/**
+    Struct to represent the stack traces that have the same fingerprint
+    * fingerprint The common fingerprint
+    * exception_type The type of the exception
+    * count The number of traces
+    * example The first trace with this fingerprint
+ **/
+pub struct CrashGroup {
+    pub fingerprint: String,
+    pub exception_type: String,
+    pub count: usize,
+    pub example: StackTrace,
+}
+
+/**
+    Function to deduplicate stack traces by their fingerprint
+    * @param traces The stack traces
+    * @returns The groups, the most frequent first
+ **/
+pub fn group_by_fingerprint(traces: Vec<StackTrace>) -> Vec<CrashGroup> {
+    let mut groups: Vec<CrashGroup> = Vec::new();
+    let mut index_of: HashMap<String, usize> = HashMap::new();
+
+    for trace in traces {
+        let fingerprint = trace.fingerprint();
+        match index_of.get(&fingerprint) {
+            Some(&index) => groups[index].count += 1,
+            None => {
+                index_of.insert(fingerprint.clone(), groups.len());
+                groups.push(CrashGroup {
+                    fingerprint,
+                    exception_type: trace.exception_type(),
+                    count: 1,
+                    example: trace,
+                });
+            }
+        }
+    }
+
+    // The sort is stable, groups with the same count stay in the order of their first trace
+    groups.sort_by_key(|group| Reverse(group.count));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::{LineParser, LogFormat};
+
+    fn collect(lines: &[&str]) -> Vec<StackTrace> {
+        let parser = LineParser::new(LogFormat::ThreadTime);
+        let mut collector = StackTraceCollector::new();
+        for line in lines {
+            collector.push(&parser.parse(line).unwrap());
+        }
+        collector.finish()
+    }
+
+    #[test]
+    fn fatal_exception_is_one_trace() {
+        let traces = collect(&[
+            "10-15 11:02:12.641  1345  1345 E AndroidRuntime: FATAL EXCEPTION: main",
+            "10-15 11:02:12.641  1345  1345 E AndroidRuntime: Process: com.example.app, PID: 1345",
+            "10-15 11:02:12.641  1345  1345 E AndroidRuntime: java.lang.NullPointerException: Attempt to invoke a virtual method",
+            "10-15 11:02:12.641  1345  1345 E AndroidRuntime: \tat com.example.app.MainActivity.onCreate(MainActivity.java:42)",
+            "10-15 11:02:12.641  1345  1345 E AndroidRuntime: Caused by: java.io.IOException: closed",
+            "10-15 11:02:12.641  1345  1345 E AndroidRuntime: \tat com.example.app.Io.read(Io.java:7)",
+        ]);
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].exception_type(), "java.lang.NullPointerException");
+        assert_eq!(traces[0].frames(), vec!["at com.example.app.MainActivity.onCreate(MainActivity.java:42)"]);
+        assert_eq!(traces[0].causes(), vec!["java.io.IOException: closed"]);
+    }
+
+    #[test]
+    fn errors_without_an_exception_are_not_traces() {
+        let traces = collect(&[
+            "10-15 10:18:48.092   241   241 E keystore2: keystore2::error: In call_with_watchdog: getting keymint device",
+            "10-15 10:18:48.092   241   241 E keystore2: Caused by:",
+            "10-15 10:18:48.092   241   241 E keystore2:     0: In get_keymint_device.",
+            "10-15 10:18:57.408  2087  2303 E cr_ClientAuthRawSigner: ===== Beginning of DRM Plugin Log =====",
+            "10-15 10:18:57.408  2087  2303 E cr_ClientAuthRawSigner:   10-15 10:18:57.401 I found instance=clearkey",
+            "10-15 10:18:57.408  2087  2303 E cr_ClientAuthRawSigner: ===== End of DRM Plugin Log =====",
+        ]);
+        assert!(traces.is_empty());
+    }
+
+    #[test]
+    fn obfuscated_exception_is_confirmed_by_its_frame() {
+        let traces = collect(&[
+            "10-15 10:18:57.543  1370  1488 E DeviceCustomizationsImp: Failed to fetch and update the customizations",
+            "10-15 10:18:57.543  1370  1488 E DeviceCustomizationsImp: qid: INVALID_ARGUMENT: credential header not valid.",
+            "10-15 10:18:57.543  1370  1488 E DeviceCustomizationsImp: \tat qib.k(PG:1)",
+        ]);
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].exception_type(), "qid");
+    }
+}
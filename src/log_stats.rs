@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::log_processes::ProcessNames;
+use crate::logcat::{LogLevel, LogcatEntry};
+
+/**
+    Struct to represent the statistics of one process
+    * entries The number of entries
+    * errors The number of entries with level E or above
+    * first_seen, last_seen The earliest and latest timestamp of the process
+ **/
+#[derive(Debug, Clone)]
+pub struct ProcessStats {
+    pub entries: usize,
+    pub errors: usize,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/**
+    Struct to represent the statistics of one tag
+    * entries The number of entries
+    * errors The number of entries with level E or above
+ **/
+#[derive(Debug, Clone, Default)]
+pub struct TagStats {
+    pub entries: usize,
+    pub errors: usize,
+}
+
+impl TagStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.entries == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.entries as f64
+        }
+    }
+}
+
+/**
+    Struct to represent the aggregate statistics of a log, computed in one pass
+    * total The number of entries
+    * processes The statistics by pid
+    * threads The number of entries by tid, the entries without a tid are not counted
+    * tags The statistics by tag
+    * levels The number of entries by level
+ **/
+#[derive(Debug, Clone, Default)]
+pub struct LogStatistics {
+    pub total: usize,
+    pub processes: HashMap<u32, ProcessStats>,
+    pub threads: HashMap<u32, usize>,
+    pub tags: HashMap<String, TagStats>,
+    pub levels: BTreeMap<LogLevel, usize>,
+}
+
+// Sorts by count, the largest first, ties broken by the key so the output is stable
+fn ranked<K: Ord + Clone, V>(map: &HashMap<K, V>, count: impl Fn(&V) -> usize, n: usize) -> Vec<(K, &V)> {
+    let mut ranking: Vec<(K, &V)> = map.iter().map(|(key, value)| (key.clone(), value)).collect();
+    ranking.sort_by(|a, b| count(b.1).cmp(&count(a.1)).then_with(|| a.0.cmp(&b.0)));
+    ranking.truncate(n);
+    ranking
+}
+
+impl LogStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Function to add an entry to the statistics
+        * @param entry The entry to count
+     **/
+    pub fn add(&mut self, entry: &LogcatEntry) {
+        let is_error = entry.level >= LogLevel::Error;
+        let timestamp = format!("{} {}", entry.date, entry.time);
+        self.total += 1;
+
+        // Tag format doesn't show the pid, those entries belong to no process
+        if let Some(pid) = entry.pid {
+            let process = self.processes.entry(pid).or_insert_with(|| ProcessStats {
+                entries: 0,
+                errors: 0,
+                first_seen: timestamp.clone(),
+                last_seen: timestamp.clone(),
+            });
+            process.entries += 1;
+            if is_error {
+                process.errors += 1;
+            }
+            // The lines of a capture are not always in order, so the extremes are kept
+            if timestamp < process.first_seen {
+                process.first_seen = timestamp.clone();
+            }
+            if timestamp > process.last_seen {
+                process.last_seen = timestamp;
+            }
+        }
+
+        if let Some(tid) = entry.tid {
+            *self.threads.entry(tid).or_insert(0) += 1;
+        }
+
+        let tag = self.tags.entry(entry.tag.clone()).or_default();
+        tag.entries += 1;
+        if is_error {
+            tag.errors += 1;
+        }
+
+        *self.levels.entry(entry.level).or_insert(0) += 1;
+    }
+
+    /**
+        Function to get the process ids in increasing order
+     **/
+    pub fn process_ids(&self) -> Vec<u32> {
+        let mut pids: Vec<u32> = self.processes.keys().cloned().collect();
+        pids.sort();
+        pids
+    }
+
+    pub fn top_processes(&self, n: usize) -> Vec<(u32, &ProcessStats)> {
+        ranked(&self.processes, |process| process.entries, n)
+    }
+
+    /**
+        Function to get the processes with the most errors, processes without errors are left out
+     **/
+    pub fn top_error_processes(&self, n: usize) -> Vec<(u32, &ProcessStats)> {
+        let mut ranking = ranked(&self.processes, |process| process.errors, self.processes.len());
+        ranking.retain(|(_, process)| process.errors > 0);
+        ranking.truncate(n);
+        ranking
+    }
+
+    pub fn top_threads(&self, n: usize) -> Vec<(u32, usize)> {
+        ranked(&self.threads, |count| *count, n).into_iter().map(|(tid, count)| (tid, *count)).collect()
+    }
+
+    pub fn top_tags(&self, n: usize) -> Vec<(String, &TagStats)> {
+        ranked(&self.tags, |tag| tag.entries, n)
+    }
+
+    pub fn top_error_tags(&self, n: usize) -> Vec<(String, &TagStats)> {
+        let mut ranking = ranked(&self.tags, |tag| tag.errors, self.tags.len());
+        ranking.retain(|(_, tag)| tag.errors > 0);
+        ranking.truncate(n);
+        ranking
+    }
+
+    /**
+        Function to write the statistics as aligned tables
+        * @param out Where to write the tables
+        * @param n The number of rows of the rankings
+        * @param names The names of the processes
+     **/
+    pub fn write_tables(&self, out: &mut dyn Write, n: usize, names: &ProcessNames) -> io::Result<()> {
+        writeln!(out, "Entries by level:")?;
+        let rows: Vec<Vec<String>> = self.levels.iter()
+            .map(|(level, count)| vec![level.to_string(), count.to_string()])
+            .collect();
+        write_table(out, &["level", "entries"], &rows)?;
+
+        writeln!(out, "Top {} processes:", n)?;
+        let rows: Vec<Vec<String>> = self.top_processes(n).iter()
+            .map(|(pid, process)| vec![
+                pid.to_string(), names.names_of(*pid).join(", "), process.entries.to_string(), process.errors.to_string(),
+                process.first_seen.clone(), process.last_seen.clone(),
+            ])
+            .collect();
+        write_table(out, &["pid", "name", "entries", "errors", "first seen", "last seen"], &rows)?;
+
+        writeln!(out, "Top {} threads:", n)?;
+        let rows: Vec<Vec<String>> = self.top_threads(n).iter()
+            .map(|(tid, count)| vec![tid.to_string(), count.to_string()])
+            .collect();
+        write_table(out, &["tid", "entries"], &rows)?;
+
+        writeln!(out, "Top {} tags:", n)?;
+        let rows: Vec<Vec<String>> = self.top_tags(n).iter()
+            .map(|(tag, stats)| vec![
+                tag.clone(), stats.entries.to_string(), stats.errors.to_string(),
+                format!("{:.1}%", stats.error_rate() * 100.0),
+            ])
+            .collect();
+        write_table(out, &["tag", "entries", "errors", "error rate"], &rows)?;
+
+        writeln!(out, "Top {} tags by errors:", n)?;
+        let rows: Vec<Vec<String>> = self.top_error_tags(n).iter()
+            .map(|(tag, stats)| vec![
+                tag.clone(), stats.entries.to_string(), stats.errors.to_string(),
+                format!("{:.1}%", stats.error_rate() * 100.0),
+            ])
+            .collect();
+        write_table(out, &["tag", "entries", "errors", "error rate"], &rows)
+    }
+}
+
+/**
+    Function to write a table with aligned columns
+    * Numbers are aligned to the right, everything else to the left
+    * @param out Where to write the table
+    * @param headers The column headers
+    * @param rows The rows, every row has one value per header
+ **/
+pub fn write_table(out: &mut dyn Write, headers: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let format_row = |values: Vec<&str>| -> String {
+        let cells: Vec<String> = values.iter().zip(&widths)
+            .map(|(value, width)| {
+                let is_number = !value.is_empty() && value.trim_end_matches('%').parse::<f64>().is_ok();
+                if is_number { format!("{:>width$}", value) } else { format!("{:<width$}", value) }
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+
+    writeln!(out, "{}", format_row(headers.to_vec()))?;
+    writeln!(out, "{}", widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("  "))?;
+    for row in rows {
+        writeln!(out, "{}", format_row(row.iter().map(|value| value.as_str()).collect()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::{LineParser, LogFormat};
+
+    #[test]
+    fn entries_without_a_pid_belong_to_no_process() {
+        let mut statistics = LogStatistics::new();
+        statistics.add(&LineParser::new(LogFormat::Tag).parse("E/Tag: no pid").unwrap());
+        statistics.add(&LineParser::new(LogFormat::Brief).parse("E/Tag( 1234): message").unwrap());
+        assert_eq!(statistics.total, 2);
+        assert_eq!(statistics.process_ids(), vec![1234]);
+        assert_eq!(statistics.top_error_processes(1).iter().map(|(pid, _)| *pid).collect::<Vec<_>>(), vec![1234]);
+    }
+
+    fn statistics(lines: &[&str]) -> LogStatistics {
+        let parser = LineParser::new(LogFormat::ThreadTime);
+        let mut statistics = LogStatistics::new();
+        for line in lines {
+            statistics.add(&parser.parse(line).unwrap());
+        }
+        statistics
+    }
+
+    #[test]
+    fn entries_are_counted_by_process_thread_tag_and_level() {
+        let statistics = statistics(&[
+            "10-15 10:18:49.000   200   201 I Wifi: connected",
+            "10-15 10:18:47.000   200   200 E Wifi: lost",
+            "10-15 10:18:48.000   100   100 F Zygote: abort",
+            "10-15 10:18:50.000   100   100 W Zygote: slow",
+            "10-15 10:18:51.000   300   300 D Audio: start",
+        ]);
+        assert_eq!(statistics.total, 5);
+        assert_eq!(statistics.levels.iter().map(|(level, count)| (level.to_string(), *count)).collect::<Vec<_>>(),
+                   [("D".to_string(), 1), ("I".to_string(), 1), ("W".to_string(), 1), ("E".to_string(), 1), ("F".to_string(), 1)]);
+
+        // The first and last timestamps are the extremes, not the first and last lines
+        let wifi = &statistics.processes[&200];
+        assert_eq!((wifi.entries, wifi.errors), (2, 1));
+        assert_eq!((wifi.first_seen.as_str(), wifi.last_seen.as_str()), ("10-15 10:18:47.000", "10-15 10:18:49.000"));
+        assert_eq!(statistics.threads[&201], 1);
+
+        // Ties are broken by the smaller key, processes and tags without errors are left out of the error rankings
+        assert_eq!(statistics.top_processes(2).iter().map(|(pid, _)| *pid).collect::<Vec<_>>(), [100, 200]);
+        assert_eq!(statistics.top_error_processes(5).iter().map(|(pid, _)| *pid).collect::<Vec<_>>(), [100, 200]);
+        assert_eq!(statistics.top_threads(1), [(100, 2)]);
+        assert_eq!(statistics.top_tags(3).iter().map(|(tag, _)| tag.as_str()).collect::<Vec<_>>(), ["Wifi", "Zygote", "Audio"]);
+        assert_eq!(statistics.top_error_tags(5).iter().map(|(tag, _)| tag.as_str()).collect::<Vec<_>>(), ["Wifi", "Zygote"]);
+        assert_eq!(statistics.tags["Zygote"].error_rate(), 0.5);
+        assert_eq!(TagStats::default().error_rate(), 0.0);
+    }
+
+    #[test]
+    fn table_values_are_aligned_numbers_to_the_right() {
+        let mut out = Vec::new();
+        let rows = vec![vec!["Wifi".to_string(), "12".to_string(), "50.0%".to_string()],
+                        vec!["AndroidRuntime".to_string(), "3".to_string(), "".to_string()]];
+        write_table(&mut out, &["tag", "entries", "rate"], &rows).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+tag             entries  rate
+--------------  -------  -----
+Wifi                 12  50.0%
+AndroidRuntime        3
+");
+    }
+}
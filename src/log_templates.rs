@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::log_diff::Normalizer;
+use crate::log_stats;
+use crate::logcat::{self, LogLine, LogcatEntry};
+
+// The token of the variable parts of a template
+const WILDCARD: &str = "<*>";
+
+// Number of example lines kept for every template
+const EXAMPLES: usize = 3;
+
+/**
+    Struct to represent the settings of the template mining
+    * depth The depth of the parse tree: the root, the number of tokens, depth - 3 layers of leading tokens (the first
+    * is the tag) and the leaves
+    * similarity The fraction of equal tokens a message needs to join a template
+    * max_children The number of children of a tree node, the other tokens share a wildcard child
+ **/
+#[derive(Debug, Clone, Copy)]
+pub struct DrainSettings {
+    pub depth: usize,
+    pub similarity: f64,
+    pub max_children: usize,
+}
+
+impl Default for DrainSettings {
+    fn default() -> Self {
+        DrainSettings { depth: 4, similarity: 0.4, max_children: 100 }
+    }
+}
+
+/**
+    Struct to represent a template found in the log
+    * tokens The tag followed by the words of the message, <*> where the entries differ
+    * count The number of entries of the template
+    * examples The first few entries of the template
+ **/
+#[derive(Debug, Clone)]
+pub struct LogTemplate {
+    pub tokens: Vec<String>,
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+impl LogTemplate {
+    /**
+        Function to get the tag of the template
+        * @returns The tag, <*> if the entries have different tags
+     **/
+    pub fn tag(&self) -> &str {
+        &self.tokens[0]
+    }
+
+    pub fn text(&self) -> String {
+        format!("{}: {}", self.tag(), self.tokens[1..].join(" "))
+    }
+
+    // The fraction of equal tokens and the number of wildcards, compared with the tokens of a message
+    fn similarity(&self, tokens: &[String]) -> (f64, usize) {
+        let mut equal = 0;
+        let mut wildcards = 0;
+        for (template_token, token) in self.tokens.iter().zip(tokens) {
+            if template_token == WILDCARD {
+                wildcards += 1;
+            } else if template_token == token {
+                equal += 1;
+            }
+        }
+        (equal as f64 / tokens.len() as f64, wildcards)
+    }
+}
+
+// A node of the parse tree; the leaves have the templates
+#[derive(Default)]
+struct TreeNode {
+    children: HashMap<String, TreeNode>,
+    templates: Vec<usize>,
+}
+
+/**
+    Groups the log messages into templates with the Drain algorithm
+    * The tag and the words of the message are the tokens, then a fixed depth tree leads to a small group of templates
+    * by the number of tokens and the leading tokens; the message joins the most similar template of the group, or of
+    * the other groups with the same number of tokens, or starts a new one. The tokens where the messages of a
+    * template differ become <*>, the tag too, e.g. for the messages every app process logs with its own name.
+    * The ids, addresses, numbers and paths are replaced first, see log_diff::Normalizer.
+ **/
+pub struct TemplateMiner {
+    settings: DrainSettings,
+    normalizer: Normalizer,
+    root: HashMap<usize, TreeNode>,
+    groups: HashMap<usize, Vec<usize>>,
+    templates: Vec<LogTemplate>,
+}
+
+// Tokens with digits, placeholders, paths or assignments are usually parameters, they don't get their own branch in the tree
+fn is_parameter(token: &str) -> bool {
+    token.contains('<') || token.contains('/') || token.contains('=') || token.chars().any(|c| c.is_ascii_digit())
+}
+
+impl TemplateMiner {
+    pub fn new(settings: DrainSettings) -> Self {
+        TemplateMiner {
+            settings,
+            normalizer: Normalizer::new(),
+            root: HashMap::new(),
+            groups: HashMap::new(),
+            templates: Vec::new(),
+        }
+    }
+
+    /**
+        Function to add the message of a log entry
+        * @param tag The tag, the first token of the template
+        * @param message The message
+        * @param example The text kept as an example of the template, e.g. the whole line
+        * @returns The index of the template of the message
+     **/
+    pub fn add(&mut self, tag: &str, message: &str, example: &str) -> usize {
+        let template = self.normalizer.template(message);
+        let tokens: Vec<String> = std::iter::once(tag.to_string())
+            .chain(template.split_whitespace().map(|token| token.to_string()))
+            .collect();
+
+        // The path of the tree: the number of tokens, then the leading tokens
+        let max_children = self.settings.max_children;
+        let mut node = self.root.entry(tokens.len()).or_default();
+        for token in tokens.iter().take(self.settings.depth.saturating_sub(3)) {
+            let key = if is_parameter(token) { WILDCARD } else { token.as_str() };
+            let key = if node.children.contains_key(key) || node.children.len() + 1 < max_children {
+                key
+            } else {
+                WILDCARD
+            };
+            node = node.children.entry(key.to_string()).or_default();
+        }
+
+        // The most similar template of the leaf, the one with fewer wildcards if they are equally similar; if none
+        // is similar enough, the most similar one of the other leaves with the same number of tokens, since their
+        // leading tokens may be parameters too. A template found in another leaf is added to this one
+        let templates = &self.templates;
+        let similarity = self.settings.similarity;
+        let most_similar = |candidates: &[usize]| candidates.iter()
+            .map(|&index| (index, templates[index].similarity(&tokens)))
+            .filter(|(_, (value, _))| *value >= similarity)
+            .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)))
+            .map(|(index, _)| index);
+        let group = self.groups.entry(tokens.len()).or_default();
+        let best = most_similar(&node.templates).or_else(|| {
+            let index = most_similar(group)?;
+            node.templates.push(index);
+            Some(index)
+        });
+
+        let index = match best {
+            Some(index) => {
+                let template = &mut self.templates[index];
+                for (template_token, token) in template.tokens.iter_mut().zip(&tokens) {
+                    if template_token != token {
+                        *template_token = WILDCARD.to_string();
+                    }
+                }
+                index
+            }
+            None => {
+                self.templates.push(LogTemplate { tokens, count: 0, examples: Vec::new() });
+                node.templates.push(self.templates.len() - 1);
+                group.push(self.templates.len() - 1);
+                self.templates.len() - 1
+            }
+        };
+        let template = &mut self.templates[index];
+        template.count += 1;
+        if template.examples.len() < EXAMPLES {
+            template.examples.push(example.to_string());
+        }
+        index
+    }
+
+    /**
+        Function to add a log entry; only the first line of the message is used, like in log_diff
+        * @param entry The entry
+        * @returns The index of the template of the entry
+     **/
+    pub fn add_entry(&mut self, entry: &LogcatEntry) -> usize {
+        let first_line = entry.message.lines().next().unwrap_or("");
+        let example = entry.raw.lines().next().unwrap_or("");
+        self.add(&entry.tag, first_line, example)
+    }
+
+    /**
+        Function to get the templates
+        * @returns The templates, the most frequent first
+     **/
+    pub fn templates(&self) -> Vec<&LogTemplate> {
+        let mut templates: Vec<&LogTemplate> = self.templates.iter().collect();
+        templates.sort_by_key(|template| std::cmp::Reverse(template.count));
+        templates
+    }
+}
+
+/**
+    Function to mine the templates of a log file
+    * @param file_path The log file, in any logcat format
+    * @param settings The settings of the parse tree
+    * @returns The miner with the templates, and the number of entries
+ **/
+pub fn mine_templates(file_path: &str, settings: DrainSettings) -> io::Result<(TemplateMiner, usize)> {
+    let mut miner = TemplateMiner::new(settings);
+    let mut entries = 0;
+    for log_line in logcat::read_log_file(file_path)? {
+        if let LogLine::Entry(entry) = log_line? {
+            miner.add_entry(&entry);
+            entries += 1;
+        }
+    }
+    Ok((miner, entries))
+}
+
+/**
+    Function to write the most frequent templates as a table, with their examples below it
+    * @param out Where to write
+    * @param templates The templates, see TemplateMiner::templates
+    * @param n The number of templates
+ **/
+pub fn write_templates(out: &mut dyn Write, templates: &[&LogTemplate], n: usize) -> io::Result<()> {
+    let rows: Vec<Vec<String>> = templates.iter().take(n)
+        .map(|template| vec![template.count.to_string(), template.text()])
+        .collect();
+    log_stats::write_table(out, &["count", "template"], &rows)?;
+    for template in templates.iter().take(n) {
+        writeln!(out, "{}", template.text())?;
+        for example in &template.examples {
+            writeln!(out, "    {}", example)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(miner: &TemplateMiner) -> Vec<(usize, String)> {
+        miner.templates().iter().map(|template| (template.count, template.text())).collect()
+    }
+
+    #[test]
+    fn similar_messages_share_a_template_with_wildcards() {
+        let mut miner = TemplateMiner::new(DrainSettings::default());
+        let connected = miner.add("Wifi", "connected to home", "1");
+        assert_eq!(miner.add("Wifi", "connected to office", "2"), connected);
+        assert_eq!(miner.add("Wifi", "connected to office after 25 ms", "3"), miner.add("Wifi", "connected to home after 3 ms", "4"));
+        // Only the tag is equal, and a different number of tokens is always another template
+        miner.add("Wifi", "scan started now", "5");
+        miner.add("Wifi", "connected", "6");
+        miner.add("Wifi", "connected to cafe", "7");
+        miner.add("Wifi", "connected to hotel", "8");
+
+        assert_eq!(texts(&miner), [
+            (4, "Wifi: connected to <*>".to_string()),
+            (2, "Wifi: connected to <*> after <num> ms".to_string()),
+            (1, "Wifi: scan started now".to_string()),
+            (1, "Wifi: connected".to_string()),
+        ]);
+        assert_eq!(miner.templates()[0].examples, ["1", "2", "7"]);
+    }
+
+    #[test]
+    fn messages_of_different_tags_are_merged_across_leaves() {
+        let mut miner = TemplateMiner::new(DrainSettings::default());
+        let first = miner.add("com.example.mail", "Background concurrent copying GC freed 512 objects", "");
+        assert_eq!(miner.add("com.example.maps", "Background concurrent copying GC freed 64 objects", ""), first);
+        // The leaf of the second tag found the template too, so it stays one template
+        assert_eq!(miner.add("com.example.maps", "Background concurrent copying GC freed 8 objects", ""), first);
+        assert_eq!(texts(&miner), [(3, "<*>: Background concurrent copying GC freed <num> objects".to_string())]);
+        assert_eq!(miner.templates()[0].tag(), WILDCARD);
+    }
+
+    #[test]
+    fn templates_are_written_with_their_examples() {
+        let mut miner = TemplateMiner::new(DrainSettings::default());
+        miner.add("Wifi", "connected to home", "I Wifi: connected to home");
+        miner.add("Wifi", "connected to office", "I Wifi: connected to office");
+        miner.add("Audio", "start", "D Audio: start");
+        let mut out = Vec::new();
+        write_templates(&mut out, &miner.templates(), 1).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+count  template
+-----  ----------------------
+    2  Wifi: connected to <*>
+Wifi: connected to <*>
+    I Wifi: connected to home
+    I Wifi: connected to office
+");
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::logcat::{LogLevel, LogcatEntry};
+
+// Number of buckets on one line of the sparklines
+const SPARKLINE_WIDTH: usize = 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// More empty buckets than this in a row split the timeline, e.g. after a clock reset or in a capture of several days
+const MAX_GAP: u64 = SPARKLINE_WIDTH as u64;
+
+// The counts of an empty bucket
+static NO_ENTRIES: BTreeMap<LogLevel, usize> = BTreeMap::new();
+
+// Days before the first day of each month, the year is not in the logcat timestamps so it is never a leap year
+const DAYS_BEFORE_MONTH: [u64; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+/**
+    The length of the time buckets of the histograms
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BucketSize {
+    Second,
+    Minute,
+}
+
+impl BucketSize {
+    fn seconds(&self) -> u64 {
+        match self {
+            BucketSize::Second => 1,
+            BucketSize::Minute => 60,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BucketSize::Second => "second",
+            BucketSize::Minute => "minute",
+        }
+    }
+}
+
+/**
+    Function to convert a logcat timestamp to seconds since the beginning of the year
+    * @param date The date, e.g. 10-15
+    * @param time The time, e.g. 10:18:47.782, the fraction is dropped
+    * @returns The seconds, None if the timestamp is missing or invalid
+ **/
+pub fn timestamp_seconds(date: &str, time: &str) -> Option<u64> {
+    let (month, day) = date.split_once('-')?;
+    let month: usize = month.parse().ok()?;
+    let day: u64 = day.parse().ok()?;
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+
+    let mut parts = time.split('.').next()?.split(':');
+    let mut next_number = || -> Option<u64> { parts.next()?.parse().ok() };
+    let (hours, minutes, seconds) = (next_number()?, next_number()?, next_number()?);
+
+    let days = DAYS_BEFORE_MONTH[month - 1] + day - 1;
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/**
+    Function to convert seconds since the beginning of the year back to a timestamp
+    * @param seconds The seconds
+    * @returns The timestamp, e.g. 10-15 10:18:47
+ **/
+fn timestamp_label(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let month = DAYS_BEFORE_MONTH.iter().rposition(|&before| before <= days).unwrap_or(0);
+    let day = days - DAYS_BEFORE_MONTH[month] + 1;
+    let time = seconds % 86400;
+    format!("{:02}-{:02} {:02}:{:02}:{:02}", month + 1, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/**
+    Struct to represent the number of entries by time bucket and level
+    * bucket_size The length of the buckets
+    * buckets The counts by level, keyed by the start of the bucket in seconds since the beginning of the year
+    * Entries without a timestamp (e.g. brief format) are not counted
+ **/
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    pub bucket_size: BucketSize,
+    buckets: BTreeMap<u64, BTreeMap<LogLevel, usize>>,
+}
+
+impl Timeline {
+    pub fn new(bucket_size: BucketSize) -> Self {
+        Timeline {
+            bucket_size,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /**
+        Function to add an entry to its bucket
+        * @param entry The entry to count
+     **/
+    pub fn add(&mut self, entry: &LogcatEntry) {
+        if let Some(seconds) = timestamp_seconds(&entry.date, &entry.time) {
+            let start = seconds - seconds % self.bucket_size.seconds();
+            *self.buckets.entry(start).or_default().entry(entry.level).or_insert(0) += 1;
+        }
+    }
+
+    /**
+        Function to get the buckets with entries
+        * @returns The start of each bucket with its counts by level
+     **/
+    pub fn buckets(&self) -> Vec<(u64, &BTreeMap<LogLevel, usize>)> {
+        self.buckets.iter().map(|(start, counts)| (*start, counts)).collect()
+    }
+
+    /**
+        Function to get the runs of consecutive buckets, the short gaps filled with empty buckets
+        * A gap of more than MAX_GAP empty buckets ends a run, so a clock reset doesn't make millions of buckets
+        * @returns The runs in chronological order, every bucket with its start and its counts by level
+     **/
+    pub fn segments(&self) -> Vec<Vec<(u64, &BTreeMap<LogLevel, usize>)>> {
+        let step = self.bucket_size.seconds();
+        let mut segments: Vec<Vec<(u64, &BTreeMap<LogLevel, usize>)>> = Vec::new();
+        for (&start, counts) in &self.buckets {
+            match segments.last_mut() {
+                Some(segment) if (start - segment.last().unwrap().0) / step <= MAX_GAP + 1 => {
+                    let mut empty = segment.last().unwrap().0 + step;
+                    while empty < start {
+                        segment.push((empty, &NO_ENTRIES));
+                        empty += step;
+                    }
+                    segment.push((start, counts));
+                }
+                _ => segments.push(vec![(start, counts)]),
+            }
+        }
+        segments
+    }
+
+    pub fn label(&self, start: u64) -> String {
+        timestamp_label(start)
+    }
+
+    fn levels(&self) -> Vec<LogLevel> {
+        let mut levels: Vec<LogLevel> = self.buckets.values().flat_map(|counts| counts.keys().cloned()).collect();
+        levels.sort();
+        levels.dedup();
+        levels
+    }
+
+    /**
+        Function to write the histograms of every level as sparklines
+        * Every level is scaled to its own maximum, the maximum is written after the line
+        * @param out Where to write the histograms
+     **/
+    pub fn write_sparklines(&self, out: &mut dyn Write) -> io::Result<()> {
+        let segments = self.segments();
+        writeln!(out, "Entries by {} and level:", self.bucket_size.name())?;
+        if segments.is_empty() {
+            return writeln!(out, "No entries with a timestamp");
+        }
+
+        let levels = self.levels();
+        let mut previous_end = None;
+        for segment in &segments {
+            if let Some(end) = previous_end {
+                let empty = (segment[0].0 - end) / self.bucket_size.seconds() - 1;
+                writeln!(out, "... no entries for {} {}s", empty, self.bucket_size.name())?;
+            }
+            for chunk in segment.chunks(SPARKLINE_WIDTH) {
+                let last = chunk.len() - 1;
+                writeln!(out, "{} - {}", self.label(chunk[0].0), self.label(chunk[last].0))?;
+                for level in &levels {
+                    let counts: Vec<usize> = chunk.iter().map(|(_, counts)| counts.get(level).cloned().unwrap_or(0)).collect();
+                    let max = counts.iter().cloned().max().unwrap_or(0);
+                    writeln!(out, "  {} {} max {}", level, sparkline(&counts, max), max)?;
+                }
+            }
+            previous_end = Some(segment[segment.len() - 1].0);
+        }
+        Ok(())
+    }
+}
+
+/**
+    Function to draw counts as a line of bars
+    * Empty buckets are spaces, so they can be told apart from small counts
+    * @param counts The counts
+    * @param max The count drawn as a full bar
+    * @returns The sparkline, one character per count
+ **/
+pub fn sparkline(counts: &[usize], max: usize) -> String {
+    counts.iter()
+        .map(|&count| {
+            if count == 0 || max == 0 {
+                ' '
+            } else {
+                SPARKS[((count - 1) * SPARKS.len() / max).min(SPARKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+/**
+    Struct to represent the settings of the burst detection
+    * window The number of previous buckets the baseline is averaged over
+    * factor How many times the baseline a bucket has to reach to be a burst
+    * min_count The smallest count that can be a burst, so a few errors after a quiet period are not flagged
+ **/
+#[derive(Debug, Clone, Copy)]
+pub struct BurstSettings {
+    pub window: usize,
+    pub factor: f64,
+    pub min_count: usize,
+}
+
+impl Default for BurstSettings {
+    fn default() -> Self {
+        BurstSettings {
+            window: 10,
+            factor: 3.0,
+            min_count: 5,
+        }
+    }
+}
+
+/**
+    Struct to represent a time window with unusually many warnings and errors
+    * start, end The first and last bucket of the burst
+    * count The number of warnings and errors in the burst
+    * baseline The average count per bucket before the burst
+ **/
+#[derive(Debug, Clone)]
+pub struct Burst {
+    pub start: String,
+    pub end: String,
+    pub count: usize,
+    pub baseline: f64,
+}
+
+/**
+    Function to find the bursts of warnings and errors
+    * A bucket is flagged if its count of W and above is at least min_count and more than factor times
+    * the average of the previous window buckets; consecutive flagged buckets make one burst.
+    * The first bucket has no baseline, it is never flagged; neither is the first bucket after a long gap, see
+    * Timeline::segments, the baseline starts again there.
+    * @param timeline The counts by bucket
+    * @param settings The window, the factor and the minimal count
+    * @returns The bursts in chronological order
+ **/
+pub fn detect_bursts(timeline: &Timeline, settings: &BurstSettings) -> Vec<Burst> {
+    let mut bursts: Vec<Burst> = Vec::new();
+    for segment in timeline.segments() {
+        detect_segment_bursts(timeline, &segment, settings, &mut bursts);
+    }
+    bursts
+}
+
+fn detect_segment_bursts(timeline: &Timeline, segment: &[(u64, &BTreeMap<LogLevel, usize>)], settings: &BurstSettings, bursts: &mut Vec<Burst>) {
+    let counts: Vec<(u64, usize)> = segment.iter()
+        .map(|(start, levels)| {
+            let count = levels.iter().filter(|(level, _)| **level >= LogLevel::Warning).map(|(_, count)| count).sum();
+            (*start, count)
+        })
+        .collect();
+
+    let mut previous_flagged = false;
+    for (index, &(start, count)) in counts.iter().enumerate() {
+        let history = &counts[index.saturating_sub(settings.window)..index];
+        let flagged = if history.is_empty() {
+            false
+        } else {
+            let baseline = history.iter().map(|(_, count)| *count).sum::<usize>() as f64 / history.len() as f64;
+            let flagged = count >= settings.min_count && count as f64 > settings.factor * baseline;
+            if flagged && !previous_flagged {
+                bursts.push(Burst { start: timeline.label(start), end: String::new(), count: 0, baseline });
+            }
+            flagged
+        };
+        if let (true, Some(burst)) = (flagged, bursts.last_mut()) {
+            burst.end = timeline.label(start);
+            burst.count += count;
+        }
+        previous_flagged = flagged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::{LineParser, LogFormat};
+
+    fn timeline(lines: &[&str]) -> Timeline {
+        let parser = LineParser::new(LogFormat::ThreadTime);
+        let mut timeline = Timeline::new(BucketSize::Second);
+        for line in lines {
+            timeline.add(&parser.parse(line).unwrap());
+        }
+        timeline
+    }
+
+    #[test]
+    fn short_gaps_are_filled_with_empty_buckets() {
+        let timeline = timeline(&[
+            "10-15 10:18:47.782  1234  1234 I Tag: a",
+            "10-15 10:18:50.100  1234  1234 E Tag: b",
+        ]);
+        let segments = timeline.segments();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].len(), 4);
+        assert!(segments[0][1].1.is_empty());
+        assert_eq!(timeline.buckets().len(), 2);
+    }
+
+    #[test]
+    fn clock_reset_is_one_gap() {
+        let timeline = timeline(&[
+            "01-01 00:00:05.000  1234  1234 I Tag: after the reset",
+            "10-15 10:18:47.782  1234  1234 W Tag: before the reset",
+        ]);
+        let segments = timeline.segments();
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|segment| segment.len() == 1));
+
+        let mut out = Vec::new();
+        timeline.write_sparklines(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("... no entries for"));
+        assert!(detect_bursts(&timeline, &BurstSettings::default()).is_empty());
+    }
+}
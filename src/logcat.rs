@@ -0,0 +1,477 @@
+use regex::Regex;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+
+/**
+    The priority of a logcat entry, from the least to the most severe
+ **/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Verbose,
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Fatal,
+    Assert,
+}
+
+impl LogLevel {
+    /**
+        Function to get the level from its logcat letter
+        * @param letter One of V D I W E F A
+        * @returns The level, None for any other letter
+     **/
+    pub fn from_letter(letter: char) -> Option<LogLevel> {
+        match letter {
+            'V' => Some(LogLevel::Verbose),
+            'D' => Some(LogLevel::Debug),
+            'I' => Some(LogLevel::Info),
+            'W' => Some(LogLevel::Warning),
+            'E' => Some(LogLevel::Error),
+            'F' => Some(LogLevel::Fatal),
+            'A' => Some(LogLevel::Assert),
+            _ => None,
+        }
+    }
+
+    pub fn letter(&self) -> char {
+        match self {
+            LogLevel::Verbose => 'V',
+            LogLevel::Debug => 'D',
+            LogLevel::Info => 'I',
+            LogLevel::Warning => 'W',
+            LogLevel::Error => 'E',
+            LogLevel::Fatal => 'F',
+            LogLevel::Assert => 'A',
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+/**
+    Struct to represent one logcat entry, whatever format it was captured in
+    * date, time The timestamp, e.g. 10-15 and 10:18:47.782; the year is dropped and epoch
+    * timestamps are converted to UTC; empty if the format has no timestamp
+    * uid The user id column, if the capture has one
+    * pid The process id, None if the format doesn't show it (tag format)
+    * tid The thread id, None if the format doesn't show it (only threadtime and long do)
+    * level The priority of the entry
+    * tag The tag, without the padding
+    * message The message, continuation lines are appended after a newline
+    * raw The original text of the entry, including continuation lines
+    * line_number The line of the file where the entry starts, 0 if it was not read from a file
+ **/
+#[derive(Debug, Clone)]
+pub struct LogcatEntry {
+    pub date: String,
+    pub time: String,
+    pub uid: Option<String>,
+    pub pid: Option<u32>,
+    pub tid: Option<u32>,
+    pub level: LogLevel,
+    pub tag: String,
+    pub message: String,
+    pub raw: String,
+    pub line_number: usize,
+}
+
+/**
+    Writes the entry in threadtime format, the way logcat prints it
+ **/
+impl fmt::Display for LogcatEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.date.is_empty() {
+            write!(f, "{} {} ", self.date, self.time)?;
+        }
+        if let Some(uid) = &self.uid {
+            write!(f, "{:>5} ", uid)?;
+        }
+        let id = |id: Option<u32>| id.map_or("-".to_string(), |id| id.to_string());
+        write!(f, "{:>5} {:>5} {} {:<8}: {}", id(self.pid), id(self.tid), self.level, self.tag, self.message)
+    }
+}
+
+/**
+    One item of a log file
+    * Entry A parsed entry
+    * Unparsed A line that is not an entry, e.g. --------- beginning of main
+ **/
+#[derive(Debug, Clone)]
+pub enum LogLine {
+    Entry(LogcatEntry),
+    Unparsed { line_number: usize, text: String },
+}
+
+/**
+    The output formats of logcat (logcat -v <format>)
+    * Brief I/Tag( 1234): message
+    * Process I( 1234) message  (Tag)
+    * Tag I/Tag: message
+    * Time 10-15 10:18:47.782 I/Tag( 1234): message
+    * ThreadTime 10-15 10:18:47.782  1234  1234 I Tag: message
+    * Long [ 10-15 10:18:47.782  1234: 1234 I/Tag ] followed by the message lines and an empty line
+    * The timestamps may have a year (2022-10-15) or be epoch seconds (1665829127.782),
+    * and a uid column may come before the pid
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    ThreadTime,
+    Time,
+    Long,
+    Brief,
+    Process,
+    Tag,
+}
+
+// Detection prefers the earlier formats when several parse the same lines (e.g. every brief line is also a tag line)
+const FORMATS: [LogFormat; 6] = [
+    LogFormat::ThreadTime,
+    LogFormat::Time,
+    LogFormat::Long,
+    LogFormat::Brief,
+    LogFormat::Process,
+    LogFormat::Tag,
+];
+
+// Number of lines looked at by the format detection
+pub const DETECTION_LINES: usize = 20;
+
+const TIMESTAMP: &str = r"(?:(?:\d{4}-)?(?P<date>\d\d-\d\d) (?P<time>\d\d:\d\d:\d\d\.\d+)|(?P<epoch>\d+\.\d+))";
+const PROCESS_ID: &str = r"\(\s*(?:(?P<uid>[^:\s()]+):\s*)?(?P<pid>\d+)\)";
+
+impl LogFormat {
+    fn pattern(&self) -> String {
+        // The tag can contain colons (e.g. niss:interactor), in threadtime it ends at the first ": "
+        match self {
+            LogFormat::ThreadTime => format!(r"^{} +(?:(?P<uid>\S+) +)?(?P<pid>\d+) +(?P<tid>\d+) (?P<level>[VDIWEFA]) (?P<tag>.*?):(?: (?P<message>.*)|$)", TIMESTAMP),
+            LogFormat::Time => format!(r"^{} +(?P<level>[VDIWEFA])/(?P<tag>.*?){}:(?: (?P<message>.*)|$)", TIMESTAMP, PROCESS_ID),
+            LogFormat::Long => format!(r"^\[ {} +(?:(?P<uid>\S+) +)?(?P<pid>\d+): *(?P<tid>\d+) (?P<level>[VDIWEFA])/(?P<tag>.*?) *\]$", TIMESTAMP),
+            LogFormat::Brief => format!(r"^(?P<level>[VDIWEFA])/(?P<tag>.*?){}:(?: (?P<message>.*)|$)", PROCESS_ID),
+            LogFormat::Process => format!(r"^(?P<level>[VDIWEFA]){} (?P<message>.*?)  \((?P<tag>.*)\)$", PROCESS_ID),
+            LogFormat::Tag => r"^(?P<level>[VDIWEFA])/(?P<tag>.*?):(?: (?P<message>.*)|$)".to_string(),
+        }
+    }
+}
+
+/**
+    Function to convert epoch seconds to an UTC date and time
+    * @param epoch The seconds since 1970-01-01, e.g. 1665829127.782
+    * @returns The date (MM-DD) and the time (HH:MM:SS.mmm)
+ **/
+fn epoch_to_date_time(epoch: &str) -> Option<(String, String)> {
+    let (seconds, fraction) = epoch.split_once('.').unwrap_or((epoch, ""));
+    let seconds: i64 = seconds.parse().ok()?;
+    let days = seconds.div_euclid(86400);
+    let time_of_day = seconds.rem_euclid(86400);
+
+    // Civil date from the number of days since the epoch (H. Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+
+    let date = format!("{:02}-{:02}", month, day);
+    let mut time = format!("{:02}:{:02}:{:02}", time_of_day / 3600, time_of_day % 3600 / 60, time_of_day % 60);
+    if !fraction.is_empty() {
+        time.push('.');
+        time.push_str(fraction);
+    }
+    Some((date, time))
+}
+
+/**
+    Parser of the lines of one logcat format
+ **/
+pub struct LineParser {
+    format: LogFormat,
+    pattern: Regex,
+}
+
+impl LineParser {
+    pub fn new(format: LogFormat) -> Self {
+        LineParser {
+            format,
+            pattern: Regex::new(&format.pattern()).unwrap(),
+        }
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    /**
+        Function to parse one line
+        * In long format this is the header line, the message comes in the following lines
+        * @param line The line to parse
+        * @returns The entry, None if the line is not in this format
+     **/
+    pub fn parse(&self, line: &str) -> Option<LogcatEntry> {
+        let captures = self.pattern.captures(line)?;
+        let text = |name: &str| captures.name(name).map_or("", |value| value.as_str());
+
+        let (date, time) = match captures.name("epoch") {
+            Some(epoch) => epoch_to_date_time(epoch.as_str())?,
+            None => (text("date").to_string(), text("time").to_string()),
+        };
+        // A missing id is None, an id that doesn't fit a u32 makes the line unparsed
+        let number = |name: &str| -> Option<Option<u32>> {
+            match captures.name(name) {
+                Some(value) => Some(Some(value.as_str().parse().ok()?)),
+                None => Some(None),
+            }
+        };
+
+        Some(LogcatEntry {
+            date,
+            time,
+            uid: captures.name("uid").map(|uid| uid.as_str().to_string()),
+            pid: number("pid")?,
+            tid: number("tid")?,
+            level: LogLevel::from_letter(text("level").chars().next()?)?,
+            tag: text("tag").trim().to_string(),
+            message: text("message").to_string(),
+            raw: line.to_string(),
+            line_number: 0,
+        })
+    }
+
+    /**
+        Function to find the message in a line
+        * The message is at the end of the line, except in process format where the tag follows it
+        * @param line The line
+        * @returns The byte range of the message, empty at the end of the line if there is none (e.g. a long
+        * header); None if the line is not in this format
+     **/
+    pub fn message_range(&self, line: &str) -> Option<Range<usize>> {
+        let captures = self.pattern.captures(line)?;
+        Some(captures.name("message").map_or(line.len()..line.len(), |message| message.range()))
+    }
+}
+
+/**
+    Function to detect the format of a capture from its first lines
+    * @param lines The first lines of the capture
+    * @returns The format that parses the most lines, ThreadTime if none of them parse
+ **/
+pub fn detect_format(lines: &[String]) -> LogFormat {
+    let mut best = (LogFormat::ThreadTime, 0);
+    for format in FORMATS {
+        let parser = LineParser::new(format);
+        let count = lines.iter().filter(|line| parser.parse(line).is_some()).count();
+        if count > best.1 {
+            best = (format, count);
+        }
+    }
+    best.0
+}
+
+/**
+    Iterator that parses a logcat capture line by line
+    * The format is detected from the first lines, unless it is given
+    * Indented lines that don't parse are continuation lines, they are attached to the entry before them
+    * (in long format every line after the header belongs to the entry, until an empty line)
+    * An entry is only returned once the next line is read, so its continuation lines are complete; if that
+    * line can't be read, the entry is returned before the error
+ **/
+pub struct LogcatReader<R: BufRead> {
+    lines: io::Lines<R>,
+    buffered: VecDeque<String>,
+    line_number: usize,
+    parser: LineParser,
+    pending: Option<LogcatEntry>,
+    ready: VecDeque<LogLine>,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead> LogcatReader<R> {
+    /**
+        Function to create a reader that detects the format of the capture
+        * @param reader The capture
+        * @returns The reader, or an error if the first lines can't be read
+     **/
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        let mut buffered = VecDeque::new();
+        while buffered.len() < DETECTION_LINES {
+            match lines.next() {
+                Some(line) => buffered.push_back(line?),
+                None => break,
+            }
+        }
+        let format = detect_format(buffered.make_contiguous());
+
+        Ok(LogcatReader {
+            lines,
+            buffered,
+            line_number: 0,
+            parser: LineParser::new(format),
+            pending: None,
+            ready: VecDeque::new(),
+            error: None,
+        })
+    }
+
+    /**
+        Function to create a reader for a known format
+        * @param reader The capture
+        * @param format The format of the capture
+     **/
+    pub fn with_format(reader: R, format: LogFormat) -> Self {
+        LogcatReader {
+            lines: reader.lines(),
+            buffered: VecDeque::new(),
+            line_number: 0,
+            parser: LineParser::new(format),
+            pending: None,
+            ready: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.parser.format()
+    }
+
+    fn next_line(&mut self) -> Option<io::Result<String>> {
+        match self.buffered.pop_front() {
+            Some(line) => Some(Ok(line)),
+            None => self.lines.next(),
+        }
+    }
+
+    // Appends a continuation line to the entry that is being read
+    fn continue_entry(&mut self, line: &str) -> bool {
+        let is_long = self.parser.format() == LogFormat::Long;
+        let Some(entry) = self.pending.as_mut() else {
+            return false;
+        };
+        if is_long {
+            // The message starts on the line after the header
+            if !entry.message.is_empty() || entry.raw.contains('\n') {
+                entry.message.push('\n');
+            }
+            entry.message.push_str(line);
+        } else if line.starts_with([' ', '\t']) {
+            entry.message.push('\n');
+            entry.message.push_str(line.trim());
+        } else {
+            return false;
+        }
+        entry.raw.push('\n');
+        entry.raw.push_str(line);
+        true
+    }
+}
+
+impl<R: BufRead> Iterator for LogcatReader<R> {
+    type Item = io::Result<LogLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() {
+            if let Some(err) = self.error.take() {
+                return Some(Err(err));
+            }
+        }
+        while self.ready.is_empty() {
+            let line = match self.next_line() {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => match self.pending.take() {
+                    Some(entry) => {
+                        self.error = Some(err);
+                        self.ready.push_back(LogLine::Entry(entry));
+                        continue;
+                    }
+                    None => return Some(Err(err)),
+                },
+                None => return self.pending.take().map(|entry| Ok(LogLine::Entry(entry))),
+            };
+            self.line_number += 1;
+
+            if let Some(mut entry) = self.parser.parse(&line) {
+                entry.line_number = self.line_number;
+                if let Some(previous) = self.pending.replace(entry) {
+                    self.ready.push_back(LogLine::Entry(previous));
+                }
+                continue;
+            }
+
+            // In long format an empty line closes the entry
+            if self.parser.format() == LogFormat::Long && line.is_empty() {
+                if let Some(previous) = self.pending.take() {
+                    self.ready.push_back(LogLine::Entry(previous));
+                }
+                continue;
+            }
+
+            if self.continue_entry(&line) {
+                continue;
+            }
+
+            if let Some(previous) = self.pending.take() {
+                self.ready.push_back(LogLine::Entry(previous));
+            }
+            self.ready.push_back(LogLine::Unparsed { line_number: self.line_number, text: line });
+        }
+        self.ready.pop_front().map(Ok)
+    }
+}
+
+/**
+    Function to open a log file for parsing, the format is detected automatically
+    * @param file_path The path of the log file
+    * @returns An iterator over the lines of the log
+ **/
+pub fn read_log_file(file_path: &str) -> io::Result<LogcatReader<BufReader<File>>> {
+    let file = File::open(file_path)?;
+    LogcatReader::new(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tid_is_only_set_by_formats_that_show_it() {
+        let entry = LineParser::new(LogFormat::ThreadTime).parse("10-15 10:18:47.782  1234  1240 I Tag: message").unwrap();
+        assert_eq!((entry.pid, entry.tid), (Some(1234), Some(1240)));
+        let entry = LineParser::new(LogFormat::Time).parse("10-15 10:18:47.782 I/Tag( 1234): message").unwrap();
+        assert_eq!((entry.pid, entry.tid), (Some(1234), None));
+        assert_eq!(entry.to_string(), "10-15 10:18:47.782  1234     - I Tag     : message");
+        let entry = LineParser::new(LogFormat::Tag).parse("I/Tag: message").unwrap();
+        assert_eq!((entry.pid, entry.tid), (None, None));
+    }
+
+    fn first_lines(filename: &str) -> Vec<String> {
+        BufReader::new(File::open(filename).unwrap()).lines().take(DETECTION_LINES).map(|line| line.unwrap()).collect()
+    }
+
+    #[test]
+    fn every_sample_format_is_detected() {
+        let samples = [
+            ("src/resources/sample_reboot.log", LogFormat::ThreadTime),
+            ("src/resources/sample_threadtime_uid.log", LogFormat::ThreadTime),
+            ("src/resources/sample_time.log", LogFormat::Time),
+            ("src/resources/sample_long.log", LogFormat::Long),
+            ("src/resources/sample_brief.log", LogFormat::Brief),
+            ("src/resources/sample_process.log", LogFormat::Process),
+            ("src/resources/sample_tag.log", LogFormat::Tag),
+        ];
+        for (filename, format) in samples {
+            assert_eq!(detect_format(&first_lines(filename)), format, "{}", filename);
+        }
+        assert_eq!(detect_format(&["not a log line".to_string()]), LogFormat::ThreadTime);
+    }
+}
@@ -4,6 +4,26 @@ Informatika III.
 */
 
 mod automata;
+mod grammar;
+mod log_automata;
+mod log_diff;
+mod log_error;
+mod log_filter;
+mod log_follow;
+mod log_incidents;
+mod log_index;
+mod log_lint;
+mod log_merge;
+mod log_processes;
+mod log_redact;
+mod log_report;
+mod log_rules;
+mod log_sessions;
+mod log_stack_traces;
+mod log_stats;
+mod log_templates;
+mod log_timeline;
+mod logcat;
 mod regular_expressions;
 mod stack;
 
@@ -29,8 +49,8 @@ fn test_equivalence_check(){
     // Read dfa_5 and dfa_6
     let filename_1 = "src/resources/dfa_5.txt";
     let filename_2 = "src/resources/dfa_6.txt";
-    let mut automata_1 = automata::read_automata(filename_1);
-    let mut automata_2 = automata::read_automata(filename_2);
+    let automata_1 = automata::read_automata(filename_1);
+    let automata_2 = automata::read_automata(filename_2);
 
     // Print the automatas
     automata_1.print_automata();
@@ -39,31 +59,658 @@ fn test_equivalence_check(){
     // Check if they are equivalent
     println!();
     println!("Checking if the automatas are equivalent...");
-    let result = automata::check_equivalence(&mut automata_1, &mut automata_2);
+    let result = automata::check_equivalence(&automata_1, &automata_2);
     println!("The automatas are equivalent: {}", result);
 
 }
 
 fn test_regular_expressions(){
     let filename_1 = "src/resources/2022-10-15-10.18.37.log";
-    if let Err(err) = regular_expressions::process_log_file(filename_1) {
+    let rules_filename = "src/resources/log_rules.txt";
+
+    println!("Histogram bucket size, s (second) or m (minute) [s]:");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).expect("Failed to read line");
+    let bucket_size = if input.trim().eq_ignore_ascii_case("m") {
+        log_timeline::BucketSize::Minute
+    } else {
+        log_timeline::BucketSize::Second
+    };
+
+    println!("Pattern engine, r (regex), a (automata over characters) or b (automata over bytes) [r]:");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).expect("Failed to read line");
+    let automata = match input.trim() {
+        "a" | "A" => Some(log_automata::Alphabet::Char),
+        "b" | "B" => Some(log_automata::Alphabet::Byte),
+        _ => None,
+    };
+
+    let report = match regular_expressions::process_log_file(filename_1, rules_filename, bucket_size, automata) {
+        Ok(report) => report,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    for (name, err) in &report.automata_fallbacks {
+        println!("{} is matched with the regex engine, its pattern can't be matched the same way by an automata {}", name, err);
+    }
+
+    // Writing the results to the screen
+    if let Err(err) = log_report::ReportFormat::Text.writer().write_report(&report, &mut std::io::stdout()) {
         println!("Error: {}", err);
     }
+
+    println!("Overwrite the previous results instead of appending to them? [y/N]:");
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).expect("Failed to read line");
+    let mode = if input.trim().eq_ignore_ascii_case("y") {
+        log_report::WriteMode::Overwrite
+    } else {
+        log_report::WriteMode::Append
+    };
+
+    let outputs = [
+        ("processed.txt", log_report::ReportFormat::Text),
+        ("processed.json", log_report::ReportFormat::Json),
+        ("processed.csv", log_report::ReportFormat::Csv),
+    ];
+    for (path, format) in outputs {
+        match log_report::save_report(&report, path, format, mode) {
+            Ok(()) => println!("The results were saved to {}", path),
+            Err(err) => println!("Error: {}", err),
+        }
+    }
 }
 
 fn test_stack_automata(){
     let filename_1 = "src/resources/dfa_7.txt";
     let word = String::from("aaabbb");
-    let mut automata = stack::read_automata(filename_1);
+    let automata = stack::read_automata(filename_1);
     automata.print_automata();
 
     println!("Processing the word: {}", word);
-    let result = automata.process_word(&word);
+    let result = automata.accepts_word(&word);
     println!("The word is accepted: {}", result);
 }
+
+fn test_deterministic_stack_automata(){
+    let filenames = ["src/resources/dfa_7.txt", "src/resources/dfa_8.txt"];
+    let words = ["", "ab", "aabb", "abba", "abab", "aaabbb"];
+
+    for filename in filenames {
+        println!("Checking {}", filename);
+        let automata = stack::read_automata(filename);
+        automata.print_conflicts();
+
+        // A DPDA is run in linear time, otherwise the configurations are searched
+        for word in words {
+            let result = automata.accepts_word(word);
+            println!("The word '{}' is accepted: {}", word, result);
+        }
+        println!();
+    }
+}
+
+fn test_stack_automata_to_grammar(){
+    let filename_1 = "src/resources/dfa_7.txt";
+    let automata = stack::read_automata(filename_1);
+    automata.print_automata();
+
+    println!();
+    println!("Converting the stack automata (empty stack acceptance) to a grammar...");
+    let grammar = automata.to_context_free_grammar();
+    grammar.print_grammar();
+}
+
+fn test_grammar_validation(){
+    let filenames = ["src/resources/cfg_1.txt", "src/resources/cfg_2.txt"];
+    for filename in filenames {
+        let grammar = grammar::read_grammar(filename);
+        grammar.print_grammar();
+        grammar.print_issues();
+        println!();
+    }
+}
+
+fn test_chomsky_normal_form(){
+    let grammar = grammar::read_grammar("src/resources/cfg_1.txt");
+    grammar.print_grammar();
+
+    println!();
+    println!("Converting the grammar to Chomsky normal form...");
+    let normal_form = grammar.to_chomsky_normal_form();
+    normal_form.print_grammar();
+
+    // dfa_7 accepts the same language, so CYK and the stack automata should agree
+    let automata = stack::read_automata("src/resources/dfa_7.txt");
+    for word in ["", "ab", "aabb", "aab", "abab", "aaabbb"] {
+        let result = normal_form.cyk(word);
+        println!();
+        println!("CYK table for '{}':", word);
+        result.print_table();
+        if let Some(parse_tree) = &result.parse_tree {
+            println!("Parse tree:");
+            parse_tree.print_tree(0);
+        }
+        let automata_result = automata.accepts_word(word);
+        println!("CYK: {}, stack automata: {}, agree: {}", result.is_accepted(), automata_result,
+                 result.is_accepted() == automata_result);
+    }
+}
+
+fn test_grammar_to_stack_automata(){
+    let filenames = [
+        ("src/resources/cfg_1.txt", "cfg_1_pda.txt"),
+        ("src/resources/cfg_3.txt", "cfg_3_pda.txt"),
+    ];
+
+    for (grammar_filename, automata_filename) in filenames {
+        let grammar = grammar::read_grammar(grammar_filename);
+        grammar.print_grammar();
+
+        // Save the generated automata and read it back, like any other automata file
+        let generated = match stack::StackAutomata::from_grammar(&grammar) {
+            Ok(automata) => automata,
+            Err(err) => {
+                println!("Error: {}", err);
+                continue;
+            }
+        };
+        if let Err(err) = generated.write_automata(automata_filename) {
+            println!("Error: {}", err);
+            continue;
+        }
+        println!("The stack automata was saved to {}", automata_filename);
+        let automata = stack::read_automata(automata_filename);
+
+        // Every word up to length 6 is checked against CYK
+        let normal_form = grammar.to_chomsky_normal_form();
+        let mut words = vec![String::new()];
+        let mut last_words = words.clone();
+        for _ in 0..6 {
+            last_words = last_words.iter()
+                .flat_map(|word| grammar.terminals().iter().map(move |terminal| format!("{}{}", word, terminal)))
+                .collect();
+            words.extend(last_words.iter().cloned());
+        }
+
+        let mut disagreements = 0;
+        for word in &words {
+            let derived = normal_form.cyk(word).is_accepted();
+            let accepted = automata.accepts_word(word);
+            if derived {
+                println!("'{}' is derived by the grammar, accepted by the stack automata: {}", word, accepted);
+            }
+            if derived != accepted {
+                disagreements += 1;
+            }
+        }
+        println!("Checked {} words, disagreements: {}", words.len(), disagreements);
+        println!();
+    }
+}
+
+fn run_filter(filename: &str, expression: &str) {
+    let filter = match log_filter::parse_filter(expression) {
+        Ok(filter) => filter,
+        Err(err) => {
+            println!("Invalid filter {}", err);
+            return;
+        }
+    };
+    match regular_expressions::filter_log_file(filename, &filter) {
+        Ok(count) => println!("Number of matching entries: {}", count),
+        Err(err) => println!("Error: {}", err),
+    }
+}
+
+fn test_log_filter(){
+    println!("Filter expression (e.g. level>=W and tag~\"ExoPlayer\" and time in 10:18:51..10:19:00):");
+    let mut expression = String::new();
+    std::io::stdin().read_line(&mut expression).expect("Failed to read line");
+    run_filter("src/resources/2022-10-15-10.18.37.log", expression.trim());
+}
+
+fn test_log_formats() {
+    let filenames = [
+        "src/resources/2022-10-15-10.18.37.log",
+        "src/resources/sample_threadtime_uid.log",
+        "src/resources/sample_time.log",
+        "src/resources/sample_long.log",
+        "src/resources/sample_brief.log",
+        "src/resources/sample_process.log",
+        "src/resources/sample_tag.log",
+    ];
+    for filename in filenames {
+        let reader = match logcat::read_log_file(filename) {
+            Ok(reader) => reader,
+            Err(err) => {
+                println!("Error reading {}: {}", filename, err);
+                continue;
+            }
+        };
+        println!("{}: detected format {:?}", filename, reader.format());
+        let mut entries = 0;
+        let mut unparsed = 0;
+        for log_line in reader {
+            match log_line {
+                Ok(logcat::LogLine::Entry(entry)) => {
+                    // Every entry is printed in threadtime format, only the first few of the big log
+                    if entries < 5 {
+                        println!("{}", entry);
+                    }
+                    entries += 1;
+                }
+                Ok(logcat::LogLine::Unparsed { .. }) => unparsed += 1,
+                Err(err) => {
+                    println!("Error: {}", err);
+                    break;
+                }
+            }
+        }
+        println!("Entries: {}, other lines: {}", entries, unparsed);
+        println!();
+    }
+
+    // The format can also be given instead of detected
+    let file = std::fs::File::open("src/resources/sample_brief.log").expect("Failed to open file");
+    let reader = logcat::LogcatReader::with_format(std::io::BufReader::new(file), logcat::LogFormat::Tag);
+    let tags: Vec<String> = reader
+        .filter_map(|log_line| match log_line {
+            Ok(logcat::LogLine::Entry(entry)) => Some(entry.tag),
+            _ => None,
+        })
+        .collect();
+    println!("sample_brief.log read as {:?} format gives the tags {:?}", logcat::LogFormat::Tag, tags);
+}
+
+fn test_log_merge() {
+    let filenames = [
+        "src/resources/2022-10-15-10.18.37.log",
+        "src/resources/sample_time.log",
+        "src/resources/sample_threadtime_uid.log",
+    ];
+    let merged = match log_merge::merge_log_files(&filenames) {
+        Ok(merged) => merged,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    merged.write_summary(&mut std::io::stdout()).expect("Failed to write the summary");
+
+    println!("The entries of the samples in the merged stream:");
+    for (index, merged_entry) in merged.entries.iter().enumerate().filter(|(_, merged_entry)| merged_entry.file > 0) {
+        let buffer = &merged.segments[merged_entry.segment].buffer;
+        println!("{:>6} [{} {}] {}", index, merged_entry.file, buffer, merged_entry.entry);
+    }
+
+    match merged.save("merged.log") {
+        Ok(()) => println!("The merged log was saved to merged.log"),
+        Err(err) => println!("Error: {}", err),
+    }
+}
+
+fn test_log_follow() {
+    use std::io::Write;
+
+    let rules = match log_rules::read_rules("src/resources/log_rules.txt") {
+        Ok(rules) => rules,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    let log = std::fs::read_to_string("src/resources/2022-10-15-10.18.37.log").expect("Failed to read the log");
+    let lines: Vec<String> = log.lines().take(400).map(|line| line.to_string()).collect();
+
+    // A writer thread plays the emulator: it appends lines, then truncates the file, then rotates it
+    let path = std::env::temp_dir().join(format!("follow_test_{}.log", std::process::id()));
+    let rotated_path = path.with_extension("log.1");
+    std::fs::write(&path, "").expect("Failed to create the temporary log");
+    let writer_path = path.clone();
+    let writer_rotated_path = rotated_path.clone();
+    let writer = std::thread::spawn(move || {
+        let append = |lines: &[String]| {
+            let mut file = std::fs::OpenOptions::new().append(true).open(&writer_path).unwrap();
+            for line in lines {
+                writeln!(file, "{}", line).unwrap();
+            }
+        };
+        for chunk in lines[..200].chunks(50) {
+            append(chunk);
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        std::fs::write(&writer_path, "").unwrap();
+        append(&lines[200..300]);
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        std::fs::rename(&writer_path, &writer_rotated_path).unwrap();
+        std::fs::write(&writer_path, "").unwrap();
+        append(&lines[300..]);
+    });
+
+    let settings = log_follow::FollowSettings {
+        poll_interval: std::time::Duration::from_millis(50),
+        idle_timeout: Some(std::time::Duration::from_secs(1)),
+        from_start: true,
+    };
+    let mut printed_matches = 0;
+    let path_text = path.to_string_lossy().to_string();
+    println!("Following {}", path_text);
+    let result = log_follow::follow_log_file(&path_text, &rules, &settings, &mut |event| match event {
+        log_follow::FollowEvent::Match { rule, line: logcat::LogLine::Entry(entry) } if printed_matches < 10 => {
+            println!("{}: {}", rule, entry.raw);
+            printed_matches += 1;
+        }
+        log_follow::FollowEvent::Match { .. } => {}
+        log_follow::FollowEvent::Truncated => println!("The file was truncated"),
+        log_follow::FollowEvent::Rotated => println!("The file was rotated"),
+        log_follow::FollowEvent::Updated(counters) => {
+            println!("Lines: {}, entries: {}, levels: {:?}", counters.lines, counters.entries, counters.levels);
+        }
+    });
+    writer.join().expect("The writer thread failed");
+
+    match result {
+        Ok(counters) => {
+            println!("Stopped after 1 second without new lines");
+            println!("Lines: {}, entries: {}, truncations: {}, rotations: {}",
+                     counters.lines, counters.entries, counters.truncations, counters.rotations);
+            for (name, count) in counters.rule_counts {
+                println!("{}: {}", name, count);
+            }
+        }
+        Err(err) => println!("Error: {}", err),
+    }
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&rotated_path);
+}
+
+fn test_incidents() {
+    let filename = "src/resources/sample_incidents.log";
+    let log = match logcat::read_log_file(filename) {
+        Ok(log) => log,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    let mut detector = log_incidents::IncidentDetector::new();
+    for log_line in log {
+        match log_line {
+            Ok(logcat::LogLine::Entry(entry)) => detector.push(&entry),
+            Ok(logcat::LogLine::Unparsed { .. }) => {}
+            Err(err) => {
+                println!("Error: {}", err);
+                return;
+            }
+        }
+    }
+    for incident in detector.finish() {
+        println!("{}", incident);
+        println!();
+    }
+}
+
+fn test_log_redaction() {
+    let input = "src/resources/2022-10-15-10.18.37.log";
+    let output = "redacted.log";
+    let rules = match log_redact::read_redaction_rules("src/resources/redaction_rules.txt") {
+        Ok(rules) => rules,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    let mut redactor = log_redact::Redactor::new(rules);
+    match log_redact::redact_log_file(input, output, &mut redactor) {
+        Ok(changed) => println!("{} lines were redacted, the copy was saved to {}", changed, output),
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    }
+    for (name, replaced, distinct) in redactor.counts() {
+        println!("{}: {} values replaced, {} distinct", name, replaced, distinct);
+    }
+
+    // The redacted copy has the same structure, so it can be analyzed the same way
+    let original = std::fs::read_to_string(input).expect("Failed to read the log");
+    let redacted = std::fs::read_to_string(output).expect("Failed to read the redacted log");
+    let examples = original.lines().zip(redacted.lines()).filter(|(before, after)| before != after).take(5);
+    for (before, after) in examples {
+        println!("- {}", before);
+        println!("+ {}", after);
+    }
+    let count_entries = |path: &str| logcat::read_log_file(path)
+        .map(|log| log.filter(|log_line| matches!(log_line, Ok(logcat::LogLine::Entry(_)))).count())
+        .unwrap_or(0);
+    println!("Entries in the original: {}, in the redacted copy: {}", count_entries(input), count_entries(output));
+}
+
+fn test_log_index() {
+    let log_file = "src/resources/2022-10-15-10.18.37.log";
+    let started = std::time::Instant::now();
+    let (index, built) = match log_index::LogIndex::open(log_file) {
+        Ok(opened) => opened,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    println!("The index of {} entries was {} in {:?}", index.entries.len(),
+             if built { "built and saved" } else { "loaded" }, started.elapsed());
+
+    let queries = [
+        ("level>=E", log_index::IndexQuery { min_level: Some(logcat::LogLevel::Error), ..Default::default() }),
+        ("tag=ActivityManager", log_index::IndexQuery { tag: Some("ActivityManager".to_string()), ..Default::default() }),
+        ("pid=1370 and level>=W", log_index::IndexQuery { pid: Some(1370), min_level: Some(logcat::LogLevel::Warning), ..Default::default() }),
+        ("time in 10-15 10:18:51..10-15 10:19:00", log_index::IndexQuery {
+            from: Some("10-15 10:18:51".to_string()), to: Some("10-15 10:19:00".to_string()), ..Default::default()
+        }),
+        ("words: failed connect", log_index::IndexQuery { tokens: vec!["failed".to_string(), "connect".to_string()], ..Default::default() }),
+    ];
+    let mut file = std::fs::File::open(log_file).expect("Failed to open the log");
+    for (name, query) in &queries {
+        let started = std::time::Instant::now();
+        let ids = index.query(query);
+        let index_time = started.elapsed();
+
+        // The same query by reading the whole log, to compare the results and the time
+        let started = std::time::Instant::now();
+        let scanned = logcat::read_log_file(log_file).expect("Failed to read the log")
+            .filter(|log_line| matches!(log_line, Ok(logcat::LogLine::Entry(entry)) if query.matches(entry)))
+            .count();
+        let scan_time = started.elapsed();
+
+        println!("{}: {} entries in {:?} with the index, {} entries in {:?} by scanning", name, ids.len(), index_time, scanned, scan_time);
+        for id in ids.iter().take(2) {
+            println!("    {}", index.read_entry(&mut file, *id).expect("Failed to read the entry"));
+        }
+    }
+}
+
+fn test_log_automata() {
+    let log_file = "src/resources/2022-10-15-10.18.37.log";
+
+    // The patterns of the counting and redaction rules, and a few that use the rest of the syntax
+    let mut patterns: Vec<String> = Vec::new();
+    match log_rules::read_rules("src/resources/log_rules.txt") {
+        Ok(rules) => patterns.extend(rules.rules.into_iter().map(|rule| rule.pattern).filter(|pattern| !pattern.is_empty())),
+        Err(err) => println!("Error: {}", err),
+    }
+    match log_redact::read_redaction_rules("src/resources/redaction_rules.txt") {
+        Ok(rules) => patterns.extend(rules.into_iter().map(|rule| rule.pattern.as_str().to_string())),
+        Err(err) => println!("Error: {}", err),
+    }
+    patterns.extend([
+        r"(?:Activity|Window)Manager: (Start|Displayed) ",
+        r"\b\d{3,5}\b.*\bE\b",
+        r"[^\x00-\x7F]",
+        r"\.java:\d+\)$",
+        r"^.{18}\s+\d+\s+\d+ [VDIWEF] ",
+        r"é|ü|ő",
+    ].iter().map(|pattern| pattern.to_string()));
+    let patterns: Vec<&str> = patterns.iter().map(|pattern| pattern.as_str()).collect();
+
+    // A small pattern, to show the automata it is compiled into
+    match log_automata::DfaPattern::new(r"\bE\b", log_automata::Alphabet::Char) {
+        Ok(pattern) => pattern.to_automata().print_automata(),
+        Err(err) => println!("Error: {}", err),
+    }
+
+    for alphabet in [log_automata::Alphabet::Char, log_automata::Alphabet::Byte] {
+        println!("Automata over {:?}:", alphabet);
+        let started = std::time::Instant::now();
+        let comparison = match log_automata::compare_engines(log_file, &patterns, alphabet) {
+            Ok(comparison) => comparison,
+            Err(err) => {
+                println!("Error: {}", err);
+                continue;
+            }
+        };
+        println!("Matched {} lines with {} patterns in {:?}", comparison.lines, patterns.len(), started.elapsed());
+        for (index, pattern) in patterns.iter().enumerate() {
+            match &comparison.compiled[index] {
+                Ok(states) => println!("{} states, {} / {} matching lines (regex / automata): {}",
+                                       states, comparison.regex_matches[index], comparison.automata_matches[index], pattern),
+                Err(err) => println!("not compiled {}: {}", err, pattern),
+            }
+        }
+        println!("Lines where the engines disagree: {}", comparison.disagreements.len());
+        for disagreement in comparison.disagreements.iter().take(5) {
+            println!("    line {} (regex: {}) {}: {}", disagreement.line_number, disagreement.regex,
+                     patterns[disagreement.pattern], disagreement.text);
+        }
+        println!();
+    }
+}
+
+fn run_diff(before: &str, after: &str) {
+    let normalizer = log_diff::Normalizer::new();
+    let counts = log_diff::count_templates(before, &normalizer)
+        .and_then(|before| Ok((before, log_diff::count_templates(after, &normalizer)?)));
+    let (before_counts, after_counts) = match counts {
+        Ok(counts) => counts,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    println!("{}: {} entries, {} templates", before, before_counts.total, before_counts.counts.len());
+    println!("{}: {} entries, {} templates", after, after_counts.total, after_counts.counts.len());
+    let changes = log_diff::diff_templates(&before_counts, &after_counts, &log_diff::DiffSettings::default());
+    log_diff::write_changes(&mut std::io::stdout(), &changes, 15).expect("Failed to write the changes");
+}
+
+fn test_log_diff() {
+    let read_path = |prompt: &str, default: &str| -> String {
+        println!("{} [{}]:", prompt, default);
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).expect("Failed to read line");
+        if input.trim().is_empty() { default.to_string() } else { input.trim().to_string() }
+    };
+    let before = read_path("First log", "src/resources/2022-10-15-10.18.37.log");
+    let after = read_path("Second log", "src/resources/sample_incidents.log");
+    run_diff(&before, &after);
+}
+
+fn test_log_templates() {
+    let log_file = "src/resources/2022-10-15-10.18.37.log";
+    let started = std::time::Instant::now();
+    let (miner, entries) = match log_templates::mine_templates(log_file, log_templates::DrainSettings::default()) {
+        Ok(mined) => mined,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    let templates = miner.templates();
+    println!("{} entries were grouped into {} templates in {:?}", entries, templates.len(), started.elapsed());
+    let single = templates.iter().filter(|template| template.count == 1).count();
+    println!("Templates with a single entry: {}", single);
+    let mut covered = 0;
+    let common = templates.iter().take_while(|template| {
+        let needed = covered * 10 < entries * 9;
+        covered += template.count;
+        needed
+    }).count();
+    println!("The {} most frequent templates cover 90% of the entries", common);
+    log_templates::write_templates(&mut std::io::stdout(), &templates, 20).expect("Failed to write the templates");
+}
+
+fn test_log_sessions() {
+    for filename in ["src/resources/sample_reboot.log", "src/resources/2022-10-15-10.18.37.log"] {
+        let log = match logcat::read_log_file(filename) {
+            Ok(log) => log,
+            Err(err) => {
+                println!("Error: {}", err);
+                continue;
+            }
+        };
+        println!("{}:", filename);
+        let mut tracker = log_sessions::SessionTracker::new();
+        let mut names = log_processes::ProcessNames::new();
+        let mut detector = log_incidents::IncidentDetector::new();
+        for log_line in log {
+            let log_line = match log_line {
+                Ok(log_line) => log_line,
+                Err(err) => {
+                    println!("Error: {}", err);
+                    break;
+                }
+            };
+            if let logcat::LogLine::Entry(entry) = &log_line {
+                names.add(entry);
+                detector.push(entry);
+            }
+            tracker.push(&log_line);
+        }
+        let sessions = tracker.finish(&detector.finish(), &names);
+        sessions.write_tables(&mut std::io::stdout()).expect("Failed to write the sessions");
+        println!();
+    }
+}
+
+fn test_log_lint() {
+    let rules = match log_rules::read_rules("src/resources/log_rules.txt") {
+        Ok(rules) => rules,
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+    match log_lint::lint_rules(&rules, "src/resources/2022-10-15-10.18.37.log") {
+        Ok(lints) => log_lint::write_lints(&mut std::io::stdout(), &lints).expect("Failed to write the lints"),
+        Err(err) => println!("Error: {}", err),
+    }
+
+    // A few patterns that use the rest of the syntax
+    for pattern in [r"(?:Activity|Window)Manager: (Start|Displayed) ", r"^.{18}\s+\d+\s+\d+ [VDIWEF] ", r"[^\x00-\x7F]", r"(?i)fatal", r"(?=lookahead)"] {
+        match log_lint::explain_pattern(pattern) {
+            Ok(explanation) => println!("{:?} matches {}", pattern, explanation),
+            Err(err) => println!("{:?} can't be explained {}", pattern, err),
+        }
+    }
+}
+
 fn main() {
+    // Command line usage: formalis_nyelvek filter <log file> <expression>
+    //                     formalis_nyelvek diff <first log file> <second log file>
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 4 && args[1] == "filter" {
+        run_filter(&args[2], &args[3]);
+        return;
+    }
+    if args.len() == 4 && args[1] == "diff" {
+        run_diff(&args[2], &args[3]);
+        return;
+    }
+
     loop {
-        println!("Which excercise would you like to run? [0(exit), 1 (dfa minimization), 2 (equivalence test), 3 (regex), 4 (stack-automata)]:");
+        println!("Which excercise would you like to run? [0(exit), 1 (dfa minimization), 2 (equivalence test), 3 (regex), 4 (stack-automata), 5 (dpda check), 6 (pda to grammar), 7 (grammar validation), 8 (cnf and cyk), 9 (grammar to pda), 10 (log filter), 11 (log formats), 12 (log merge), 13 (log follow), 14 (incidents), 15 (log redaction), 16 (log diff), 17 (log index), 18 (log automata), 19 (log templates), 20 (log sessions), 21 (log rule lint)]:");
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).expect("Failed to read line");
         let input: u32 = match input.trim().parse() {
@@ -92,6 +739,74 @@ fn main() {
                 test_stack_automata();
 
             },
+            5 => {
+                println!("5. Testing the deterministic stack automata check");
+                test_deterministic_stack_automata();
+            },
+            6 => {
+                println!("6. Testing the stack automata to grammar conversion");
+                test_stack_automata_to_grammar();
+            },
+            7 => {
+                println!("7. Testing the grammar validation");
+                test_grammar_validation();
+            },
+            8 => {
+                println!("8. Testing the Chomsky normal form and CYK");
+                test_chomsky_normal_form();
+            },
+            9 => {
+                println!("9. Testing the grammar to stack automata construction");
+                test_grammar_to_stack_automata();
+            },
+            10 => {
+                println!("10. Testing the log filter expressions");
+                test_log_filter();
+            },
+            11 => {
+                println!("11. Testing the logcat format detection");
+                test_log_formats();
+            },
+            12 => {
+                println!("12. Testing the chronological merge of log files");
+                test_log_merge();
+            },
+            13 => {
+                println!("13. Testing the follow mode on a growing log file");
+                test_log_follow();
+            },
+            14 => {
+                println!("14. Testing the incident detector");
+                test_incidents();
+            },
+            15 => {
+                println!("15. Testing the log redaction");
+                test_log_redaction();
+            },
+            16 => {
+                println!("16. Testing the comparison of two logs by message templates");
+                test_log_diff();
+            },
+            17 => {
+                println!("17. Testing the log index");
+                test_log_index();
+            },
+            18 => {
+                println!("18. Testing the log patterns with the finite automata");
+                test_log_automata();
+            },
+            19 => {
+                println!("19. Testing the template mining of the log messages");
+                test_log_templates();
+            },
+            20 => {
+                println!("20. Testing the session segmentation");
+                test_log_sessions();
+            },
+            21 => {
+                println!("21. Testing the rule linter");
+                test_log_lint();
+            },
             _ => {
                 println!("Invalid input, please try again");
                 continue;
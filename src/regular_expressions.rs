@@ -1,183 +1,222 @@
-use regex::Regex;
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufRead, BufReader, Write};
-
-pub fn process_log_file(file_path: &str) -> io::Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-
-    // Read the content of the log file into a String
-    let logcat: String = reader.lines().collect::<Result<Vec<_>, _>>()?.join("\n");
+use std::io;
+
+use crate::log_automata::Alphabet;
+use crate::log_error::LogAnalysisError;
+use crate::log_filter::Filter;
+use crate::log_incidents::IncidentDetector;
+use crate::log_processes::ProcessNames;
+use crate::log_report::LogReport;
+use crate::log_rules;
+use crate::log_sessions::SessionTracker;
+use crate::log_stats::LogStatistics;
+use crate::log_stack_traces::{self, StackTraceCollector};
+use crate::log_timeline::{self, BucketSize, BurstSettings, Timeline};
+use crate::logcat::{self, LogLine};
+
+/**
+    Function to analyze a log file
+    * @param file_path The log file to analyze
+    * @param rules_path The file with the counting rules, see log_rules::read_rules
+    * @param bucket_size The length of the time buckets of the histograms
+    * @param automata If set, the patterns are matched with this crate's automata over this alphabet
+    * @returns The report of the analysis, to be printed or saved with log_report::save_report; the tasks that failed are in its
+    * errors. An error if the log can't be read or has no entries, there is nothing to report then
+ **/
+pub fn process_log_file(file_path: &str, rules_path: &str, bucket_size: BucketSize, automata: Option<Alphabet>) -> Result<LogReport, LogAnalysisError> {
+    let log = logcat::read_log_file(file_path).map_err(LogAnalysisError::io(file_path))?;
+
+    // The problems of the tasks that failed, the other tasks are still reported
+    let mut errors = Vec::new();
 
     // I.
-    // Task 1
-    let pattern1 = Regex::new(r"^10-15").unwrap();
-    let mut count10_15 = 0;
-
-    // Task 2
-    let pattern2 = Regex::new(r"^10-15 10:18:51").unwrap();
-    let mut count10_15_10_18_51 = 0;
-
-    // Task 3
-    let pattern3 = Regex::new(r"^[0-9-]+ [0-9:]+:51").unwrap();
-    let mut count51 = 0;
-
-    // Task 4
-    let pattern4 = Regex::new(r"lowmemorykiller").unwrap();
-    let mut count_low_memory_killer = 0;
-
-    // Task 5
-    let pattern5 = Regex::new(r"^10-15 10:18:51.* 221 ").unwrap();
-    let mut count10_18_51_221 = 0;
-
-    // Task 6
-    let pattern6 = Regex::new(r"\bE\b").unwrap();
-    let mut count_e = 0;
-
-    // Task 7
-    let pattern7 = Regex::new(r".* W PackageManager.* ").unwrap();
-    let mut count_package_manager_w = 0;
+    // Counting the lines matching each rule, skipped if the rules can't be read
+    let mut rules = match log_rules::read_rules(rules_path) {
+        Ok(rules) => Some(rules),
+        Err(err) => {
+            errors.push(err);
+            None
+        }
+    };
+    let automata_fallbacks = match (&mut rules, automata) {
+        (Some(rules), Some(alphabet)) => rules.use_automata(alphabet),
+        _ => Vec::new(),
+    };
+    let mut counts = vec![0; rules.as_ref().map_or(0, |rules| rules.rules.len())];
 
-    // Task 8
-    let pattern8 = Regex::new(r".* D .*ExoPlayer.* ").unwrap();
-    let mut count_exo_player_d = 0;
+    // II.
+    // Task 1
+    let mut stack_trace_collector = StackTraceCollector::new();
 
-    // Task 9
-    let pattern9 = Regex::new(r".* [EW].*\.java:").unwrap();
-    let mut count_ew_java = 0;
+    // Task 2 and 3
+    let mut statistics = LogStatistics::new();
 
-    // Task 10
-    let pattern10 = Regex::new(r".* [^:]+: .*Thread.*").unwrap();
-    let mut count_thread = 0;
+    // Histograms by time bucket, instead of counting the lines of one second
+    let mut timeline = Timeline::new(bucket_size);
 
-    // Counting
-    for line in logcat.lines() {
-        if pattern1.is_match(line) {
-            count10_15 += 1;
-        }
+    // The names of the processes, from the ActivityManager and Zygote events
+    let mut process_names = ProcessNames::new();
 
-        if pattern2.is_match(line) {
-            count10_15_10_18_51 += 1;
-        }
+    // Task 4
+    // Errors on the main thread, fatal exceptions, ANRs and native crashes
+    let mut incident_detector = IncidentDetector::new();
+
+    // The device boots and app lifecycles, each with its own summary
+    let mut session_tracker = SessionTracker::new();
+
+    // Lines that are not logcat entries, e.g. --------- beginning of main
+    let mut unparsed_lines = Vec::new();
+
+    // Everything is computed in one pass over the parsed log; if a line can't be read, the lines before it are
+    // still analyzed
+    let mut entries = 0;
+    let mut read_error = None;
+    for log_line in log {
+        let log_line = match log_line {
+            Ok(log_line) => log_line,
+            Err(err) => {
+                read_error = Some(LogAnalysisError::Io { path: file_path.to_string(), error: err });
+                break;
+            }
+        };
 
-        if pattern3.is_match(line) {
-            count51 += 1;
+        // Counting
+        if let Some(rules) = &rules {
+            for index in rules.matching_rules(&log_line) {
+                counts[index] += 1;
+            }
         }
-
-        if pattern4.is_match(line) {
-            count_low_memory_killer += 1;
+        session_tracker.push(&log_line);
+
+        match log_line {
+            LogLine::Entry(entry) => {
+                entries += 1;
+                stack_trace_collector.push(&entry);
+                statistics.add(&entry);
+                timeline.add(&entry);
+                process_names.add(&entry);
+                incident_detector.push(&entry);
+            }
+            LogLine::Unparsed { line_number, .. } => {
+                unparsed_lines.push(line_number);
+            }
         }
+    }
 
-        if pattern5.is_match(line) {
-            count10_18_51_221 += 1;
-        }
+    if entries == 0 {
+        return Err(read_error.unwrap_or(LogAnalysisError::EmptyInput { path: file_path.to_string() }));
+    }
+    errors.extend(read_error);
 
-        if pattern6.is_match(line) {
-            count_e += 1;
-        }
+    // Task 1
+    // Identical crashes are counted once, by the fingerprint of their stack trace
+    let stack_traces = stack_trace_collector.finish();
+    let stack_trace_count = stack_traces.len();
+    let crash_groups = log_stack_traces::group_by_fingerprint(stack_traces);
 
-        if pattern7.is_match(line) {
-            count_package_manager_w += 1;
-        }
+    // Task 2
+    let unique_process_ids = statistics.process_ids();
 
-        if pattern8.is_match(line) {
-            count_exo_player_d += 1;
-        }
+    // Task 3
+    let most_common_error_process = statistics.top_error_processes(1).first().map(|(pid, _)| *pid);
 
-        if pattern9.is_match(line) {
-            count_ew_java += 1;
-        }
+    // Task 4
+    // The incidents that don't name their process get the name resolved from its pid
+    let mut incidents = incident_detector.finish();
+    for incident in incidents.iter_mut().filter(|incident| incident.process.is_none()) {
+        incident.process = incident.pid.and_then(|pid| process_names.name_at(pid, &incident.timestamp())).map(|name| name.to_string());
+    }
 
-        if pattern10.is_match(line) {
-            count_thread += 1;
+    let bursts = log_timeline::detect_bursts(&timeline, &BurstSettings::default());
+    let sessions = session_tracker.finish(&incidents, &process_names);
+
+    let report = LogReport {
+        log_file: file_path.to_string(),
+        errors,
+        automata_fallbacks,
+        rule_counts: rules.iter().flat_map(|rules| &rules.rules).map(|rule| rule.name.clone()).zip(counts).collect(),
+        unparsed_lines,
+        stack_trace_count,
+        crash_groups,
+        unique_process_ids,
+        most_common_error_process,
+        incidents,
+        statistics,
+        timeline,
+        bursts,
+        process_names,
+        sessions,
+    };
+    Ok(report)
+}
+
+/**
+    Function to print the entries of a log file that match a filter
+    * @param file_path The log file
+    * @param filter The parsed filter expression, see log_filter::parse_filter
+    * @returns The number of matching entries
+ **/
+pub fn filter_log_file(file_path: &str, filter: &Filter) -> io::Result<usize> {
+    let mut count = 0;
+    for log_line in logcat::read_log_file(file_path)? {
+        if let LogLine::Entry(entry) = log_line? {
+            if filter.matches(&entry) {
+                println!("{}", entry.raw);
+                count += 1;
+            }
         }
     }
+    Ok(count)
+}
 
-    // Writing the results to the screen
-    println!("Number of lines made on 10-15: {}", count10_15);
-    println!("Number of lines made on 10-15 10:18:51: {}", count10_15_10_18_51);
-    println!("Number of lines made on 51 seconds: {}", count51);
-    println!("Number of lines containing lowmemorykiller: {}", count_low_memory_killer);
-    println!("Number of lines made on 10-15 10:18:51 221: {}", count10_18_51_221);
-    println!("Number of lines containing E: {}", count_e);
-    println!("Number of lines containing PackageManager W: {}", count_package_manager_w);
-    println!("Number of lines containing ExoPlayer D: {}", count_exo_player_d);
-    println!("Number of lines containing E or W and .java: {}", count_ew_java);
-    println!("Number of lines containing Thread: {}", count_thread);
-
-    // Writing the results to the processed.txt file
-    let mut processed_file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open("processed.txt")?;
-    writeln!(processed_file, "Number of lines made on 10-15: {}", count10_15)?;
-    writeln!(processed_file, "Number of lines made on 10-15 10:18:51: {}", count10_15_10_18_51)?;
-    writeln!(processed_file, "Number of lines made on 51 seconds: {}", count51)?;
-    writeln!(processed_file, "Number of lines containing lowmemorykiller: {}", count_low_memory_killer)?;
-    writeln!(processed_file, "Number of lines made on 10-15 10:18:51 221: {}", count10_18_51_221)?;
-    writeln!(processed_file, "Number of lines containing E: {}", count_e)?;
-    writeln!(processed_file, "Number of lines containing PackageManager W: {}", count_package_manager_w)?;
-    writeln!(processed_file, "Number of lines containing ExoPlayer D: {}", count_exo_player_d)?;
-    writeln!(processed_file, "Number of lines containing E or W and .java: {}", count_ew_java)?;
-    writeln!(processed_file, "Number of lines containing Thread: {}", count_thread)?;
-
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // II.
-    let pattern_ex = Regex::new(r"([0-9-]+ [0-9:.]+) +([0-9]+) +([0-9]+) ([IDVEFAW]) ([^:]+): (.*)").unwrap();
+    const RULES: &str = "src/resources/log_rules.txt";
 
-    // Task 1
-    let mut stack_traces = Vec::new();
-    let mut current_stack_trace = String::new();
-    let mut inside_stack_trace = false;
-
-    for line in logcat.lines() {
-        if let Some(captures) = pattern_ex.captures(line) {
-            if captures[4].eq("E") {
-                inside_stack_trace = true;
-                current_stack_trace.push_str(&format!("{}:\n→ {}\n", captures[6].trim(), captures[6].trim()));
-            }
-        } else if inside_stack_trace && !line.starts_with(' ') {
-            inside_stack_trace = false;
-            stack_traces.push(current_stack_trace.clone());
-            current_stack_trace.clear();
-        }
+    fn temp_file(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
     }
 
-    for stack_trace in stack_traces {
-        println!("{}", stack_trace);
-        writeln!(processed_file, "{}", stack_trace)?;
+    fn entries(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|second| format!("10-15 10:18:{:02}.000  1234  1234 E Tag: error {}\n", second, second).into_bytes())
+            .collect()
     }
 
-    // Task 2
-    let unique_process_ids: Vec<_> = pattern_ex.captures_iter(logcat.as_str())
-        .map(|cap| cap[3].to_string())
-        .collect();
-    let unique_process_ids: Vec<_> = unique_process_ids.into_iter().collect();
-    println!("Unique process ids: {:?}", unique_process_ids);
-    writeln!(processed_file, "Unique process ids: {:?}", unique_process_ids)?;
+    #[test]
+    fn invalid_rules_leave_the_other_tasks_reported() {
+        let log = temp_file("invalid_rules.log", &entries(3));
+        let rules = temp_file("invalid_rules.txt", b"[Errors]\npattern = (\n");
+        let report = process_log_file(&log, &rules, BucketSize::Second, None).unwrap();
+        assert!(matches!(report.errors.as_slice(), [LogAnalysisError::InvalidPattern { rule, .. }] if rule == "Errors"));
+        assert!(report.rule_counts.is_empty());
+        assert_eq!(report.statistics.total, 3);
+        assert_eq!(report.unique_process_ids, [1234]);
+        std::fs::remove_file(log).unwrap();
+        std::fs::remove_file(rules).unwrap();
+    }
 
-    // Task 3
-    let error_processes: Vec<_> = pattern_ex.captures_iter(logcat.as_str())
-        .filter(|cap| cap[4].eq("E"))
-        .map(|cap| cap[3].to_string())
-        .collect();
-    let most_common_error_process = error_processes.iter()
-        .max_by(|&x, &y| error_processes.iter().filter(|&e| e.clone() == x.clone()).count()
-            .cmp(&error_processes.iter().filter(|&e| e.clone() == y.clone()).count()))
-        .unwrap();
-    println!("Most common error process: {}", most_common_error_process);
-    writeln!(processed_file, "Most common error process: {}", most_common_error_process)?;
+    #[test]
+    fn lines_before_an_unreadable_line_are_reported() {
+        // The invalid UTF-8 comes after the lines read for the format detection
+        let mut contents = entries(25);
+        contents.extend(b"10-15 10:18:30.000  1234  1234 E Tag: \xff\n");
+        contents.extend(entries(2));
+        let log = temp_file("unreadable_line.log", &contents);
+        let report = process_log_file(&log, RULES, BucketSize::Second, None).unwrap();
+        assert!(matches!(report.errors.as_slice(), [LogAnalysisError::Io { path, .. }] if *path == log));
+        assert_eq!(report.statistics.total, 25);
+        std::fs::remove_file(log).unwrap();
+    }
 
-    // Task 4
-    let main_thread_errors: Vec<_> = pattern_ex.captures_iter(logcat.as_str())
-        .filter(|cap| cap[3] == cap[4] && cap[4].eq( "E"))
-        .map(|cap| format!("{}: {}", cap[3].to_string(), cap[0].to_string()))
-        .collect();
-    println!("Main thread errors:");
-    writeln!(processed_file, "Main thread errors:")?;
-    for main_thread_error in main_thread_errors {
-        println!("{}", main_thread_error);
-        writeln!(processed_file, "{}", main_thread_error)?;
+    #[test]
+    fn a_log_without_entries_is_an_error() {
+        let log = temp_file("no_entries.log", b"--------- beginning of main\n");
+        assert!(matches!(process_log_file(&log, RULES, BucketSize::Second, None), Err(LogAnalysisError::EmptyInput { .. })));
+        std::fs::remove_file(&log).unwrap();
+        assert!(matches!(process_log_file(&log, RULES, BucketSize::Second, None), Err(LogAnalysisError::Io { .. })));
     }
-    Ok(())
-}
\ No newline at end of file
+}
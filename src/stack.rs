@@ -1,7 +1,71 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead};
+use std::io::{BufRead, Write};
+
+use crate::grammar::ContextFreeGrammar;
+
+/**
+    Struct to represent one transition of the stack automata
+    * from_state, input_symbol, stack_symbol, to_state As written in the automata file, E is the empty word
+    * pushed The symbols that replace the stack symbol, bottom to top, empty if it is only popped
+    * line The line of the transition in the automata file, None if the automata was built in code
+ **/
+#[derive(Debug, Clone)]
+struct Transition {
+    from_state: String,
+    input_symbol: String,
+    stack_symbol: String,
+    pushed: Vec<String>,
+    to_state: String,
+    line: Option<usize>,
+}
+
+// The final states line of an automata file that accepts by empty stack
+const EMPTY_STACK: &str = "empty_stack";
+
+impl Transition {
+    // The pushed symbols as they are written in the automata file, E if there are none
+    fn written_stack_symbols(&self) -> String {
+        if self.pushed.is_empty() {
+            "E".to_string()
+        } else {
+            self.pushed.concat()
+        }
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {} {}", self.from_state, self.input_symbol, self.stack_symbol, self.written_stack_symbols(), self.to_state)
+    }
+}
+
+/**
+    How a stack automata accepts a word that it read entirely
+    * FinalState The automata is in a final state
+    * EmptyStack The stack is empty, the final states are not used
+ **/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Acceptance {
+    FinalState,
+    EmptyStack,
+}
+
+/**
+    Two transitions that make the stack automata non-deterministic
+    * first, second The indexes of the conflicting transitions
+    * first_line, second_line The lines of the transitions in the automata file, None if it was built in code
+    * reason Why the two transitions are in conflict
+ **/
+pub struct TransitionConflict {
+    pub first: usize,
+    pub second: usize,
+    pub first_line: Option<usize>,
+    pub second_line: Option<usize>,
+    pub reason: &'static str,
+}
 
 pub struct StackAutomata {
     nr_of_states: usize,
@@ -11,164 +75,630 @@ pub struct StackAutomata {
     start_state: String,
     stack_start: String,
     final_states: Vec<String>,
-    transitions: Vec<(String, String, String, String, String)>,
-    stack: VecDeque<String>,
+    acceptance: Acceptance,
+    transitions: Vec<Transition>,
 }
 
 impl StackAutomata {
-    fn new(nr_of_states: usize, states: Vec<String>, alphabet: Vec<String>, stack_alphabet: Vec<String>,
-           start_state: String, stack_start: String, final_states: Vec<String>) -> Self {
+    fn new(states: Vec<String>, alphabet: Vec<String>, stack_alphabet: Vec<String>,
+           start_state: String, stack_start: String, final_states: Vec<String>, acceptance: Acceptance) -> Self {
         StackAutomata {
-            nr_of_states,
+            nr_of_states: states.len(),
             states,
             alphabet,
             stack_alphabet,
             start_state,
             stack_start,
             final_states,
+            acceptance,
             transitions: Vec::new(),
-            stack: VecDeque::new(),
         }
     }
 
     fn add_transition(&mut self, from_state: String, input_symbol: String,
-                      stack_symbol: String, new_stack_symbols: String, to_state: String) {
-        self.transitions.push((from_state, input_symbol, stack_symbol, new_stack_symbols, to_state));
+                      stack_symbol: String, pushed: Vec<String>, to_state: String, line: Option<usize>) {
+        self.transitions.push(Transition { from_state, input_symbol, stack_symbol, pushed, to_state, line });
     }
 
-    fn add_to_stack(&mut self, new_stack_symbols: &str) {
-        self.stack.push_back(new_stack_symbols.to_string());
+    fn is_final_state(&self, state: &str) -> bool {
+        self.final_states.contains(&state.to_string())
+    }
 
-        // Print the current state of the stack
-        println!("Stack after adding: {:?}", self.stack);
+    fn is_accepting(&self, state: &str, stack: &[String]) -> bool {
+        match self.acceptance {
+            Acceptance::FinalState => self.is_final_state(state),
+            Acceptance::EmptyStack => stack.is_empty(),
+        }
     }
 
-    fn remove_from_stack(&mut self) {
-        // Pop a symbol from the stack
-        if let Some(popped_symbol) = self.stack.pop_back() {
-            // Print the symbol that was removed and the current state of the stack
-            println!("Removed symbol: {}", popped_symbol);
-            println!("Stack after removing: {:?}", self.stack);
+    pub(crate) fn print_automata(&self) {
+        println!("The stack automata:");
+        println!("Number of states: {}", self.nr_of_states);
+        println!("States: {:?}", self.states);
+        println!("Alphabet: {:?}", self.alphabet);
+        println!("Stack alphabet: {:?}", self.stack_alphabet);
+        println!("Start State: {}", self.start_state);
+        println!("Stack start: {}", self.stack_start);
+        match self.acceptance {
+            Acceptance::FinalState => println!("Final States: {:?}", self.final_states),
+            Acceptance::EmptyStack => println!("Accepts by empty stack"),
+        }
+        println!("Transitions:");
+        for transition in &self.transitions {
+            println!("{} --{}--> {} \n {} -> {}", transition.from_state, transition.input_symbol, transition.to_state,
+                     transition.stack_symbol, transition.written_stack_symbols());
         }
     }
 
-    fn is_final_state(&self, state: &str) -> bool {
-        self.final_states.contains(&state.to_string())
+    /**
+        Function to get the line of a transition in the automata file
+        * @param index The index of the transition
+        * @returns The 1-based line number of the transition, None if the automata was built in code
+     **/
+    pub fn transition_line(&self, index: usize) -> Option<usize> {
+        self.transitions[index].line
     }
 
-    fn is_valid_transition(&self, from_state: &str, input_symbol: &str,
-                           stack_state: &str) -> bool {
-        self.transitions.iter().any(|(transition_from_state, transition_input_symbol,
-                                         transition_stack_symbol, _, _)| {
-            transition_from_state == from_state
-                && transition_input_symbol == input_symbol
-                && transition_stack_symbol.starts_with(stack_state)
-        })
+    /**
+        Function to split a written stack string (e.g. z0z1) into stack symbols
+        * The string is written bottom to top, so the last symbol becomes the new top
+        * E stands for the empty string
+        * @param symbols The stack string from a transition
+        * @returns The stack symbols, longest match first
+     **/
+    pub fn split_stack_symbols(&self, symbols: &str) -> Vec<String> {
+        let mut result = Vec::new();
+        if symbols == "E" {
+            return result;
+        }
+
+        let mut rest = symbols;
+        while !rest.is_empty() {
+            let symbol = self.stack_alphabet.iter()
+                .filter(|symbol| !symbol.is_empty() && rest.starts_with(symbol.as_str()))
+                .max_by_key(|symbol| symbol.len())
+                .cloned()
+                .unwrap_or_else(|| rest.chars().next().unwrap().to_string());
+            rest = &rest[symbol.len()..];
+            result.push(symbol);
+        }
+        result
     }
 
-    fn get_transition(&self, from_state: &str, input_symbol: &str, stack_state: &str)
-                      -> Option<(String, String, String, String, String)> {
+    /**
+        Function to collect every pair of transitions that breaks determinism
+        * Two transitions conflict if they start from the same state with the same stack top and
+        * either read the same input symbol, or one of them is an epsilon transition
+        * @returns The conflicting transition pairs
+     **/
+    pub fn find_conflicts(&self) -> Vec<TransitionConflict> {
+        let mut conflicts = Vec::new();
+        for i in 0..self.transitions.len() {
+            for j in (i + 1)..self.transitions.len() {
+                let first = &self.transitions[i];
+                let second = &self.transitions[j];
+                if first.from_state != second.from_state || first.stack_symbol != second.stack_symbol {
+                    continue;
+                }
+
+                let reason = if first.input_symbol == second.input_symbol {
+                    "same state, input and stack top"
+                } else if first.input_symbol == "E" || second.input_symbol == "E" {
+                    "epsilon transition next to an input transition"
+                } else {
+                    continue;
+                };
+
+                conflicts.push(TransitionConflict {
+                    first: i,
+                    second: j,
+                    first_line: self.transition_line(i),
+                    second_line: self.transition_line(j),
+                    reason,
+                });
+            }
+        }
+        conflicts
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.find_conflicts().is_empty()
+    }
+
+    pub fn print_conflicts(&self) {
+        let conflicts = self.find_conflicts();
+        if conflicts.is_empty() {
+            println!("The stack automata is deterministic (DPDA)");
+            return;
+        }
+
+        println!("The stack automata is not deterministic, conflicting transitions:");
+        for conflict in &conflicts {
+            for (index, line) in [(conflict.first, conflict.first_line), (conflict.second, conflict.second_line)] {
+                match line {
+                    Some(line) => println!("line {}: {}", line, self.transitions[index]),
+                    None => println!("transition {}: {}", index + 1, self.transitions[index]),
+                }
+            }
+            println!("  -> {}", conflict.reason);
+        }
+    }
+
+    // Applies a transition to a stack: pops the top and pushes the written symbols
+    fn apply_to_stack(stack: &mut Vec<String>, pushed: &[String]) {
+        stack.pop();
+        stack.extend(pushed.iter().cloned());
+    }
+
+    fn find_transition(&self, state: &str, input_symbol: &str, stack_top: &str) -> Option<&Transition> {
         self.transitions.iter().find(|transition|
-            transition.0 == from_state && transition.1 == input_symbol && transition.2 == stack_state
-        ).cloned()
+            transition.from_state == state && transition.input_symbol == input_symbol && transition.stack_symbol == stack_top
+        )
+    }
+
+    /**
+        Function to run a deterministic stack automata on a word in linear time
+        * At every step at most one transition can apply, so no configuration search is needed
+        * The word is accepted if it is read entirely and the automata ends in an accepting configuration
+        * @param word The word to process
+        * @returns true if the word is accepted, false otherwise
+     **/
+    pub fn run_deterministic(&self, word: &str) -> bool {
+        let mut state = self.start_state.clone();
+        let mut stack = vec![self.stack_start.clone()];
+        let mut letters = word.chars().map(|letter| letter.to_string()).peekable();
+
+        // The (state, top, height) configurations of the current run of epsilon moves whose stack wasn't
+        // popped below them since. Reaching the same state and top again at the same height or higher means
+        // the moves in between didn't look below the top, so they repeat forever
+        let mut epsilon_run: Vec<(String, String, usize)> = Vec::new();
+
+        loop {
+            if letters.peek().is_none() && self.is_accepting(&state, &stack) {
+                return true;
+            }
+            let Some(top) = stack.last().cloned() else {
+                return false;
+            };
+
+            if let Some(transition) = self.find_transition(&state, "E", &top) {
+                epsilon_run.retain(|(_, _, height)| *height <= stack.len());
+                if epsilon_run.iter().any(|(seen_state, seen_top, _)| *seen_state == state && *seen_top == top) {
+                    return false;
+                }
+                epsilon_run.push((state.clone(), top, stack.len()));
+                state = transition.to_state.clone();
+                Self::apply_to_stack(&mut stack, &transition.pushed);
+                continue;
+            }
+
+            let Some(letter) = letters.next() else {
+                return false;
+            };
+            match self.find_transition(&state, &letter, &top) {
+                Some(transition) => {
+                    epsilon_run.clear();
+                    state = transition.to_state.clone();
+                    Self::apply_to_stack(&mut stack, &transition.pushed);
+                }
+                None => return false,
+            }
+        }
     }
 
-    pub(crate) fn process_word(&mut self, word: &str) -> bool {
-        // Add the start symbol to the stack
-        let stack_start = self.stack_start.clone();
-        self.add_to_stack(&stack_start);
+    /**
+        Function to decide if a word is accepted by searching through the configurations
+        * A configuration is (state, position in the word, stack)
+        * The word is accepted if some configuration read the whole word and is accepting
+        * The stack height is bounded so epsilon transitions that keep pushing cannot loop forever
+        * With empty stack acceptance, configurations that can't empty the stack any more are dropped
+        * @param word The word to process
+        * @returns true if the word is accepted, false otherwise
+     **/
+    pub fn search_configurations(&self, word: &str) -> bool {
+        let letters: Vec<String> = word.chars().map(|letter| letter.to_string()).collect();
+        let max_push = self.transitions.iter()
+            .map(|transition| transition.pushed.len())
+            .max()
+            .unwrap_or(0);
+        let stack_limit = (letters.len() + 1) * max_push.max(1) + self.states.len() * self.stack_alphabet.len() + 1;
 
-        let mut current_state = self.start_state.clone();
-        let mut current_stack_state = stack_start.clone();
+        // With empty stack acceptance, a symbol that has no epsilon transition can only be popped by
+        // reading a letter, so there can't be more of them on the stack than letters left in the word
+        let popped_by_input: HashSet<&String> = self.stack_alphabet.iter()
+            .filter(|symbol| !self.transitions.iter().any(|transition| transition.input_symbol == "E" && &transition.stack_symbol == *symbol))
+            .collect();
 
-        for letter in word.chars() {
-            if let Some(letter_transition) = self.get_transition(&current_state, &letter.to_string(), &current_stack_state) {
-                println!("Current transition: {:?}", letter_transition);
+        let start = (self.start_state.clone(), 0, vec![self.stack_start.clone()]);
+        let mut visited: HashSet<(String, usize, Vec<String>)> = HashSet::new();
+        let mut queue: VecDeque<(String, usize, Vec<String>)> = VecDeque::new();
+        visited.insert(start.clone());
+        queue.push_back(start);
 
-                let (_, _, _, next_stack_state, next_state) = letter_transition;
+        while let Some((state, position, stack)) = queue.pop_front() {
+            if position == letters.len() && self.is_accepting(&state, &stack) {
+                return true;
+            }
+            let Some(top) = stack.last() else {
+                continue;
+            };
 
-                let second_part = if next_stack_state.chars().next().unwrap() != 'E' {
-                    next_stack_state[2..].to_string()
+            for transition in &self.transitions {
+                if transition.from_state != state || &transition.stack_symbol != top {
+                    continue;
+                }
+                let next_position = if transition.input_symbol == "E" {
+                    position
+                } else if position < letters.len() && transition.input_symbol == letters[position] {
+                    position + 1
                 } else {
-                    "E".to_string()
+                    continue;
                 };
 
-                let next_stack_state = second_part;
-
-                if self.is_valid_transition(&current_state, &letter.to_string(), &current_stack_state) {
-                    if next_stack_state == "E" {
-                        self.remove_from_stack();
-                        current_state = next_state;
-                        current_stack_state = self.stack.back().map_or_else(|| stack_start.clone(), |s| s.clone());
-                    } else {
-                        self.add_to_stack(&next_stack_state);
-                        current_state = next_state;
-                        current_stack_state = next_stack_state.clone();
-                    }
-                } else {
-                    return false;
+                let mut next_stack = stack.clone();
+                Self::apply_to_stack(&mut next_stack, &transition.pushed);
+                if next_stack.len() > stack_limit {
+                    continue;
+                }
+                if self.acceptance == Acceptance::EmptyStack
+                    && next_stack.iter().filter(|symbol| popped_by_input.contains(symbol)).count() > letters.len() - next_position {
+                    continue;
+                }
+
+                let next = (transition.to_state.clone(), next_position, next_stack);
+                if visited.insert(next.clone()) {
+                    queue.push_back(next);
                 }
-            } else {
-                return false;
             }
         }
+        false
+    }
 
-        if self.is_final_state(&self.start_state) && self.stack.back().map_or(false, |s| s == &stack_start) {
-            true
+    /**
+        Function to decide if a word is accepted
+        * Uses the linear runner for a DPDA and the configuration search otherwise
+        * @param word The word to process
+        * @returns true if the word is accepted, false otherwise
+     **/
+    pub fn accepts_word(&self, word: &str) -> bool {
+        if self.is_deterministic() {
+            self.run_deterministic(word)
         } else {
-            false
+            self.search_configurations(word)
         }
     }
 
-    pub(crate) fn print_automata(&self) {
-        println!("The stack automata:");
-        println!("Number of states: {}", self.nr_of_states);
-        println!("States: {:?}", self.states);
-        println!("Alphabet: {:?}", self.alphabet);
-        println!("Stack alphabet: {:?}", self.stack_alphabet);
-        println!("Start State: {}", self.start_state);
-        println!("Stack start: {}", self.stack_start);
-        println!("Final States: {:?}", self.final_states);
-        println!("Transitions:");
-        for (from_state, input_symbol, stack_symbol, new_stack_symbols, to_state) in &self.transitions {
-            let second_part = if new_stack_symbols.chars().next().unwrap() != 'E' {
-                new_stack_symbols[2..].to_string()
-            } else {
-                "E".to_string()
-            };
-            println!("{} --{}--> {} \n {} -> {}", from_state, input_symbol, to_state, stack_symbol, second_part);
+    /**
+        Function to convert the stack automata into a context-free grammar
+        * The automata is read as accepting by empty stack
+        * Uses the triple construction: [p,X,q] derives the words that take the automata
+        * from state p to state q while popping X from the top of the stack
+        * The useless nonterminals are removed at the end so the grammar stays readable
+        * @returns The equivalent context-free grammar with start symbol S
+     **/
+    pub fn to_context_free_grammar(&self) -> ContextFreeGrammar {
+        let triple = |from: &str, symbol: &str, to: &str| format!("[{},{},{}]", from, symbol, to);
+
+        let mut nonterminals = vec!["S".to_string()];
+        for from in &self.states {
+            for symbol in &self.stack_alphabet {
+                for to in &self.states {
+                    nonterminals.push(triple(from, symbol, to));
+                }
+            }
+        }
+        let mut grammar = ContextFreeGrammar::new(self.alphabet.clone(), nonterminals, "S".to_string());
+
+        // S -> [q0,Z0,q] for every state q
+        for state in &self.states {
+            grammar.add_production("S", vec![triple(&self.start_state, &self.stack_start, state)]);
+        }
+
+        for Transition { from_state, input_symbol, stack_symbol, pushed, to_state, .. } in &self.transitions {
+            // The pushed symbols are popped from the top, so they are taken in reverse order
+            let pushed: Vec<&String> = pushed.iter().rev().collect();
+            let read: Vec<String> = if input_symbol == "E" { Vec::new() } else { vec![input_symbol.clone()] };
+
+            if pushed.is_empty() {
+                grammar.add_production(&triple(from_state, stack_symbol, to_state), read);
+                continue;
+            }
+
+            // Every sequence of intermediate states q1 .. qk gives one production:
+            // [p,X,qk] -> a [r,Yk,q1] [q1,Yk-1,q2] ... [qk-1,Y1,qk]
+            let mut sequences: Vec<Vec<String>> = vec![Vec::new()];
+            for _ in 0..pushed.len() {
+                sequences = sequences.iter()
+                    .flat_map(|sequence| self.states.iter().map(move |state| {
+                        let mut next = sequence.clone();
+                        next.push(state.clone());
+                        next
+                    }))
+                    .collect();
+            }
+
+            for sequence in sequences {
+                let mut right = read.clone();
+                let mut current = to_state;
+                for (symbol, next) in pushed.iter().zip(&sequence) {
+                    right.push(triple(current, symbol, next));
+                    current = next;
+                }
+                grammar.add_production(&triple(from_state, stack_symbol, current), right);
+            }
+        }
+
+        grammar.remove_useless_symbols();
+        grammar
+    }
+
+    /**
+        Function to build a stack automata from a context-free grammar
+        * Uses the single state top-down construction, accepting by empty stack:
+        * q E A -> α q for every production A -> α (the nonterminal is expanded on the stack)
+        * q a a -> E q for every terminal a (the terminal on top is matched with the input)
+        * The grammar symbols become the stack alphabet and are pushed one by one, so they may have any name,
+        * but a terminal can't be E, it would be read as the empty word
+        * @param grammar The grammar to convert
+        * @returns The equivalent stack automata, or the reason the grammar can't be converted
+     **/
+    pub fn from_grammar(grammar: &ContextFreeGrammar) -> Result<StackAutomata, String> {
+        if grammar.terminals().iter().any(|terminal| terminal == "E") {
+            return Err("the terminal E can't be read, E stands for the empty word".to_string());
+        }
+
+        let state = "q".to_string();
+        let stack_alphabet: Vec<String> = grammar.nonterminals().iter()
+            .chain(grammar.terminals())
+            .cloned()
+            .collect();
+
+        let mut automata = StackAutomata::new(vec![state.clone()], grammar.terminals().to_vec(), stack_alphabet,
+                                              state.clone(), grammar.start_symbol().to_string(), Vec::new(), Acceptance::EmptyStack);
+
+        for (left, right) in grammar.productions() {
+            // The stack is bottom to top, so the first symbol of α has to be the last
+            let pushed: Vec<String> = right.iter().rev().cloned().collect();
+            automata.add_transition(state.clone(), "E".to_string(), left.clone(), pushed, state.clone(), None);
+        }
+        for terminal in grammar.terminals() {
+            automata.add_transition(state.clone(), terminal.clone(), terminal.clone(), Vec::new(), state.clone(), None);
+        }
+
+        Ok(automata)
+    }
+
+    /**
+        Function to write the stack automata to a file, in the format read_automata reads
+        * The pushed symbols are written without separators, so the automata is only written if every
+        * transition reads back the same (e.g. not with stack symbols A and AB, or a pushed symbol E)
+        * @param filename The name of the file to write to
+     **/
+    pub fn write_automata(&self, filename: &str) -> io::Result<()> {
+        if let Some(transition) = self.transitions.iter()
+            .find(|transition| self.split_stack_symbols(&transition.written_stack_symbols()) != transition.pushed) {
+            let message = format!("the stack symbols of the transition {} can't be read back from the file", transition);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        let mut file = File::create(filename)?;
+        writeln!(file, "{}", self.states.join(" "))?;
+        writeln!(file, "{}", self.alphabet.join(" "))?;
+        writeln!(file, "{}", self.stack_alphabet.join(" "))?;
+        writeln!(file, "{}", self.start_state)?;
+        writeln!(file, "{}", self.stack_start)?;
+        match self.acceptance {
+            Acceptance::FinalState => write!(file, "{}", self.final_states.join(" "))?,
+            Acceptance::EmptyStack => write!(file, "{}", EMPTY_STACK)?,
         }
+        for transition in &self.transitions {
+            write!(file, "\n{}", transition)?;
+        }
+        Ok(())
     }
 }
 
+/**
+    Function to read a stack automata from a file
+    * The lines of the file are the following:
+    * states, alphabet, stack alphabet (separated by spaces), start state, stack start symbol,
+    * final states (empty_stack for acceptance by empty stack), then one transition per line:
+    * from_state input_symbol stack_symbol new_stack_symbols to_state
+    * E stands for the empty word, new_stack_symbols are written bottom to top
+ **/
 pub fn read_automata(filename: &str) -> StackAutomata {
     let file = File::open(filename).expect("Unable to open file");
     let reader = io::BufReader::new(file);
 
-    let mut lines = reader.lines().map(|line| line.unwrap());
+    // Every line with its 1-based number, so the transitions know where they were read from
+    let mut lines = reader.lines().map(|line| line.unwrap()).zip(1..);
+    let mut next_line = || lines.next().unwrap().0;
 
     // Read the states and alphabet
-    let states_line = lines.next().unwrap();
+    let states_line = next_line();
     let states: Vec<String> = states_line.split_whitespace().map(|s| s.to_string()).collect();
-    let alphabet: Vec<String> = lines.next().unwrap().split_whitespace().map(|s| s.to_string()).collect();
-    let stack_alphabet: Vec<String> = lines.next().unwrap().split_whitespace().map(|s| s.to_string()).collect();
-
-    let nr_of_states = states.len();
-    let start_state = lines.next().unwrap();
-    let stack_start = lines.next().unwrap();
-    let final_states: Vec<String> = lines.next().unwrap().split_whitespace().map(|s| s.to_string()).collect();
-    let transitions: Vec<(String, String, String, String, String)> = lines.map(|line| {
-        let parts: Vec<_> = line.split_whitespace().map(|s| s.to_string()).collect();
-        (parts[0].clone(), parts[1].clone(), parts[2].clone(), parts[3].clone(), parts[4].clone())
-    }).collect();
+    let alphabet: Vec<String> = next_line().split_whitespace().map(|s| s.to_string()).collect();
+    let stack_alphabet: Vec<String> = next_line().split_whitespace().map(|s| s.to_string()).collect();
 
-    let mut stack_automaton = StackAutomata::new(nr_of_states, states, alphabet, stack_alphabet, start_state, stack_start, final_states);
+    let start_state = next_line();
+    let stack_start = next_line();
+    let final_states_line = next_line();
+    let (final_states, acceptance) = if final_states_line.trim() == EMPTY_STACK {
+        (Vec::new(), Acceptance::EmptyStack)
+    } else {
+        (final_states_line.split_whitespace().map(|s| s.to_string()).collect(), Acceptance::FinalState)
+    };
+    let mut stack_automaton = StackAutomata::new(states, alphabet, stack_alphabet, start_state, stack_start, final_states, acceptance);
 
-    for transition in transitions {
+    for (line, number) in lines {
+        let parts: Vec<_> = line.split_whitespace().map(|s| s.to_string()).collect();
+        if parts.is_empty() {
+            continue;
+        }
+        let pushed = stack_automaton.split_stack_symbols(&parts[3]);
         stack_automaton.add_transition(
-            transition.0.clone(), transition.1.clone(), transition.2.clone(), transition.3.clone(), transition.4.clone()
+            parts[0].clone(), parts[1].clone(), parts[2].clone(), pushed, parts[4].clone(), Some(number)
         );
     }
 
     stack_automaton
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar::read_grammar;
+
+    fn automata(states: &[&str], alphabet: &[&str], stack_alphabet: &[&str], final_states: &[&str], transitions: &[&str]) -> StackAutomata {
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<String>>();
+        let mut automata = StackAutomata::new(strings(states), strings(alphabet), strings(stack_alphabet),
+                                              states[0].to_string(), stack_alphabet[0].to_string(), strings(final_states), Acceptance::FinalState);
+        for transition in transitions {
+            let parts = strings(&transition.split_whitespace().collect::<Vec<&str>>());
+            let pushed = automata.split_stack_symbols(&parts[3]);
+            automata.add_transition(parts[0].clone(), parts[1].clone(), parts[2].clone(), pushed, parts[4].clone(), None);
+        }
+        automata
+    }
+
+    #[test]
+    fn epsilon_loop_that_pushes_stops() {
+        let automata = automata(&["q0", "q1"], &["a"], &["z0"], &["q1"], &["q0 E z0 z0z0 q0"]);
+        assert!(automata.is_deterministic());
+        assert!(!automata.accepts_word(""));
+        assert!(!automata.accepts_word("a"));
+    }
+
+    #[test]
+    fn epsilon_moves_that_pop_the_stack_still_accept() {
+        // a^n b^n, the bottom of the stack is popped with an epsilon move after the last b
+        let automata = automata(&["q0", "q1", "q2"], &["a", "b"], &["z0", "A"], &["q2"],
+                                &["q0 a z0 z0A q0", "q0 a A AA q0", "q0 b A E q1", "q1 b A E q1", "q1 E z0 E q2"]);
+        assert!(automata.is_deterministic());
+        assert!(automata.run_deterministic("aabb"));
+        assert!(!automata.run_deterministic("aab"));
+        assert!(!automata.run_deterministic("abb"));
+    }
+
+    #[test]
+    fn final_state_automata_from_a_file_accepts_a_n_b_n() {
+        let automata = read_automata("src/resources/dfa_7.txt");
+        assert_eq!(automata.acceptance, Acceptance::FinalState);
+        for (word, expected) in [("", true), ("ab", true), ("aaabbb", true), ("aab", false), ("abab", false)] {
+            assert_eq!(automata.accepts_word(word), expected, "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn acceptance_mode_is_read_from_the_file() {
+        let transitions = ["q0 a z0 z0A q0", "q0 a A AA q0", "q0 b A E q1", "q1 b A E q1", "q1 E z0 E q1"];
+        let mut by_empty_stack = automata(&["q0", "q1"], &["a", "b"], &["z0", "A"], &[], &transitions);
+        by_empty_stack.acceptance = Acceptance::EmptyStack;
+        let path = std::env::temp_dir().join(format!("{}_empty_stack_pda.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        by_empty_stack.write_automata(path).unwrap();
+        let read = read_automata(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(read.acceptance, Acceptance::EmptyStack);
+        assert!(read.accepts_word("aabb"));
+        assert!(!read.accepts_word("aab"));
+
+        // Without final states an automata that accepts by final state accepts nothing
+        let by_final_state = automata(&["q0", "q1"], &["a", "b"], &["z0", "A"], &[], &transitions);
+        assert!(!by_final_state.accepts_word("aabb"));
+        assert!(!by_final_state.search_configurations("aabb"));
+    }
+
+    #[test]
+    fn conflicts_are_found_for_both_kinds() {
+        let automata = automata(&["q0", "q1"], &["a", "b"], &["z0"], &["q1"],
+                                &["q0 a z0 z0 q0", "q0 a z0 E q1", "q0 E z0 z0 q1", "q0 b z0 z0 q0", "q1 a z0 z0 q1"]);
+        let conflicts: Vec<(usize, usize, &str)> = automata.find_conflicts().iter()
+            .map(|conflict| (conflict.first, conflict.second, conflict.reason))
+            .collect();
+        assert_eq!(conflicts, vec![
+            (0, 1, "same state, input and stack top"),
+            (0, 2, "epsilon transition next to an input transition"),
+            (1, 2, "epsilon transition next to an input transition"),
+            (2, 3, "epsilon transition next to an input transition"),
+        ]);
+        assert!(!automata.is_deterministic());
+        assert!(automata.find_conflicts().iter().all(|conflict| conflict.first_line.is_none() && conflict.second_line.is_none()));
+    }
+
+    #[test]
+    fn conflicts_point_to_the_lines_of_the_file() {
+        let automata = read_automata("src/resources/dfa_8.txt");
+        let lines: Vec<(Option<usize>, Option<usize>)> = automata.find_conflicts().iter()
+            .map(|conflict| (conflict.first_line, conflict.second_line))
+            .collect();
+        assert_eq!(lines, vec![(Some(7), Some(13)), (Some(8), Some(13)), (Some(9), Some(14)), (Some(10), Some(15)),
+                               (Some(11), Some(14)), (Some(12), Some(15))]);
+    }
+
+    fn words(alphabet: &[&str], max_length: usize) -> Vec<String> {
+        let mut words = vec![String::new()];
+        let mut last = vec![String::new()];
+        for _ in 0..max_length {
+            last = last.iter().flat_map(|word| alphabet.iter().map(move |letter| format!("{}{}", word, letter))).collect();
+            words.extend(last.iter().cloned());
+        }
+        words
+    }
+
+    #[test]
+    fn triple_construction_derives_the_words_of_the_automata() {
+        // w w^R, the last move pops z0, so the stack is empty exactly when q2 is reached
+        let automata = read_automata("src/resources/dfa_8.txt");
+        let grammar = automata.to_context_free_grammar();
+        assert_eq!(grammar.start_symbol(), "S");
+        assert!(grammar.productions().contains(&("S".to_string(), vec!["[q0,z0,q2]".to_string()])));
+        assert!(grammar.productions().contains(&("[q1,z0,q2]".to_string(), Vec::new())));
+        assert!(grammar.validate().is_empty());
+
+        let grammar = grammar.to_chomsky_normal_form();
+        for word in words(&["a", "b"], 6) {
+            let expected = word.len() % 2 == 0 && word.chars().rev().collect::<String>() == word;
+            assert_eq!(automata.accepts_word(&word), expected, "{:?}", word);
+            assert_eq!(grammar.cyk(&word).is_accepted(), expected, "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn grammar_to_automata_and_back_derives_the_same_words() {
+        for filename in ["src/resources/cfg_1.txt", "src/resources/cfg_3.txt"] {
+            let grammar = read_grammar(filename);
+            let automata = StackAutomata::from_grammar(&grammar).unwrap();
+            let original = grammar.to_chomsky_normal_form();
+            let round_trip = automata.to_context_free_grammar().to_chomsky_normal_form();
+            assert!(original.cyk("aabb").is_accepted() && !original.cyk("aab").is_accepted());
+            for word in words(&["a", "b"], 6) {
+                let expected = original.cyk(&word).is_accepted();
+                assert_eq!(automata.accepts_word(&word), expected, "{} {:?}", filename, word);
+                assert_eq!(round_trip.cyk(&word).is_accepted(), expected, "{} {:?}", filename, word);
+            }
+        }
+    }
+
+    #[test]
+    fn grammar_symbols_that_prefix_each_other_or_are_e_stay_apart() {
+        // E derives aba or the empty word, A is a prefix of AB
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<String>>();
+        let mut grammar = ContextFreeGrammar::new(strings(&["a", "b"]), strings(&["S", "E", "A", "B", "AB"]), "S".to_string());
+        for (left, right) in [("S", vec!["E", "E"]), ("E", vec!["B", "A"]), ("E", vec![]), ("A", vec!["a"]),
+                              ("B", vec!["AB", "b"]), ("AB", vec!["a"])] {
+            grammar.add_production(left, strings(&right));
+        }
+        let automata = StackAutomata::from_grammar(&grammar).unwrap();
+        for word in words(&["a", "b"], 6) {
+            let expected = ["", "aba", "abaaba"].contains(&word.as_str());
+            assert_eq!(automata.accepts_word(&word), expected, "{:?}", word);
+        }
+
+        let path = std::env::temp_dir().join(format!("{}_prefix_symbols_pda.txt", std::process::id()));
+        assert!(automata.write_automata(path.to_str().unwrap()).is_err());
+
+        let with_e_terminal = ContextFreeGrammar::new(strings(&["E"]), strings(&["S"]), "S".to_string());
+        assert!(StackAutomata::from_grammar(&with_e_terminal).is_err());
+    }
+}